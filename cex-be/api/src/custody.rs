@@ -0,0 +1,40 @@
+use std::fmt;
+
+use db::Withdrawal;
+use log::info;
+
+#[derive(Debug)]
+pub struct CustodyError(pub String);
+
+impl fmt::Display for CustodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CustodyError {}
+
+/// Whatever moves approved withdrawals off the exchange. The engine only
+/// learns the outcome, a sent withdrawal burns its hold and a failed one releases it.
+pub trait Custody: Send + Sync {
+    /// Send `withdrawal` on chain, returning its transaction hash. Asked again
+    /// for the same withdrawal_id it must return the first transaction rather
+    /// than send twice, an approval that never reached the engine is retried.
+    fn send(&self, withdrawal: &Withdrawal) -> Result<String, CustodyError>;
+}
+
+/// In-process custody for development and offline runs, nothing leaves the
+/// machine. Addresses starting with `fail` are refused, so both outcomes can be exercised.
+#[derive(Default)]
+pub struct MockCustody;
+
+impl Custody for MockCustody {
+    fn send(&self, withdrawal: &Withdrawal) -> Result<String, CustodyError> {
+        if withdrawal.address.starts_with("fail") {
+            return Err(CustodyError(format!("address {} refused", withdrawal.address)));
+        }
+        let tx_hash = format!("mock-{}", withdrawal.withdrawal_id);
+        info!("Mock custody sent {} {} to {} as {}", withdrawal.amount, withdrawal.asset, withdrawal.address, tx_hash);
+        Ok(tx_hash)
+    }
+}
@@ -0,0 +1,49 @@
+use poem::{http::StatusCode, Response};
+use protocol::messages::MessageToApi;
+use serde_json::json;
+
+/// A request the engine refused with `MessageToApi::ERROR`.
+#[derive(Debug)]
+pub struct EngineError {
+    pub code: String,
+    pub message: String,
+}
+
+impl EngineError {
+    /// The error carried by an engine reply, if it is one.
+    pub fn from_reply(reply: &MessageToApi) -> Option<Self> {
+        match reply {
+            MessageToApi::ERROR(payload) => Some(Self {
+                code: payload.code.clone(),
+                message: payload.message.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self.code.as_str() {
+            "UNKNOWN_MARKET" | "ORDER_NOT_FOUND" | "UNKNOWN_USER" | "WITHDRAWAL_NOT_FOUND" => StatusCode::NOT_FOUND,
+            "NOT_OWNER" => StatusCode::FORBIDDEN,
+            "INSUFFICIENT_BALANCE" | "UNKNOWN_ASSET" => StatusCode::UNPROCESSABLE_ENTITY,
+            "INVALID_SIDE" | "INVALID_ORDER" | "INVALID_MARKET" | "INVALID_DEPOSIT" | "INVALID_WITHDRAWAL" => StatusCode::BAD_REQUEST,
+            "MARKET_UNAVAILABLE" | "DUPLICATE_CLIENT_ORDER_ID" => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<EngineError> for poem::Error {
+    fn from(error: EngineError) -> Self {
+        let body = json!({
+            "error": error.message,
+            "code": error.code,
+        });
+        poem::Error::from_response(
+            Response::builder()
+                .status(error.status())
+                .content_type("application/json")
+                .body(body.to_string()),
+        )
+    }
+}
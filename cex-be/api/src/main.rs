@@ -1,6 +1,8 @@
 use poem::{listener::TcpListener, EndpointExt, Route, Server, middleware::Cors};
 
-use crate::{redismanager::RedisManager, routes::{depth, klines, order, ticker, trades, auth}};
+use std::sync::Arc;
+
+use crate::{custody::{Custody, MockCustody}, redismanager::RedisManager, registry::MarketRegistry, routes::{account, admin, depth, klines, markets, order, ticker, trades, auth}};
 mod routes {
     pub mod order;
     pub mod depth;
@@ -8,12 +10,18 @@ mod routes {
     pub mod klines;
     pub mod ticker;
     pub mod auth;
+    pub mod account;
+    pub mod admin;
+    pub mod markets;
 }
 mod types;
+mod engine_error;
 mod redismanager;
 mod auth_service;
 mod middleware;
 mod validation;
+mod custody;
+mod registry;
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
@@ -31,6 +39,12 @@ async fn main() -> Result<(), std::io::Error> {
 
     log::info!("Connected to Redis successfully");
 
+    let pool = db::establish_connection();
+    let registry = MarketRegistry::load(pool.clone()).expect("failed to load the market registry");
+
+    // no real custody yet, withdrawals go through the in-process mock
+    let custody: Arc<dyn Custody> = Arc::new(MockCustody::default());
+
     let app = Route::new()
                     // Public routes (no authentication required)
                     .nest("/api/v1/auth", auth::auth_routes())
@@ -38,10 +52,17 @@ async fn main() -> Result<(), std::io::Error> {
                     .nest("/api/v1/trades", trades::trade_routes())
                     .nest("/api/v1/klines", klines::klines_routes())
                     .nest("/api/v1/tickers", ticker::ticker_routes())
+                    .nest("/api/v1/markets", markets::markets_routes())
                     // Protected routes (authentication required)
                     .nest("/api/v1/order", order::order_routes())
+                    .nest("/api/v1/account", account::account_routes())
+                    // Admin routes (user listed in ADMIN_USER_IDS)
+                    .nest("/api/v1/admin", admin::admin_routes())
                     .with(Cors::new())
-                    .data(manager);
+                    .data(manager)
+                    .data(pool)
+                    .data(registry)
+                    .data(custody);
 
     log::info!("API routes configured");
     log::info!("Server starting on 0.0.0.0:3000");
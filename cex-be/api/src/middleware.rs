@@ -5,6 +5,16 @@ pub fn extract_claims(request: &Request) -> Option<&Claims> {
     request.extensions().get::<Claims>()
 }
 
+/// Claims of an authenticated user listed in `ADMIN_USER_IDS` (comma separated).
+pub fn extract_admin(request: &Request) -> Option<&Claims> {
+    let claims = extract_claims(request)?;
+    let admins = std::env::var("ADMIN_USER_IDS").unwrap_or_default();
+    admins
+        .split(',')
+        .any(|admin| admin.trim() == claims.user_id)
+        .then_some(claims)
+}
+
 pub fn verify_token(request: &mut Request) -> Result<&Claims, String> {
     let auth_header = request
         .headers()
@@ -6,7 +6,7 @@ use uuid::Uuid;
 use std::sync::Arc;
 use futures_util::StreamExt;
 
-use crate::types::MessageToEngine;
+use protocol::{channels, messages::{MessageFromApi, MessageToApi, ProcessInput}, queues};
 
 
 pub struct RedisManager {
@@ -26,22 +26,23 @@ impl RedisManager {
         Uuid::new_v4().to_string()
     }
 
-    pub async fn send_and_await(&self, msg: MessageToEngine) -> redis::RedisResult<String> {
+    pub async fn send_and_await(&self, message: MessageFromApi) -> redis::RedisResult<MessageToApi> {
         let id = self.get_random_client_id();
         let (mut sink, mut stream) = self.client.get_async_pubsub().await?.split();
-        sink.subscribe(&id).await?;
+        // subscribe before pushing so the reply can't arrive first
+        sink.subscribe(channels::api_response(&id)).await?;
 
         {
             let mut publisher = self.publisher.lock().await;
-            // Serialize msg to a String before pushing to Redis
-            let serialized_msg = serde_json::to_string(&msg)
+            let serialized_msg = protocol::encode(&ProcessInput { message, client_id: id, timestamp: 0 })
                 .map_err(|e| redis::RedisError::from((redis::ErrorKind::TypeError, "Serialization error", e.to_string())))?;
-            publisher.lpush::<_, _, ()>("message", serialized_msg).await?;
+            publisher.lpush::<_, _, ()>(queues::ENGINE_INPUT, serialized_msg).await?;
         }
 
         if let Some(message) = stream.next().await {
             let payload: String = message.get_payload()?;
-            return Ok(payload);
+            return protocol::decode(&payload)
+                .map_err(|e| redis::RedisError::from((redis::ErrorKind::TypeError, "Invalid engine reply", e.to_string())));
         }
 
         Err(redis::RedisError::from((
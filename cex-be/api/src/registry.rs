@@ -0,0 +1,34 @@
+use std::{error::Error, sync::{Arc, RwLock}};
+
+use db::{markets::load_markets, DbPool};
+
+use crate::validation::OrderValidator;
+
+/// The market registry as orders are validated against it, loaded once and
+/// reloaded whenever a market is listed or changes status through the API.
+pub struct MarketRegistry {
+    pool: DbPool,
+    validator: RwLock<Arc<OrderValidator>>,
+}
+
+impl MarketRegistry {
+    pub fn load(pool: DbPool) -> Result<Arc<Self>, Box<dyn Error>> {
+        let validator = Self::read(&pool)?;
+        Ok(Arc::new(Self { pool, validator: RwLock::new(validator) }))
+    }
+
+    pub fn validator(&self) -> Arc<OrderValidator> {
+        self.validator.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn refresh(&self) -> Result<(), Box<dyn Error>> {
+        let validator = Self::read(&self.pool)?;
+        *self.validator.write().unwrap_or_else(|e| e.into_inner()) = validator;
+        Ok(())
+    }
+
+    fn read(pool: &DbPool) -> Result<Arc<OrderValidator>, Box<dyn Error>> {
+        let mut conn = pool.get()?;
+        Ok(Arc::new(OrderValidator::new(load_markets(&mut conn)?)))
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use poem::{get, handler, web::{Data, Json, Query}, Route, Result, error::InternalServerError};
+use rust_decimal::Decimal;
+use serde_json::json;
+use log::{info, warn, error};
+use validator::Validate;
+
+use db::{DbPool, fee_tiers::{trailing_volume, window_start}, withdrawals::user_withdrawals};
+use protocol::{fee_tiers, messages::{GetBalancesData, MessageFromApi, MessageToApi, WithdrawData}};
+
+use crate::{engine_error::EngineError, redismanager::RedisManager, types::{PortfolioQuery, Withdraw}, middleware::extract_claims};
+
+// /fees get
+#[handler]
+async fn get_fees(Data(pool): Data<&DbPool>, request: &poem::Request) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    info!("Getting fee tier for user: {}", claims.user_id);
+
+    let mut conn = pool.get()
+        .map_err(|e| InternalServerError(e))?;
+    let volume = trailing_volume(&mut conn, &claims.user_id, window_start())
+        .map_err(|e| InternalServerError(e))?;
+
+    // the engine picks this tier up on the next fee tier job run
+    let tier = fee_tiers::tier_for_volume(volume);
+    let next_tier = fee_tiers::next_tier(tier.level).map(|next| json!({
+        "tier": next.level,
+        "discount": next.discount,
+        "min_volume": next.min_volume,
+        "volume_needed": next.min_volume - volume,
+    }));
+
+    Ok(Json(json!({
+        "tier": tier.level,
+        "discount": tier.discount,
+        "volume_30d": volume,
+        "next_tier": next_tier,
+    })))
+}
+
+// /balances get
+#[handler]
+async fn get_balances(
+    Data(manager): Data<&Arc<RedisManager>>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    info!("Getting balances for user: {}", claims.user_id);
+
+    query_balances(manager, &claims.user_id, None).await
+}
+
+// /portfolio get
+#[handler]
+async fn get_portfolio(
+    Data(manager): Data<&Arc<RedisManager>>,
+    Query(query): Query<PortfolioQuery>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    let quote = query.quote.unwrap_or_else(|| "USD".to_string());
+    info!("Getting portfolio in {} for user: {}", quote, claims.user_id);
+
+    if quote.is_empty() || quote.len() > 16 || !quote.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        warn!("Invalid quote asset: {}", quote);
+        return Ok(Json(json!({
+            "error": "Invalid quote asset. Expected an asset symbol (e.g., USD)"
+        })));
+    }
+
+    query_balances(manager, &claims.user_id, Some(quote)).await
+}
+
+async fn query_balances(manager: &Arc<RedisManager>, user_id: &str, quote_asset: Option<String>) -> Result<Json<serde_json::Value>> {
+    let response = manager
+        .send_and_await(MessageFromApi::GET_BALANCES(GetBalancesData {
+            user_id: user_id.to_string(),
+            quote_asset,
+        }));
+
+    match response.await {
+        Ok(MessageToApi::BALANCES(balances)) => Ok(Json(json!({
+            "balances": balances.balances,
+            "portfolio": balances.portfolio,
+        }))),
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected balance query for user {}: {:?}", user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            error!("Unexpected reply to a balance query: {:?}", response);
+            Ok(Json(json!({
+                "error": "Failed to get balances"
+            })))
+        }
+        Err(e) => {
+            error!("Failed to get balances for user {}: {}", user_id, e);
+            Ok(Json(json!({
+                "error": "Failed to get balances",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// /withdrawals post
+#[handler]
+async fn withdraw(
+    Data(manager): Data<&Arc<RedisManager>>,
+    Json(payload): Json<Withdraw>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    info!("Withdrawing {} {} for user: {}", payload.amount, payload.asset, claims.user_id);
+
+    if let Err(validation_errors) = payload.validate() {
+        warn!("Withdrawal validation failed for user {}: {:?}", claims.user_id, validation_errors);
+        return Ok(Json(json!({
+            "error": "Validation failed",
+            "details": validation_errors.field_errors()
+        })));
+    }
+
+    if payload.amount <= Decimal::ZERO {
+        warn!("Invalid withdrawal amount: {}", payload.amount);
+        return Ok(Json(json!({
+            "error": "Amount must be greater than 0"
+        })));
+    }
+
+    // the engine holds the funds, the withdrawal row follows on the db queue
+    let response = manager
+        .send_and_await(MessageFromApi::WITHDRAW(WithdrawData {
+            withdrawal_id: payload.withdrawal_id.clone(),
+            user_id: claims.user_id.clone(),
+            asset: payload.asset.clone(),
+            amount: payload.amount,
+            address: payload.address.clone(),
+        }));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected withdrawal for user {}: {:?}", claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            Ok(Json(json!({
+                "success": true,
+                "message": "Withdrawal pending approval",
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to withdraw for user {}: {}", claims.user_id, e);
+            Ok(Json(json!({
+                "error": "Failed to withdraw",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// /withdrawals get
+#[handler]
+async fn get_withdrawals(Data(pool): Data<&DbPool>, request: &poem::Request) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    let mut conn = pool.get()
+        .map_err(|e| InternalServerError(e))?;
+    let withdrawals = user_withdrawals(&mut conn, &claims.user_id)
+        .map_err(|e| InternalServerError(e))?;
+
+    Ok(Json(json!({ "withdrawals": withdrawals })))
+}
+
+pub fn account_routes() -> Route {
+    Route::new()
+        .at("/fees", get(get_fees))
+        .at("/balances", get(get_balances))
+        .at("/portfolio", get(get_portfolio))
+        .at("/withdrawals", get(get_withdrawals).post(withdraw))
+}
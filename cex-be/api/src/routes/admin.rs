@@ -0,0 +1,374 @@
+use std::sync::Arc;
+
+use poem::{get, handler, post, web::{Data, Json}, Route, Result, error::InternalServerError, http::StatusCode};
+use serde_json::json;
+use log::{info, warn, error};
+
+use db::{DbPool, markets::{update_status, upsert_market}, withdrawals::{load_withdrawal, transition}, Withdrawal};
+use diesel::PgConnection;
+use protocol::messages::{MessageFromApi, MessageToApi, SetMarketStatusData, SettleWithdrawalData, WithdrawalStatus, ONRAMPDATA};
+use rust_decimal::Decimal;
+use validator::Validate;
+
+use crate::{custody::Custody, engine_error::EngineError, redismanager::RedisManager, registry::MarketRegistry, types::{Deposit, MarketConfig, SetMarketStatus, WithdrawalAction}, middleware::{extract_admin, extract_claims}, validation::validate_market_format};
+
+// /markets post
+#[handler]
+async fn add_market(
+    Data(manager): Data<&Arc<RedisManager>>,
+    Data(pool): Data<&DbPool>,
+    Data(registry): Data<&Arc<MarketRegistry>>,
+    Json(payload): Json<MarketConfig>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    if extract_claims(request).is_none() {
+        return Err(poem::Error::from_string("Authentication required", StatusCode::UNAUTHORIZED));
+    }
+    let claims = extract_admin(request)
+        .ok_or_else(|| poem::Error::from_string("Admin access required", StatusCode::FORBIDDEN))?;
+
+    let market = payload.ticker();
+    info!("Admin {} listing {}", claims.user_id, market);
+
+    if !validate_market_format(&market) {
+        warn!("Invalid market format: {}", market);
+        return Ok(Json(json!({
+            "error": "Invalid market format. Expected format: BASE-QUOTE (e.g., BTC-USD)"
+        })));
+    }
+
+    // the engine has the final say on the config, the registry records what it listed
+    let response = manager.send_and_await(MessageFromApi::ADD_MARKET(payload));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected listing of {}: {:?}", market, engine_error);
+                return Err(engine_error.into());
+            }
+            if let MessageToApi::MARKET_ADDED(listed) = &response {
+                let mut conn = pool.get()
+                    .map_err(|e| InternalServerError(e))?;
+                upsert_market(&mut conn, listed)
+                    .map_err(|e| InternalServerError(e))?;
+                refresh_registry(registry);
+            }
+            Ok(Json(json!({
+                "success": true,
+                "message": "Market listed",
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to list {}: {}", market, e);
+            Ok(Json(json!({
+                "error": "Failed to list market",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// /markets/status post
+#[handler]
+async fn set_market_status(
+    Data(manager): Data<&Arc<RedisManager>>,
+    Data(pool): Data<&DbPool>,
+    Data(registry): Data<&Arc<MarketRegistry>>,
+    Json(payload): Json<SetMarketStatus>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    if extract_claims(request).is_none() {
+        return Err(poem::Error::from_string("Authentication required", StatusCode::UNAUTHORIZED));
+    }
+    let claims = extract_admin(request)
+        .ok_or_else(|| poem::Error::from_string("Admin access required", StatusCode::FORBIDDEN))?;
+
+    info!("Admin {} setting {} to {}", claims.user_id, payload.market, payload.status.as_str());
+
+    if !validate_market_format(&payload.market) {
+        warn!("Invalid market format: {}", payload.market);
+        return Ok(Json(json!({
+            "error": "Invalid market format. Expected format: BASE-QUOTE (e.g., BTC-USD)"
+        })));
+    }
+
+    let response = manager
+        .send_and_await(
+            MessageFromApi::SET_MARKET_STATUS(SetMarketStatusData {
+                market: payload.market.clone(),
+                status: payload.status,
+            }));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected status change of {}: {:?}", payload.market, engine_error);
+                return Err(engine_error.into());
+            }
+            let mut conn = pool.get()
+                .map_err(|e| InternalServerError(e))?;
+            if !update_status(&mut conn, &payload.market, payload.status).map_err(|e| InternalServerError(e))? {
+                warn!("{} is not in the market registry", payload.market);
+            }
+            refresh_registry(registry);
+            Ok(Json(json!({
+                "success": true,
+                "message": "Market status updated",
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to set status of {}: {}", payload.market, e);
+            Ok(Json(json!({
+                "error": "Failed to set market status",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// orders are validated against the cached registry, it has to see the change
+fn refresh_registry(registry: &MarketRegistry) {
+    if let Err(e) = registry.refresh() {
+        error!("Failed to reload the market registry: {}", e);
+    }
+}
+
+// /deposits post
+#[handler]
+async fn add_deposit(
+    Data(manager): Data<&Arc<RedisManager>>,
+    Json(payload): Json<Deposit>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    if extract_claims(request).is_none() {
+        return Err(poem::Error::from_string("Authentication required", StatusCode::UNAUTHORIZED));
+    }
+    let claims = extract_admin(request)
+        .ok_or_else(|| poem::Error::from_string("Admin access required", StatusCode::FORBIDDEN))?;
+
+    info!("Admin {} crediting {} {} to {} ({})", claims.user_id, payload.amount, payload.asset, payload.user_id, payload.txn_id);
+
+    if let Err(validation_errors) = payload.validate() {
+        warn!("Deposit validation failed: {:?}", validation_errors);
+        return Ok(Json(json!({
+            "error": "Validation failed",
+            "details": validation_errors.field_errors()
+        })));
+    }
+
+    if payload.amount <= Decimal::ZERO {
+        warn!("Invalid deposit amount: {}", payload.amount);
+        return Ok(Json(json!({
+            "error": "Amount must be greater than 0"
+        })));
+    }
+
+    let response = manager
+        .send_and_await(MessageFromApi::ON_RAMP(ONRAMPDATA {
+            amount: payload.amount,
+            user_id: payload.user_id.clone(),
+            txn_id: payload.txn_id.clone(),
+            asset: payload.asset.clone(),
+        }));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected deposit {}: {:?}", payload.txn_id, engine_error);
+                return Err(engine_error.into());
+            }
+            let message = match response {
+                MessageToApi::DEPOSIT_DUPLICATE(_) => "Deposit already credited",
+                _ => "Deposit credited",
+            };
+            Ok(Json(json!({
+                "success": true,
+                "message": message,
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to credit deposit {}: {}", payload.txn_id, e);
+            Ok(Json(json!({
+                "error": "Failed to credit deposit",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// /withdrawals/approve post
+#[handler]
+async fn approve_withdrawal(
+    Data(manager): Data<&Arc<RedisManager>>,
+    Data(pool): Data<&DbPool>,
+    Data(custody): Data<&Arc<dyn Custody>>,
+    Json(payload): Json<WithdrawalAction>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    if extract_claims(request).is_none() {
+        return Err(poem::Error::from_string("Authentication required", StatusCode::UNAUTHORIZED));
+    }
+    let claims = extract_admin(request)
+        .ok_or_else(|| poem::Error::from_string("Admin access required", StatusCode::FORBIDDEN))?;
+
+    info!("Admin {} approving withdrawal {}", claims.user_id, payload.withdrawal_id);
+
+    let mut conn = pool.get()
+        .map_err(|e| InternalServerError(e))?;
+    let withdrawal = load_withdrawal(&mut conn, &payload.withdrawal_id)
+        .map_err(|e| InternalServerError(e))?
+        .ok_or_else(|| poem::Error::from_string("Withdrawal not found", StatusCode::NOT_FOUND))?;
+    let (status, tx_hash) = match WithdrawalStatus::parse(&withdrawal.status) {
+        // only one approval gets past this, a second one or a reject finds it approved
+        Some(WithdrawalStatus::Pending) if transition(&mut conn, &payload.withdrawal_id, WithdrawalStatus::Approved, None).map_err(|e| InternalServerError(e))? => {
+            send_withdrawal(&mut conn, custody.as_ref(), &withdrawal)?
+        }
+        // approved before but custody never answered, custody doesn't send the same withdrawal twice
+        Some(WithdrawalStatus::Approved) => send_withdrawal(&mut conn, custody.as_ref(), &withdrawal)?,
+        // custody answered, the engine may not have heard
+        Some(status @ (WithdrawalStatus::Sent | WithdrawalStatus::Failed)) => (status, withdrawal.tx_hash.clone()),
+        _ => {
+            return Err(poem::Error::from_string(
+                format!("Withdrawal is {}, only pending withdrawals can be approved", withdrawal.status),
+                StatusCode::CONFLICT,
+            ));
+        }
+    };
+    settle_withdrawal(manager, SettleWithdrawalData { withdrawal_id: payload.withdrawal_id.clone(), status, tx_hash }).await
+}
+
+// hand an approved withdrawal to custody and keep the outcome on the row, so
+// approving again settles it in the engine instead of sending it a second time
+fn send_withdrawal(conn: &mut PgConnection, custody: &dyn Custody, withdrawal: &Withdrawal) -> Result<(WithdrawalStatus, Option<String>)> {
+    let (status, tx_hash) = match custody.send(withdrawal) {
+        Ok(tx_hash) => (WithdrawalStatus::Sent, Some(tx_hash)),
+        Err(e) => {
+            warn!("Custody failed to send withdrawal {}: {}", withdrawal.withdrawal_id, e);
+            (WithdrawalStatus::Failed, None)
+        }
+    };
+    transition(conn, &withdrawal.withdrawal_id, status, tx_hash.as_deref()).map_err(|e| InternalServerError(e))?;
+    Ok((status, tx_hash))
+}
+
+// /withdrawals/reject post
+#[handler]
+async fn reject_withdrawal(
+    Data(manager): Data<&Arc<RedisManager>>,
+    Data(pool): Data<&DbPool>,
+    Json(payload): Json<WithdrawalAction>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    if extract_claims(request).is_none() {
+        return Err(poem::Error::from_string("Authentication required", StatusCode::UNAUTHORIZED));
+    }
+    let claims = extract_admin(request)
+        .ok_or_else(|| poem::Error::from_string("Admin access required", StatusCode::FORBIDDEN))?;
+
+    info!("Admin {} rejecting withdrawal {}", claims.user_id, payload.withdrawal_id);
+
+    let mut conn = pool.get()
+        .map_err(|e| InternalServerError(e))?;
+    let withdrawal = load_withdrawal(&mut conn, &payload.withdrawal_id)
+        .map_err(|e| InternalServerError(e))?
+        .ok_or_else(|| poem::Error::from_string("Withdrawal not found", StatusCode::NOT_FOUND))?;
+    // an already cancelled one goes to the engine again, in case the release never got there
+    let cancelled = transition(&mut conn, &payload.withdrawal_id, WithdrawalStatus::Cancelled, None)
+        .map_err(|e| InternalServerError(e))?;
+    if !cancelled && withdrawal.status != WithdrawalStatus::Cancelled.as_str() {
+        return Err(poem::Error::from_string(
+            format!("Withdrawal is {}, only pending withdrawals can be rejected", withdrawal.status),
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    settle_withdrawal(manager, SettleWithdrawalData {
+        withdrawal_id: payload.withdrawal_id.clone(),
+        status: WithdrawalStatus::Cancelled,
+        tx_hash: None,
+    }).await
+}
+
+// burns or releases the hold in the engine, the db row follows on the db queue
+async fn settle_withdrawal(manager: &Arc<RedisManager>, settle_data: SettleWithdrawalData) -> Result<Json<serde_json::Value>> {
+    let withdrawal_id = settle_data.withdrawal_id.clone();
+    let status = settle_data.status;
+    let response = manager.send_and_await(MessageFromApi::SETTLE_WITHDRAWAL(settle_data));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected settling withdrawal {}: {:?}", withdrawal_id, engine_error);
+                return Err(engine_error.into());
+            }
+            Ok(Json(json!({
+                "success": true,
+                "message": format!("Withdrawal {}", status.as_str()),
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to settle withdrawal {}: {}", withdrawal_id, e);
+            Ok(Json(json!({
+                "error": "Failed to settle withdrawal",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// /invariants get
+#[handler]
+async fn check_invariants(
+    Data(manager): Data<&Arc<RedisManager>>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    if extract_claims(request).is_none() {
+        return Err(poem::Error::from_string("Authentication required", StatusCode::UNAUTHORIZED));
+    }
+    let claims = extract_admin(request)
+        .ok_or_else(|| poem::Error::from_string("Admin access required", StatusCode::FORBIDDEN))?;
+
+    info!("Admin {} checking engine invariants", claims.user_id);
+
+    let response = manager.send_and_await(MessageFromApi::CHECK_INVARIANTS);
+
+    match response.await {
+        Ok(MessageToApi::INVARIANTS(report)) => {
+            if !report.violations.is_empty() {
+                error!("Engine invariants broken at input {}: {:?}", report.sequence, report.violations);
+            }
+            Ok(Json(json!({
+                "success": true,
+                "message": if report.violations.is_empty() { "Engine state is consistent" } else { "Engine invariants are broken" },
+                "data": report
+            })))
+        }
+        Ok(response) => {
+            error!("Unexpected reply to an invariant check: {:?}", response);
+            Ok(Json(json!({
+                "error": "Failed to check invariants"
+            })))
+        }
+        Err(e) => {
+            error!("Failed to check invariants: {}", e);
+            Ok(Json(json!({
+                "error": "Failed to check invariants",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+pub fn admin_routes() -> Route {
+    Route::new()
+        .at("/withdrawals/approve", post(approve_withdrawal))
+        .at("/withdrawals/reject", post(reject_withdrawal))
+        .at("/deposits", post(add_deposit))
+        .at("/markets", post(add_market))
+        .at("/markets/status", post(set_market_status))
+        .at("/invariants", get(check_invariants))
+}
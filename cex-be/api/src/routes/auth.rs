@@ -1,12 +1,12 @@
-use poem::{handler, post, web::Json, Route, Result, error::InternalServerError};
+use poem::{handler, post, web::{Data, Json}, Route, Result, error::InternalServerError};
 use serde_json::json;
 use validator::Validate;
-use db::{establish_connection, User as DbUser, users};
+use db::{DbPool, User as DbUser, users};
 use diesel::prelude::*;
 use crate::auth_service::{AuthService, LoginRequest, RegisterRequest, UserInfo};
 
 #[handler]
-async fn register(Json(payload): Json<RegisterRequest>) -> Result<Json<serde_json::Value>> {
+async fn register(Data(pool): Data<&DbPool>, Json(payload): Json<RegisterRequest>) -> Result<Json<serde_json::Value>> {
     if let Err(validation_errors) = payload.validate() {
         return Ok(Json(json!({
             "error": "Validation failed",
@@ -14,7 +14,6 @@ async fn register(Json(payload): Json<RegisterRequest>) -> Result<Json<serde_jso
         })));
     }
 
-    let pool = establish_connection();
     let mut conn = pool.get()
         .map_err(|e| poem::Error::from_string(format!("Database connection error: {}", e), poem::http::StatusCode::INTERNAL_SERVER_ERROR))?;
 
@@ -62,8 +61,7 @@ async fn register(Json(payload): Json<RegisterRequest>) -> Result<Json<serde_jso
         .execute(&mut conn)
         .map_err(|e| poem::Error::from_string(format!("Database insert error: {}", e), poem::http::StatusCode::INTERNAL_SERVER_ERROR))?;
 
-    // Initialize user wallet with $10000 USD
-    initialize_user_wallet(&new_user.id.to_string(), &mut conn)?;
+    // the engine starts new accounts with no balances, funds come in as deposits
 
     let auth_service = AuthService::new();
     let token = auth_service.generate_token(&new_user.id.to_string(), &new_user.email)
@@ -83,7 +81,7 @@ async fn register(Json(payload): Json<RegisterRequest>) -> Result<Json<serde_jso
 }
 
 #[handler]
-async fn login(Json(payload): Json<LoginRequest>) -> Result<Json<serde_json::Value>> {
+async fn login(Data(pool): Data<&DbPool>, Json(payload): Json<LoginRequest>) -> Result<Json<serde_json::Value>> {
     if let Err(validation_errors) = payload.validate() {
         return Ok(Json(json!({
             "error": "Validation failed",
@@ -91,7 +89,6 @@ async fn login(Json(payload): Json<LoginRequest>) -> Result<Json<serde_json::Val
         })));
     }
 
-    let pool = establish_connection();
     let mut conn = pool.get()
         .map_err(|e| poem::Error::from_string(format!("Database connection error: {}", e), poem::http::StatusCode::INTERNAL_SERVER_ERROR))?;
 
@@ -134,21 +131,6 @@ async fn login(Json(payload): Json<LoginRequest>) -> Result<Json<serde_json::Val
     })))
 }
 
-fn initialize_user_wallet(user_id: &str, conn: &mut diesel::PgConnection) -> Result<(), poem::Error> {
-    // Initialize user with $10000 USD balance
-    log::info!("Initializing wallet for user {} with $10000 USD", user_id);
-    
-    // For now, we'll send an on_ramp message to the engine to initialize the wallet
-    // In a production system, you'd want to store balances in the database
-    // and sync with the engine
-    
-    // TODO: Implement proper balance persistence in database
-    // For now, the engine will handle wallet initialization via on_ramp
-    
-    log::info!("Wallet initialization completed for user: {}", user_id);
-    Ok(())
-}
-
 pub fn auth_routes() -> Route {
     Route::new()
         .at("/register", post(register))
@@ -1,22 +1,24 @@
 use poem::{get, handler, web::{Data, Json, Query}, Route};
 use std::sync::Arc;
 
-use crate::{redismanager::RedisManager, types::{DepthQuery, EngineData, MessageToEngine, SymbolData}};
+use protocol::messages::{MessageFromApi, MessageToApi, GETDEPTHDATA};
+
+use crate::{engine_error::EngineError, redismanager::RedisManager, types::DepthQuery};
 
 #[handler]
 async fn depth_order(
     Data(manager): Data<&Arc<RedisManager>>,
     Query(query): Query<DepthQuery>, // depth query is SOL - USDC [Asset - Quote]
-) -> poem::Result<Json<String>> {
+) -> poem::Result<Json<MessageToApi>> {
     let response = manager
-        .send_and_await(MessageToEngine {
-            type_: "GET_DEPTH".to_string(),
-            data: EngineData::Symbol(SymbolData {
-                market: query.symbol.clone().to_string(),
-            }),
-        })
+        .send_and_await(MessageFromApi::GET_DEPTH(GETDEPTHDATA {
+            market: query.symbol.clone().to_string(),
+        }))
         .await
         .map_err(poem::error::InternalServerError)?;
+    if let Some(engine_error) = EngineError::from_reply(&response) {
+        return Err(engine_error.into());
+    }
     println!("all good");
     Ok(Json(response))
 }
@@ -6,7 +6,7 @@ use log::{info, warn, error};
 use validator::Validate;
 
 use crate::{redismanager::RedisManager, types::KlinesQuery};
-use db::{establish_connection, trades};
+use db::{DbPool, trades};
 use diesel::prelude::*;
 use chrono::{NaiveDateTime, Duration};
 
@@ -26,6 +26,7 @@ pub struct KlineData {
 #[handler]
 async fn get_klines(
     Data(_manager): Data<&Arc<RedisManager>>,
+    Data(pool): Data<&DbPool>,
     Query(query): Query<KlinesQuery>
 ) -> Result<Json<serde_json::Value>> {
     info!("Getting klines for market: {}, interval: {}", query.market, query.interval);
@@ -56,7 +57,7 @@ async fn get_klines(
         })));
     }
 
-    match fetch_klines_from_db(&query.market, &query.interval, start_time, end_time).await {
+    match fetch_klines_from_db(pool, &query.market, &query.interval, start_time, end_time).await {
         Ok(klines) => {
             info!("Retrieved {} klines for market: {}", klines.len(), query.market);
             Ok(Json(json!({
@@ -77,12 +78,12 @@ async fn get_klines(
 }
 
 async fn fetch_klines_from_db(
+    pool: &DbPool,
     market: &str,
     interval: &str,
     start_time: i64,
     end_time: i64,
 ) -> Result<Vec<KlineData>, Box<dyn std::error::Error>> {
-    let pool = establish_connection();
     let mut conn = pool.get()?;
 
     let start_dt = time_to_naive_datetime(start_time);
@@ -0,0 +1,20 @@
+use poem::{get, handler, web::{Data, Json}, Route, Result, error::InternalServerError};
+use serde_json::json;
+
+use db::{DbPool, markets::load_markets};
+
+// / get
+#[handler]
+async fn get_markets(Data(pool): Data<&DbPool>) -> Result<Json<serde_json::Value>> {
+    let mut conn = pool.get()
+        .map_err(|e| InternalServerError(e))?;
+    let markets = load_markets(&mut conn)
+        .map_err(|e| InternalServerError(e))?;
+
+    Ok(Json(json!({ "markets": markets })))
+}
+
+pub fn markets_routes() -> Route {
+    Route::new()
+        .at("/", get(get_markets))
+}
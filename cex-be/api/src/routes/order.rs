@@ -1,18 +1,21 @@
 use std::sync::Arc;
 
-use poem::{get, handler, post, web::{Data, Json}, Route, Result, error::InternalServerError, http::StatusCode};
+use poem::{delete, get, handler, post, web::{Data, Json, Query}, Route, Result, http::StatusCode};
 use validator::Validate;
 use serde_json::json;
 use log::{info, warn, error};
 
-use crate::{redismanager::RedisManager, types::{CreateOrder, CreateOrderData, DeleteOrder, DeleteOrderData, EngineData, GetOpenOrder, MessageToEngine}, middleware::extract_claims, validation::{OrderValidator, validate_market_format}};
+use protocol::messages::{AmendOrderData, CancelAllOrdersData, CancelOrderData, CreateOrderData, DeadManSwitchData, GetOrderData, MessageFromApi, MessageToApi, GETOPENORDERS};
+
+use crate::{engine_error::EngineError, redismanager::RedisManager, registry::MarketRegistry, types::{AmendOrder, CancelAllQuery, CreateOrder, DeadManSwitch, DeleteOrder, GetOrderQuery}, middleware::extract_claims, validation::validate_market_format};
 
 // / post
 // / delete
 // /open get
 #[handler]
 async fn create_order(
-    Data(manager): Data<&Arc<RedisManager>>, 
+    Data(manager): Data<&Arc<RedisManager>>,
+    Data(registry): Data<&Arc<MarketRegistry>>,
     Json(payload): Json<CreateOrder>,
     request: &poem::Request,
 ) -> Result<Json<serde_json::Value>> {
@@ -36,35 +39,43 @@ async fn create_order(
         })));
     }
 
-    let validator = OrderValidator::new();
-    if let Err(validation_error) = validator.validate_order(&payload.market, payload.price, payload.quantity) {
+    if let Err(validation_error) = registry.validator().validate_order(&payload) {
         warn!("Order validation failed for user {}: {:?}", claims.user_id, validation_error);
         return Ok(Json(validation_error));
     }
 
-    let min_order_value = 1.0;
-    let order_value = payload.price * payload.quantity;
-    if order_value < min_order_value {
-        warn!("Order value too low: ${}", order_value);
-        return Ok(Json(json!({
-            "error": format!("Minimum order value is ${}", min_order_value)
-        })));
-    }
-
     let response = manager
-        .send_and_await(MessageToEngine {
-            type_: "CREATE_ORDER".to_string(),
-            data: EngineData::Order(CreateOrderData {
+        .send_and_await(MessageFromApi::CREATE_ORDER(CreateOrderData {
                 market: payload.market.clone(),
+                order_type: payload.order_type,
                 price: payload.price,
                 quantity: payload.quantity,
+                quote_quantity: payload.quote_quantity,
+                worst_price: payload.worst_price,
+                trigger_price: payload.trigger_price,
+                time_in_force: payload.time_in_force,
+                reprice_post_only: payload.reprice_post_only,
+                self_trade_prevention: payload.self_trade_prevention,
+                client_order_id: payload.client_order_id.clone(),
+                expire_at: payload.expire_at,
                 side: payload.side,
                 user_id: claims.user_id.clone()
-            })
-        });
+            }));
 
     match response.await {
         Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected order for user {}: {:?}", claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            if let MessageToApi::ORDER_DUPLICATE(existing) = &response {
+                info!("Order {} already open for client_order_id {:?}, user: {}", existing.order_id, existing.client_order_id, claims.user_id);
+                return Ok(Json(json!({
+                    "success": true,
+                    "message": "Order already exists",
+                    "data": response
+                })));
+            }
             info!("Order created successfully for user: {}", claims.user_id);
             Ok(Json(json!({
                 "success": true,
@@ -92,9 +103,10 @@ async fn delete_order(
     let claims = extract_claims(request)
         .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
 
-    info!("Deleting order {} for user: {}", payload.order_id, claims.user_id);
+    let order_ref = payload.order_id.clone().or(payload.client_order_id.clone()).unwrap_or_default();
+    info!("Deleting order {} for user: {}", order_ref, claims.user_id);
 
-    if payload.order_id.is_empty() || payload.market.is_empty() {
+    if order_ref.is_empty() || payload.market.is_empty() {
         warn!("Invalid delete order request for user {}: empty order_id or market", claims.user_id);
         return Ok(Json(json!({
             "error": "Order ID and market are required"
@@ -110,17 +122,20 @@ async fn delete_order(
 
     let response = manager
         .send_and_await(
-            MessageToEngine { 
-                type_: "CANCEL_ORDER".to_string(), 
-                data: EngineData::DeleteOrder(DeleteOrderData {
-                    market: payload.market.clone(),
-                    order_id: payload.order_id.clone()
-                }) 
-            });
+            MessageFromApi::CANCEL_ORDER(CancelOrderData {
+                market: payload.market.clone(),
+                order_id: payload.order_id.clone(),
+                client_order_id: payload.client_order_id.clone(),
+                user_id: claims.user_id.clone()
+            }));
 
     match response.await {
         Ok(response) => {
-            info!("Order {} deleted successfully for user: {}", payload.order_id, claims.user_id);
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected cancel of {} for user {}: {:?}", order_ref, claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            info!("Order {} deleted successfully for user: {}", order_ref, claims.user_id);
             Ok(Json(json!({
                 "success": true,
                 "message": "Order cancelled successfully",
@@ -128,7 +143,7 @@ async fn delete_order(
             })))
         }
         Err(e) => {
-            error!("Failed to delete order {} for user {}: {}", payload.order_id, claims.user_id, e);
+            error!("Failed to delete order {} for user {}: {}", order_ref, claims.user_id, e);
             Ok(Json(json!({
                 "error": "Failed to cancel order",
                 "details": e.to_string()
@@ -137,6 +152,226 @@ async fn delete_order(
     }
 }
 
+#[handler]
+async fn cancel_all_orders(
+    Data(manager): Data<&Arc<RedisManager>>, 
+    Query(query): Query<CancelAllQuery>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    info!("Cancelling all orders for user: {} (market {:?}, side {:?})", claims.user_id, query.market, query.side);
+
+    if let Some(market) = &query.market {
+        if !validate_market_format(market) {
+            warn!("Invalid market format for cancel all: {}", market);
+            return Ok(Json(json!({
+                "error": "Invalid market format. Expected format: BASE-QUOTE (e.g., BTC-USD)"
+            })));
+        }
+    }
+
+    let response = manager
+        .send_and_await(
+            MessageFromApi::CANCEL_ALL_ORDERS(CancelAllOrdersData {
+                user_id: claims.user_id.clone(),
+                market: query.market.clone(),
+                side: query.side,
+            }));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected cancel all for user {}: {:?}", claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            info!("All orders cancelled for user: {}", claims.user_id);
+            Ok(Json(json!({
+                "success": true,
+                "message": "Orders cancelled successfully",
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to cancel all orders for user {}: {}", claims.user_id, e);
+            Ok(Json(json!({
+                "error": "Failed to cancel orders",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+#[handler]
+async fn set_dead_man_switch(
+    Data(manager): Data<&Arc<RedisManager>>, 
+    Json(payload): Json<DeadManSwitch>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    info!("Setting dead man's switch to {}s for user: {}", payload.timeout_secs, claims.user_id);
+
+    if let Err(validation_errors) = payload.validate() {
+        warn!("Dead man's switch validation failed for user {}: {:?}", claims.user_id, validation_errors);
+        return Ok(Json(json!({
+            "error": "Validation failed",
+            "details": validation_errors.field_errors()
+        })));
+    }
+
+    let response = manager
+        .send_and_await(
+            MessageFromApi::SET_DEAD_MAN_SWITCH(DeadManSwitchData {
+                user_id: claims.user_id.clone(),
+                timeout_secs: payload.timeout_secs,
+            }));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected dead man's switch for user {}: {:?}", claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            let message = if payload.timeout_secs == 0 { "Dead man's switch disarmed" } else { "Dead man's switch armed" };
+            Ok(Json(json!({
+                "success": true,
+                "message": message,
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to set dead man's switch for user {}: {}", claims.user_id, e);
+            Ok(Json(json!({
+                "error": "Failed to set dead man's switch",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+#[handler]
+async fn amend_order(
+    Data(manager): Data<&Arc<RedisManager>>, 
+    Json(payload): Json<AmendOrder>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    let order_ref = payload.order_id.clone().or(payload.client_order_id.clone()).unwrap_or_default();
+    info!("Amending order {} for user: {}", order_ref, claims.user_id);
+
+    if order_ref.is_empty() {
+        return Ok(Json(json!({
+            "error": "Order ID or client order ID is required"
+        })));
+    }
+
+    if payload.quantity.is_none() && payload.price.is_none() {
+        return Ok(Json(json!({
+            "error": "Quantity or price is required"
+        })));
+    }
+
+    if !validate_market_format(&payload.market) {
+        warn!("Invalid market format for amend order: {}", payload.market);
+        return Ok(Json(json!({
+            "error": "Invalid market format. Expected format: BASE-QUOTE (e.g., BTC-USD)"
+        })));
+    }
+
+    let response = manager
+        .send_and_await(
+            MessageFromApi::AMEND_ORDER(AmendOrderData {
+                order_id: payload.order_id.clone(),
+                client_order_id: payload.client_order_id.clone(),
+                market: payload.market.clone(),
+                user_id: claims.user_id.clone(),
+                quantity: payload.quantity,
+                price: payload.price,
+            }));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected amend of {} for user {}: {:?}", order_ref, claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            info!("Order {} amended successfully for user: {}", order_ref, claims.user_id);
+            Ok(Json(json!({
+                "success": true,
+                "message": "Order amended successfully",
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to amend order {} for user {}: {}", order_ref, claims.user_id, e);
+            Ok(Json(json!({
+                "error": "Failed to amend order",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+#[handler]
+async fn get_order(
+    Data(manager): Data<&Arc<RedisManager>>, 
+    Query(query): Query<GetOrderQuery>,
+    request: &poem::Request,
+) -> Result<Json<serde_json::Value>> {
+    let claims = extract_claims(request)
+        .ok_or_else(|| poem::Error::from_string("Authentication required", poem::http::StatusCode::UNAUTHORIZED))?;
+
+    let order_ref = query.order_id.clone().or(query.client_order_id.clone()).unwrap_or_default();
+    info!("Getting order {} for user: {}", order_ref, claims.user_id);
+
+    if order_ref.is_empty() {
+        return Ok(Json(json!({
+            "error": "Order ID or client order ID is required"
+        })));
+    }
+
+    if !validate_market_format(&query.market) {
+        warn!("Invalid market format for get order: {}", query.market);
+        return Ok(Json(json!({
+            "error": "Invalid market format. Expected format: BASE-QUOTE (e.g., BTC-USD)"
+        })));
+    }
+
+    let response = manager
+        .send_and_await(
+            MessageFromApi::GET_ORDER(GetOrderData {
+                order_id: query.order_id.clone(),
+                client_order_id: query.client_order_id.clone(),
+                market: query.market.clone(),
+                user_id: claims.user_id.clone()
+            }));
+
+    match response.await {
+        Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected order lookup of {} for user {}: {:?}", order_ref, claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
+            Ok(Json(json!({
+                "success": true,
+                "data": response
+            })))
+        }
+        Err(e) => {
+            error!("Failed to get order {} for user {}: {}", order_ref, claims.user_id, e);
+            Ok(Json(json!({
+                "error": "Failed to retrieve order",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
 #[handler]
 async fn get_open_orders(
     Data(manager): Data<&Arc<RedisManager>>, 
@@ -166,16 +401,17 @@ async fn get_open_orders(
 
     let response = manager
         .send_and_await(
-            MessageToEngine { 
-                type_: "GET_OPEN_ORDERS".to_string(), 
-                data: EngineData::OpenOrder(GetOpenOrder {
-                    user_id: claims.user_id.clone(),
-                    market: market.clone()
-                }) 
-            });
+            MessageFromApi::GET_OPEN_ORDERS(GETOPENORDERS {
+                user_id: claims.user_id.clone(),
+                market: market.clone()
+            }));
 
     match response.await {
         Ok(response) => {
+            if let Some(engine_error) = EngineError::from_reply(&response) {
+                warn!("Engine rejected open orders request for user {}: {:?}", claims.user_id, engine_error);
+                return Err(engine_error.into());
+            }
             info!("Open orders retrieved successfully for user: {}", claims.user_id);
             Ok(Json(json!({
                 "success": true,
@@ -196,6 +432,8 @@ async fn get_open_orders(
 
 pub fn order_routes() -> Route {
     Route::new()
-        .at("/", post(create_order).delete(delete_order))
+        .at("/", get(get_order).post(create_order).delete(delete_order).patch(amend_order))
+        .at("/all", delete(cancel_all_orders))
+        .at("/dead-man-switch", post(set_dead_man_switch))
         .at("/open", get(get_open_orders))
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
-use poem::{get, handler, web::{Json, Query}, Route, Result, error::InternalServerError};
+use poem::{get, handler, web::{Data, Json, Query}, Route, Result, error::InternalServerError};
 use serde::{Serialize, Deserialize};
-use db::{establish_connection, trades, orders};
+use db::{DbPool, trades, schema::orders};
 use diesel::prelude::*;
 use diesel::query_dsl::QueryDsl;
 use diesel::expression_methods::ExpressionMethods;
@@ -28,23 +28,22 @@ pub struct TickerResponse {
 
 
 #[handler]
-async fn get_ticker(Query(params): Query<TickerParams>) -> Result<Json<TickerResponse>> {
+async fn get_ticker(Data(pool): Data<&DbPool>, Query(params): Query<TickerParams>) -> Result<Json<TickerResponse>> {
     let market = params.market.unwrap_or_else(|| "BTCUSDT".to_string());
-    let ticker = get_ticker_data(market).await?;
+    let ticker = get_ticker_data(pool, market).await?;
     Ok(Json(ticker))
 }
 
 #[handler]
-async fn get_all_tickers() -> Result<Json<Vec<TickerResponse>>> {
+async fn get_all_tickers(Data(pool): Data<&DbPool>) -> Result<Json<Vec<TickerResponse>>> {
     // For now, return a single ticker for BTCUSDT
     // In a real implementation, you'd query all markets
     let _params = TickerParams { market: Some("BTCUSDT".to_string()) };
-    let ticker = get_ticker_data("BTCUSDT".to_string()).await?;
+    let ticker = get_ticker_data(pool, "BTCUSDT".to_string()).await?;
     Ok(Json(vec![ticker]))
 }
 
-async fn get_ticker_data(market: String) -> Result<TickerResponse> {
-    let pool = establish_connection();
+async fn get_ticker_data(pool: &DbPool, market: String) -> Result<TickerResponse> {
     let mut conn = pool.get()
         .map_err(|e| InternalServerError(e))?;
 
@@ -1,6 +1,6 @@
-use poem::{get, handler, web::{Json, Query}, Route, Result, error::InternalServerError};
+use poem::{get, handler, web::{Data, Json, Query}, Route, Result, error::InternalServerError};
 use serde::{Serialize, Deserialize};
-use db::{establish_connection, Trade, trades};
+use db::{DbPool, Trade, trades};
 use diesel::prelude::*;
 use diesel::query_dsl::QueryDsl;
 use diesel::expression_methods::ExpressionMethods;
@@ -18,9 +18,7 @@ pub struct TradesResponse {
 }
 
 #[handler]
-async fn get_trades(Query(params): Query<TradesParams>) -> Result<Json<TradesResponse>> {
-    // Establish database connection
-    let pool = establish_connection();
+async fn get_trades(Data(pool): Data<&DbPool>, Query(params): Query<TradesParams>) -> Result<Json<TradesResponse>> {
     let mut conn = pool.get()
         .map_err(|e| InternalServerError(e))?;
 
@@ -33,7 +31,7 @@ async fn get_trades(Query(params): Query<TradesParams>) -> Result<Json<TradesRes
     }
     
     // Order by timestamp descending (most recent first)
-    query = query.order(trades::timestamp.desc());
+    query = query.order((trades::timestamp.desc(), trades::trade_id.desc()));
     
     // Apply limit (default to 100 if not specified)
     let limit = params.limit.unwrap_or(100).min(1000); // Cap at 1000
@@ -1,97 +1,149 @@
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use time::Time;
 use validator::Validate;
 
+// shared with the engine, the HTTP body uses the same spellings as the wire
+pub use protocol::messages::{MarketConfig, MarketStatus, OrderType, SelfTradePrevention, Side, TimeInForce};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
-#[serde(rename_all = "snake_case")] 
-pub enum Side {
-    Buy,
-    Sell
-}
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct CreateOrder {
     #[validate(length(min = 7, max = 20))]
     pub market: String,
-    #[validate(range(min = 0.01))]
-    pub price: f64,
-    #[validate(range(min = 0.00000001))]
-    pub quantity: f64,
+    #[serde(default)]
+    pub order_type: OrderType,
+    pub price: Option<Decimal>,
+    pub quantity: Option<Decimal>,
+    // market buys can spend a quote amount instead of naming a quantity
+    pub quote_quantity: Option<Decimal>,
+    // market orders stop sweeping the book past this price
+    pub worst_price: Option<Decimal>,
+    // stop orders activate once the last trade price reaches this
+    pub trigger_price: Option<Decimal>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    // post-only orders that would cross get moved behind the touch instead of rejected
+    #[serde(default)]
+    pub reprice_post_only: bool,
+    #[serde(default)]
+    pub self_trade_prevention: SelfTradePrevention,
+    // retrying with the same id returns the open order instead of placing another
+    #[validate(length(min = 1, max = 64))]
+    pub client_order_id: Option<String>,
+    // good-til-time, unix ms after which the engine expires whatever is still open
+    pub expire_at: Option<u64>,
     pub side: Side,
 }
 
+// either id names the order
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteOrder {
-    pub order_id: String,
+    pub order_id: Option<String>,
+    pub client_order_id: Option<String>,
     pub market: String
 }
 
-// market, price, quantity, side, userId
-
+// a smaller quantity at the same price keeps queue priority, anything else loses it
 #[derive(Debug, Serialize, Deserialize)]
-pub struct DepthQuery {
-    pub symbol: String
+pub struct AmendOrder {
+    pub order_id: Option<String>,
+    pub client_order_id: Option<String>,
+    pub market: String,
+    // new total quantity, including what already filled
+    pub quantity: Option<Decimal>,
+    pub price: Option<Decimal>,
 }
 
+// narrows DELETE /order/all, nothing given cancels everything
 #[derive(Debug, Serialize, Deserialize)]
-pub struct KlinesQuery {
-    pub market: String,
-    pub interval: String,
-    #[serde(rename = "startTime")]
-    pub start_time: i64,
-    #[serde(rename = "endTime")]
-    pub end_time: i64
+pub struct CancelAllQuery {
+    pub market: Option<String>,
+    pub side: Option<Side>,
 }
 
-
-#[derive(Serialize, Deserialize)]
-pub struct SymbolData {
-    pub market: String
+// POST /order/dead-man-switch, re-send before it runs out to keep orders alive, 0 disarms
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct DeadManSwitch {
+    #[validate(range(max = 86400))]
+    pub timeout_secs: u64,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct OpenMarketRequest {
+// POST /admin/deposits, credited once per txn_id however often it is sent
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct Deposit {
+    #[validate(length(min = 1, max = 255))]
     pub user_id: String,
-    pub market: String
+    #[validate(length(min = 1, max = 16))]
+    pub asset: String,
+    pub amount: Decimal,
+    #[validate(length(min = 1, max = 128))]
+    pub txn_id: String,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CreateOrderData {
-    pub market: String,
-    pub price: f64,
-    pub quantity: f64,
-    pub side: Side,
-    pub user_id: String
+// GET /account/portfolio, values everything in `quote` (USD when left out)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortfolioQuery {
+    pub quote: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct DeleteOrderData {
+// POST /account/withdrawals, the amount is held until the withdrawal is settled
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct Withdraw {
+    // picked by the client, retrying with the same id holds the funds once
+    #[validate(length(min = 1, max = 64))]
+    pub withdrawal_id: String,
+    #[validate(length(min = 1, max = 16))]
+    pub asset: String,
+    pub amount: Decimal,
+    #[validate(length(min = 1, max = 255))]
+    pub address: String,
+}
+
+// POST /admin/withdrawals/approve and /admin/withdrawals/reject
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WithdrawalAction {
+    pub withdrawal_id: String,
+}
+
+// POST /admin/markets takes a MarketConfig, the registry row as is
+
+// POST /admin/markets/status
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetMarketStatus {
     pub market: String,
-    pub order_id: String
+    pub status: MarketStatus,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct GetOpenOrder {
-    pub user_id: String,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetOrderQuery {
+    pub order_id: Option<String>,
+    pub client_order_id: Option<String>,
     pub market: String
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum EngineData {
-    Symbol(SymbolData),
-    Order(CreateOrderData),
-    DeleteOrder(DeleteOrderData),
-    OpenOrder(GetOpenOrder)
-    // Future variants can be added here, e.g. Order(OrderData), etc.
+// market, price, quantity, side, userId
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepthQuery {
+    pub symbol: String
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KlinesQuery {
+    pub market: String,
+    pub interval: String,
+    #[serde(rename = "startTime")]
+    pub start_time: i64,
+    #[serde(rename = "endTime")]
+    pub end_time: i64
+}
+
+
 #[derive(Serialize, Deserialize)]
-pub struct MessageToEngine {
-    #[serde(rename = "type")]
-    pub type_ : String,
-    pub data: EngineData
+pub struct OpenMarketRequest {
+    pub user_id: String,
+    pub market: String
 }
 
 // klines return data
@@ -1,88 +1,28 @@
+use rust_decimal::Decimal;
 use serde_json::json;
 use log::warn;
 
-#[derive(Debug, Clone)]
-pub struct MarketConfig {
-    pub base_asset: String,
-    pub quote_asset: String,
-    pub min_order_size: f64,
-    pub max_order_size: f64,
-    pub price_precision: u32,
-    pub quantity_precision: u32,
-    pub min_price: f64,
-    pub max_price: f64,
-}
+use protocol::messages::MarketConfig;
+
+use crate::types::{CreateOrder, OrderType, Side, TimeInForce};
 
 pub struct OrderValidator {
     markets: std::collections::HashMap<String, MarketConfig>,
 }
 
 impl OrderValidator {
-    pub fn new() -> Self {
-        let mut markets = std::collections::HashMap::new();
-        
-        let market_configs = vec![
-            MarketConfig {
-                base_asset: "BTC".to_string(),
-                quote_asset: "USD".to_string(),
-                min_order_size: 0.001, 
-                max_order_size: 100.0,  
-                price_precision: 2,     
-                quantity_precision: 8,  
-                min_price: 0.01,
-                max_price: 1_000_000.0,
-            },
-            MarketConfig {
-                base_asset: "ETH".to_string(),
-                quote_asset: "USD".to_string(),
-                min_order_size: 0.01,   
-                max_order_size: 1000.0, 
-                price_precision: 2,     
-                quantity_precision: 6,  
-                min_price: 0.01,
-                max_price: 100_000.0,
-            },
-            MarketConfig {
-                base_asset: "BTC".to_string(),
-                quote_asset: "USDT".to_string(),
-                min_order_size: 0.001,
-                max_order_size: 100.0, 
-                price_precision: 2,    
-                quantity_precision: 8, 
-                min_price: 0.01,
-                max_price: 1_000_000.0,
-            },
-            MarketConfig {
-                base_asset: "ETH".to_string(),
-                quote_asset: "USDT".to_string(),
-                min_order_size: 0.01,  
-                max_order_size: 1000.0,
-                price_precision: 2,    
-                quantity_precision: 6, 
-                min_price: 0.01,
-                max_price: 100_000.0,
-            },
-            MarketConfig {
-                base_asset: "SOL".to_string(),
-                quote_asset: "USD".to_string(),
-                min_order_size: 0.1,   
-                max_order_size: 10000.0,
-                price_precision: 4,    
-                quantity_precision: 4, 
-                min_price: 0.0001,
-                max_price: 1000.0,
-            },
-        ];
-
-        for config in market_configs {
-            let market = format!("{}-{}", config.base_asset, config.quote_asset);
-            markets.insert(market, config);
-        }
+    /// Validator over the market registry, the same table the engine lists from.
+    pub fn new(markets: Vec<MarketConfig>) -> Self {
+        let markets = markets
+            .into_iter()
+            .map(|config| (config.ticker(), config))
+            .collect();
 
         Self { markets }
     }
 
-    pub fn validate_order(&self, market: &str, price: f64, quantity: f64) -> Result<(), serde_json::Value> {
+    pub fn validate_order(&self, order: &CreateOrder) -> Result<(), serde_json::Value> {
+        let market = order.market.as_str();
         let config = match self.markets.get(market) {
             Some(config) => config,
             None => {
@@ -94,73 +34,188 @@ impl OrderValidator {
             }
         };
 
-        if price <= 0.0 {
-            warn!("Invalid price: {}", price);
+        if order.reprice_post_only && order.time_in_force != TimeInForce::PostOnly {
+            warn!("reprice_post_only set on a {:?} order", order.time_in_force);
             return Err(json!({
-                "error": "Price must be greater than 0"
+                "error": "reprice_post_only is only supported for POST_ONLY orders"
             }));
         }
 
-        if price < config.min_price {
-            warn!("Price too low: {} < {}", price, config.min_price);
+        if order.expire_at.is_some()
+            && (order.order_type == OrderType::Market || matches!(order.time_in_force, TimeInForce::Ioc | TimeInForce::Fok))
+        {
+            warn!("expire_at on a {:?} {:?} order", order.order_type, order.time_in_force);
             return Err(json!({
-                "error": format!("Price must be at least {}", config.min_price)
+                "error": "expire_at is only supported for orders that can rest on the book"
             }));
         }
 
-        if price > config.max_price {
-            warn!("Price too high: {} > {}", price, config.max_price);
+        let is_stop = matches!(order.order_type, OrderType::StopMarket | OrderType::StopLimit);
+        match order.trigger_price {
+            Some(trigger_price) if is_stop => self.validate_price(config, trigger_price)?,
+            None if is_stop => {
+                warn!("Stop order without trigger price");
+                return Err(json!({
+                    "error": "Stop orders require a trigger_price"
+                }));
+            }
+            Some(_) => {
+                warn!("Trigger price on a {:?} order", order.order_type);
+                return Err(json!({
+                    "error": "trigger_price is only supported for stop orders"
+                }));
+            }
+            None => {}
+        }
+
+        if is_stop && matches!(order.time_in_force, TimeInForce::Fok | TimeInForce::PostOnly) {
+            warn!("Stop order with {:?}", order.time_in_force);
             return Err(json!({
-                "error": format!("Price must be at most {}", config.max_price)
+                "error": "Stop orders only support GTC and IOC"
             }));
         }
 
-        if quantity <= 0.0 {
-            warn!("Invalid quantity: {}", quantity);
+        match order.order_type {
+            OrderType::Limit | OrderType::StopLimit => {
+                let (Some(price), Some(quantity)) = (order.price, order.quantity) else {
+                    warn!("Limit order without price or quantity");
+                    return Err(json!({
+                        "error": "Limit orders require a price and a quantity"
+                    }));
+                };
+
+                if order.quote_quantity.is_some() || order.worst_price.is_some() {
+                    warn!("Limit order with market order fields");
+                    return Err(json!({
+                        "error": "quote_quantity and worst_price are only supported for market orders"
+                    }));
+                }
+
+                self.validate_price(config, price)?;
+                self.validate_quantity(config, quantity)?;
+            }
+            OrderType::Market | OrderType::StopMarket => {
+                if order.time_in_force == TimeInForce::PostOnly {
+                    warn!("Post-only market order");
+                    return Err(json!({
+                        "error": "Market orders cannot be post-only"
+                    }));
+                }
+
+                if order.time_in_force == TimeInForce::Fok && order.quote_quantity.is_some() {
+                    warn!("Fill-or-kill market order sized by quote quantity");
+                    return Err(json!({
+                        "error": "Fill-or-kill market orders must be sized by quantity"
+                    }));
+                }
+
+                if order.price.is_some() {
+                    warn!("Market order with a price");
+                    return Err(json!({
+                        "error": "Market orders do not take a price, use worst_price to cap slippage"
+                    }));
+                }
+
+                if let Some(worst_price) = order.worst_price {
+                    self.validate_price(config, worst_price)?;
+                }
+
+                if order.order_type == OrderType::StopMarket
+                    && matches!(order.side, Side::Buy)
+                    && order.quantity.is_some()
+                    && order.worst_price.is_none()
+                {
+                    warn!("Stop-market buy sized by quantity without worst price");
+                    return Err(json!({
+                        "error": "Stop-market buys sized by quantity require a worst_price"
+                    }));
+                }
+
+                match (order.side, order.quantity, order.quote_quantity) {
+                    (_, Some(quantity), None) => self.validate_quantity(config, quantity)?,
+                    (Side::Buy, None, Some(quote_quantity)) => {
+                        if quote_quantity <= Decimal::ZERO {
+                            warn!("Invalid quote quantity: {}", quote_quantity);
+                            return Err(json!({
+                                "error": "Quote quantity must be greater than 0"
+                            }));
+                        }
+                    }
+                    (Side::Sell, _, Some(_)) => {
+                        warn!("Market sell with quote quantity");
+                        return Err(json!({
+                            "error": "quote_quantity is only supported for market buys"
+                        }));
+                    }
+                    _ => {
+                        warn!("Market order without exactly one of quantity or quote_quantity");
+                        return Err(json!({
+                            "error": "Market orders require either quantity or quote_quantity"
+                        }));
+                    }
+                }
+            }
+        }
+
+        // market orders sized in base can't be valued until they fill
+        let order_value = match order.order_type {
+            OrderType::Limit | OrderType::StopLimit => order.price.zip(order.quantity).map(|(price, quantity)| price * quantity),
+            OrderType::Market | OrderType::StopMarket => order.quote_quantity,
+        };
+        if let Some(order_value) = order_value.filter(|value| *value < config.min_notional) {
+            warn!("Order value too low: {} < {}", order_value, config.min_notional);
             return Err(json!({
-                "error": "Quantity must be greater than 0"
+                "error": format!("Minimum order value is {} {}", config.min_notional, config.quote_asset)
             }));
         }
 
-        if quantity < config.min_order_size {
-            warn!("Quantity too low: {} < {}", quantity, config.min_order_size);
+        Ok(())
+    }
+
+    fn validate_price(&self, config: &MarketConfig, price: Decimal) -> Result<(), serde_json::Value> {
+        if price <= Decimal::ZERO {
+            warn!("Invalid price: {}", price);
             return Err(json!({
-                "error": format!("Minimum order size is {}", config.min_order_size)
+                "error": "Price must be greater than 0"
             }));
         }
 
-        if quantity > config.max_order_size {
-            warn!("Quantity too high: {} > {}", quantity, config.max_order_size);
+        if !self.is_multiple(price, config.tick_size) {
+            warn!("Price off tick: {} (tick {})", price, config.tick_size);
             return Err(json!({
-                "error": format!("Maximum order size is {}", config.max_order_size)
+                "error": format!("Price must be a multiple of the {} tick size", config.tick_size)
             }));
         }
 
-        if !self.validate_precision(price, config.price_precision) {
-            warn!("Invalid price precision: {} (expected {})", price, config.price_precision);
+        Ok(())
+    }
+
+    fn validate_quantity(&self, config: &MarketConfig, quantity: Decimal) -> Result<(), serde_json::Value> {
+        if quantity <= Decimal::ZERO {
+            warn!("Invalid quantity: {}", quantity);
             return Err(json!({
-                "error": format!("Price precision must be {} decimal places", config.price_precision)
+                "error": "Quantity must be greater than 0"
             }));
         }
 
-        if !self.validate_precision(quantity, config.quantity_precision) {
-            warn!("Invalid quantity precision: {} (expected {})", quantity, config.quantity_precision);
+        if !self.is_multiple(quantity, config.lot_size) {
+            warn!("Quantity off lot: {} (lot {})", quantity, config.lot_size);
             return Err(json!({
-                "error": format!("Quantity precision must be {} decimal places", config.quantity_precision)
+                "error": format!("Quantity must be a multiple of the {} lot size", config.lot_size)
             }));
         }
 
         Ok(())
     }
 
-    fn validate_precision(&self, value: f64, precision: u32) -> bool {
-        let multiplier = 10_f64.powi(precision as i32);
-        let rounded = (value * multiplier).round() / multiplier;
-        (value - rounded).abs() < f64::EPSILON
+    fn is_multiple(&self, value: Decimal, step: Decimal) -> bool {
+        step > Decimal::ZERO && (value % step).is_zero()
     }
 
     pub fn get_supported_markets(&self) -> Vec<String> {
-        self.markets.keys().cloned().collect()
+        let mut markets: Vec<String> = self.markets.keys().cloned().collect();
+        markets.sort();
+        markets
     }
 
     pub fn get_market_config(&self, market: &str) -> Option<&MarketConfig> {
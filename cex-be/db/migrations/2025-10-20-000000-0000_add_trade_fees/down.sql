@@ -0,0 +1,5 @@
+ALTER TABLE trades
+    DROP COLUMN maker_fee,
+    DROP COLUMN maker_fee_asset,
+    DROP COLUMN taker_fee,
+    DROP COLUMN taker_fee_asset;
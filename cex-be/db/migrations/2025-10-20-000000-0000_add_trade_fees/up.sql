@@ -0,0 +1,6 @@
+-- Maker and taker fees per trade, negative for rebates
+ALTER TABLE trades
+    ADD COLUMN maker_fee VARCHAR(255) NOT NULL DEFAULT '0',
+    ADD COLUMN maker_fee_asset VARCHAR(255) NOT NULL DEFAULT '',
+    ADD COLUMN taker_fee VARCHAR(255) NOT NULL DEFAULT '0',
+    ADD COLUMN taker_fee_asset VARCHAR(255) NOT NULL DEFAULT '';
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_trades_taker_user_id;
+DROP INDEX IF EXISTS idx_trades_maker_user_id;
+
+ALTER TABLE trades
+    DROP COLUMN maker_user_id,
+    DROP COLUMN taker_user_id;
@@ -0,0 +1,7 @@
+-- Who was on each side of a trade, for per-account volume
+ALTER TABLE trades
+    ADD COLUMN maker_user_id VARCHAR(255) NOT NULL DEFAULT '',
+    ADD COLUMN taker_user_id VARCHAR(255) NOT NULL DEFAULT '';
+
+CREATE INDEX idx_trades_maker_user_id ON trades(maker_user_id, timestamp);
+CREATE INDEX idx_trades_taker_user_id ON trades(taker_user_id, timestamp);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_trades_market_trade_id;
+
+ALTER TABLE trades DROP COLUMN trade_id;
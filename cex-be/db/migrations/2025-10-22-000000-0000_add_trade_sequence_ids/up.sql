@@ -0,0 +1,15 @@
+-- The engine's per-market trade sequence, unique so a redelivered trade is dropped
+ALTER TABLE trades ADD COLUMN trade_id BIGINT NOT NULL DEFAULT 0;
+
+-- Number the trades recorded before the engine sent its own ids, in the order
+-- they happened, so they don't all collide on 0
+UPDATE trades t
+SET trade_id = numbered.trade_id
+FROM (
+    SELECT id, timestamp, row_number() OVER (PARTITION BY market ORDER BY timestamp, id) AS trade_id
+    FROM trades
+) numbered
+WHERE t.id = numbered.id AND t.timestamp = numbered.timestamp;
+
+-- Hypertable unique indexes must include the partitioning column
+CREATE UNIQUE INDEX idx_trades_market_trade_id ON trades(market, trade_id, timestamp);
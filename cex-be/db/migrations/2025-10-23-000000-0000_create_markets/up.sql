@@ -0,0 +1,26 @@
+-- Market registry, read by the engine at boot and by the API validator
+CREATE TABLE markets (
+    symbol VARCHAR(32) PRIMARY KEY,
+    base_asset VARCHAR(16) NOT NULL,
+    quote_asset VARCHAR(16) NOT NULL,
+    tick_size NUMERIC NOT NULL CHECK (tick_size > 0),
+    lot_size NUMERIC NOT NULL CHECK (lot_size > 0),
+    min_notional NUMERIC NOT NULL DEFAULT 0,
+    maker_fee_rate NUMERIC NOT NULL DEFAULT 0,
+    taker_fee_rate NUMERIC NOT NULL DEFAULT 0,
+    status VARCHAR(16) NOT NULL DEFAULT 'open',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- The markets the engine used to hardcode. Majors pay makers a 0.01% rebate
+INSERT INTO markets (symbol, base_asset, quote_asset, tick_size, lot_size, min_notional, maker_fee_rate, taker_fee_rate) VALUES
+    ('BTC-USD', 'BTC', 'USD', 0.01, 0.00000001, 1, -0.0001, 0.0005),
+    ('ETH-USD', 'ETH', 'USD', 0.01, 0.000001, 1, -0.0001, 0.0005),
+    ('BTC-USDT', 'BTC', 'USDT', 0.01, 0.00000001, 1, -0.0001, 0.0005),
+    ('ETH-USDT', 'ETH', 'USDT', 0.01, 0.000001, 1, -0.0001, 0.0005),
+    ('SOL-USD', 'SOL', 'USD', 0.0001, 0.0001, 1, 0.001, 0.002),
+    ('ADA-USD', 'ADA', 'USD', 0.0001, 0.01, 1, 0.001, 0.002),
+    ('DOT-USD', 'DOT', 'USD', 0.0001, 0.0001, 1, 0.001, 0.002),
+    ('MATIC-USD', 'MATIC', 'USD', 0.0001, 0.01, 1, 0.001, 0.002),
+    ('AVAX-USD', 'AVAX', 'USD', 0.0001, 0.0001, 1, 0.001, 0.002),
+    ('LINK-USD', 'LINK', 'USD', 0.0001, 0.0001, 1, 0.001, 0.002);
@@ -0,0 +1,10 @@
+-- Deposits credited by the engine, one row per external transaction
+CREATE TABLE deposits (
+    txn_id VARCHAR(128) PRIMARY KEY,
+    user_id VARCHAR(255) NOT NULL,
+    asset VARCHAR(16) NOT NULL,
+    amount NUMERIC NOT NULL CHECK (amount > 0),
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX deposits_user_id_idx ON deposits (user_id, created_at);
@@ -0,0 +1,16 @@
+-- Withdrawals and where they are in pending -> approved -> sent | failed, or
+-- pending -> cancelled. The engine holds the funds until sent, failed or cancelled
+CREATE TABLE withdrawals (
+    withdrawal_id VARCHAR(128) PRIMARY KEY,
+    user_id VARCHAR(255) NOT NULL,
+    asset VARCHAR(16) NOT NULL,
+    amount NUMERIC NOT NULL CHECK (amount > 0),
+    address VARCHAR(255) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    tx_hash VARCHAR(255),
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX withdrawals_user_id_idx ON withdrawals (user_id, created_at);
+CREATE INDEX withdrawals_status_idx ON withdrawals (status);
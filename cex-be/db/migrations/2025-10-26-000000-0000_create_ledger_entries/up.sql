@@ -0,0 +1,19 @@
+-- One row per leg of every engine ledger transaction. The legs of a
+-- transaction sum to zero per asset, and summing an account's legs per asset
+-- and bucket gives its balances without the engine
+CREATE TABLE ledger_entries (
+    transaction_id BIGINT NOT NULL,
+    leg INTEGER NOT NULL,
+    sequence BIGINT NOT NULL,
+    reason VARCHAR(32) NOT NULL,
+    reference VARCHAR(255) NOT NULL,
+    account VARCHAR(255) NOT NULL,
+    bucket VARCHAR(16) NOT NULL,
+    asset VARCHAR(16) NOT NULL,
+    delta NUMERIC NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (transaction_id, leg)
+);
+
+CREATE INDEX ledger_entries_account_idx ON ledger_entries (account, asset);
+CREATE INDEX ledger_entries_reference_idx ON ledger_entries (reference);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_orders_order_id;
+ALTER TABLE orders DROP COLUMN status;
+ALTER TABLE orders DROP COLUMN order_id;
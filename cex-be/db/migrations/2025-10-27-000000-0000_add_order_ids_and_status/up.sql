@@ -0,0 +1,7 @@
+-- The engine's order id and where the order stands, so every update for an
+-- order lands on its one row instead of adding another
+ALTER TABLE orders ADD COLUMN order_id VARCHAR(64) NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT '';
+
+-- Not unique: a hypertable can only enforce uniqueness together with created_at
+CREATE INDEX idx_orders_order_id ON orders(order_id);
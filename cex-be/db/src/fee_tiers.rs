@@ -0,0 +1,104 @@
+use std::env;
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{prelude::*, sql_query, sql_types::{Numeric, Timestamp, Varchar}};
+use log::{error, info};
+use protocol::{fee_tiers::{self, VOLUME_WINDOW_DAYS}, messages::{MessageFromApi, ProcessInput, SetFeeTiersData, UserFeeTier}, queues};
+use redis::Client;
+use rust_decimal::Decimal;
+
+use crate::{to_decimal, DbPool};
+
+#[derive(QueryableByName)]
+struct UserVolume {
+    #[diesel(sql_type = Varchar)]
+    user_id: String,
+    #[diesel(sql_type = Numeric)]
+    volume: BigDecimal,
+}
+
+/// Start of the trailing volume window.
+pub fn window_start() -> NaiveDateTime {
+    (Utc::now() - Duration::days(VOLUME_WINDOW_DAYS)).naive_utc()
+}
+
+/// Quote volume per user since `since`, counting both the maker and taker side.
+pub fn trailing_volumes(conn: &mut PgConnection, since: NaiveDateTime) -> QueryResult<Vec<(String, Decimal)>> {
+    let rows = sql_query(
+        "SELECT user_id, SUM(volume) AS volume FROM (
+            SELECT maker_user_id AS user_id, quote_quantity::numeric AS volume FROM trades WHERE timestamp >= $1
+            UNION ALL
+            SELECT taker_user_id AS user_id, quote_quantity::numeric AS volume FROM trades WHERE timestamp >= $1
+        ) sides
+        WHERE user_id <> ''
+        GROUP BY user_id",
+    )
+    .bind::<Timestamp, _>(since)
+    .load::<UserVolume>(conn)?;
+
+    rows.into_iter().map(|row| Ok((row.user_id, to_decimal(&row.volume)?))).collect()
+}
+
+/// Quote volume of one user since `since`.
+pub fn trailing_volume(conn: &mut PgConnection, user_id: &str, since: NaiveDateTime) -> QueryResult<Decimal> {
+    let row = sql_query(
+        "SELECT $1 AS user_id, COALESCE(SUM(quote_quantity::numeric), 0) AS volume FROM trades
+        WHERE timestamp >= $2 AND (maker_user_id = $1 OR taker_user_id = $1)",
+    )
+    .bind::<Varchar, _>(user_id)
+    .bind::<Timestamp, _>(since)
+    .get_result::<UserVolume>(conn)?;
+
+    to_decimal(&row.volume)
+}
+
+// wait before trying again after a failed run, unless the interval is shorter
+const RETRY_SECS: u64 = 60;
+
+/// Recompute every account's fee tier from its trailing volume and hand the
+/// full set to the engine, every `FEE_TIER_INTERVAL_SECS` (default an hour).
+/// A failed run is logged and tried again after `RETRY_SECS`.
+pub async fn fee_tier_job(pool: DbPool) {
+    let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| queues::ENGINE_INPUT_URL.to_string());
+    let interval_secs = env::var("FEE_TIER_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(3600);
+
+    loop {
+        let wait_secs = match push_fee_tiers(&pool, &redis_url) {
+            Ok(()) => interval_secs,
+            Err(e) => {
+                error!("Error updating fee tiers, retrying in {}s: {}", RETRY_SECS.min(interval_secs), e);
+                RETRY_SECS.min(interval_secs)
+            }
+        };
+        tokio::time::sleep(std::time::Duration::from_secs(wait_secs)).await;
+    }
+}
+
+fn push_fee_tiers(pool: &DbPool, redis_url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = pool.get()?;
+    let tiers: Vec<UserFeeTier> = trailing_volumes(&mut conn, window_start())?
+        .into_iter()
+        .map(|(user_id, volume)| UserFeeTier {
+            user_id,
+            level: fee_tiers::tier_for_volume(volume).level,
+            volume,
+        })
+        .filter(|tier| tier.level > 0)
+        .collect();
+
+    let count = tiers.len();
+    let message = protocol::encode(&ProcessInput {
+        message: MessageFromApi::SET_FEE_TIERS(SetFeeTiersData { tiers }),
+        client_id: "fee_tier_job".to_string(),
+        timestamp: 0,
+    })?;
+    let mut redis_conn = Client::open(redis_url)?.get_connection()?;
+    redis::cmd("LPUSH").arg(queues::ENGINE_INPUT).arg(message).query::<()>(&mut redis_conn)?;
+
+    info!("Pushed fee tiers for {} accounts above the base tier", count);
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use diesel::{prelude::*, sql_query, sql_types::{Numeric, Varchar}};
+use protocol::messages::LedgerTransaction;
+use rust_decimal::Decimal;
+
+use crate::{model::LedgerEntry, schema::ledger_entries, to_decimal};
+
+#[derive(QueryableByName)]
+struct BucketTotal {
+    #[diesel(sql_type = Varchar)]
+    asset: String,
+    #[diesel(sql_type = Varchar)]
+    bucket: String,
+    #[diesel(sql_type = Numeric)]
+    total: BigDecimal,
+}
+
+/// Store every leg of a LEDGER event, returns whether it was new. Transaction
+/// ids come from the engine, so a redelivered event inserts nothing.
+pub fn record(conn: &mut PgConnection, transaction: &LedgerTransaction) -> Result<bool, Box<dyn std::error::Error>> {
+    let created_at = chrono::DateTime::from_timestamp_millis(transaction.timestamp as i64)
+        .ok_or("Invalid timestamp")?
+        .naive_utc();
+    let rows = transaction.entries
+        .iter()
+        .enumerate()
+        .map(|(leg, entry)| Ok(LedgerEntry {
+            transaction_id: i64::try_from(transaction.transaction_id)?,
+            leg: i32::try_from(leg)?,
+            sequence: i64::try_from(transaction.sequence)?,
+            reason: transaction.reason.as_str().to_string(),
+            reference: transaction.reference.clone(),
+            account: entry.account.clone(),
+            bucket: entry.bucket.as_str().to_string(),
+            asset: entry.asset.clone(),
+            delta: BigDecimal::from_str(&entry.delta.to_string())?,
+            created_at,
+        }))
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+
+    let inserted = diesel::insert_into(ledger_entries::table)
+        .values(&rows)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
+
+/// Every leg posted against an order id, `<market>:<trade_id>`, deposit txn_id
+/// or withdrawal id, oldest first.
+pub fn entries_for_reference(conn: &mut PgConnection, reference: &str) -> QueryResult<Vec<LedgerEntry>> {
+    ledger_entries::table
+        .filter(ledger_entries::reference.eq(reference))
+        .order((ledger_entries::transaction_id.asc(), ledger_entries::leg.asc()))
+        .load::<LedgerEntry>(conn)
+}
+
+/// An account's balances rebuilt from its ledger alone, as (asset, bucket, total).
+pub fn account_balances(conn: &mut PgConnection, account: &str) -> QueryResult<Vec<(String, String, Decimal)>> {
+    let rows = sql_query(
+        "SELECT asset, bucket, SUM(delta) AS total FROM ledger_entries
+        WHERE account = $1
+        GROUP BY asset, bucket
+        ORDER BY asset, bucket",
+    )
+    .bind::<Varchar, _>(account)
+    .load::<BucketTotal>(conn)?;
+
+    rows.into_iter().map(|row| Ok((row.asset, row.bucket, to_decimal(&row.total)?))).collect()
+}
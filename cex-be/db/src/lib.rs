@@ -1,50 +1,24 @@
 mod model;
+pub mod fee_tiers;
+pub mod ledger;
+pub mod markets;
+pub mod orders;
+pub mod withdrawals;
 
 use diesel::{r2d2::{self, ConnectionManager}, PgConnection, prelude::*};
 pub use model::*;
+use protocol::{messages::PushToDb, queues};
 use redis::Client;
-use validator::Validate;
-use std::env;
+use std::{env, str::FromStr};
 use log::{info, error};
-use bigdecimal::FromPrimitive;
 
 
 pub mod schema;
 
 pub use schema::*;
-use serde::{Deserialize, Serialize};
 
 pub type DbPool  = r2d2::Pool<ConnectionManager<PgConnection>>;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub enum DbMessage {
-    TradeAdded(TradeMessage),
-    OrderUpdate(OrderMessage)
-}
-
-#[derive(Debug, Serialize, Deserialize, Validate)]
-pub struct TradeMessage {
-    #[validate(length(min = 1))]
-    pub id: String,
-    pub is_buyer_maker: bool,
-    pub price: String,
-    pub quantity: String,
-    pub quote_quantity: String,
-    pub timestamp: i64,
-    pub market: String,
-}
-
-#[derive(Debug, Serialize, Deserialize, Validate)]
-pub struct OrderMessage {
-    #[validate(length(min = 1))]
-    pub order_id: String,
-    pub executed_qty: f64,
-    pub market: Option<String>,
-    pub price: Option<String>,
-    pub quantity: Option<String>,
-    pub side: Option<String>,
-}
-
 pub fn establish_connection() -> DbPool {
     match dotenvy::dotenv() {
         Ok(_) => {
@@ -58,46 +32,92 @@ pub fn establish_connection() -> DbPool {
     }
 }
 
-fn process_message(message: DbMessage, pool: &DbPool) -> Result<(), Box<dyn std::error::Error>> {
+// numeric columns come back as BigDecimal, everything else works in rust_decimal.
+// A value out of rust_decimal's range fails the query rather than turning into something else
+pub(crate) fn to_decimal(value: &bigdecimal::BigDecimal) -> QueryResult<rust_decimal::Decimal> {
+    rust_decimal::Decimal::from_str(&value.to_string()).map_err(|e| {
+        diesel::result::Error::DeserializationError(format!("numeric {} doesn't fit a decimal: {}", value, e).into())
+    })
+}
+
+fn process_message(message: PushToDb, pool: &DbPool) -> Result<(), Box<dyn std::error::Error>> {
     let mut conn = pool.get()?;
     
     match message {
-        DbMessage::TradeAdded(trade_msg) => {
+        PushToDb::TRADE_ADDED(trade_msg) => {
             let trade = Trade {
                 id: uuid::Uuid::new_v4(),
                 is_buyer_maker: trade_msg.is_buyer_maker,
-                price: trade_msg.price,
-                quantity: trade_msg.quantity,
-                quote_quantity: trade_msg.quote_quantity,
+                price: trade_msg.price.to_string(),
+                quantity: trade_msg.quantity.to_string(),
+                quote_quantity: trade_msg.quote_quantity.to_string(),
                 timestamp: chrono::DateTime::from_timestamp(trade_msg.timestamp, 0)
                     .ok_or("Invalid timestamp")?
                     .naive_utc(),
                 market: trade_msg.market,
+                maker_fee: trade_msg.maker_fee.to_string(),
+                maker_fee_asset: trade_msg.maker_fee_asset,
+                taker_fee: trade_msg.taker_fee.to_string(),
+                taker_fee_asset: trade_msg.taker_fee_asset,
+                maker_user_id: trade_msg.maker_user_id,
+                taker_user_id: trade_msg.taker_user_id,
+                trade_id: i64::try_from(trade_msg.trade_id)?,
             };
             
-            diesel::insert_into(trades::table)
+            let inserted = diesel::insert_into(trades::table)
                 .values(&trade)
+                .on_conflict_do_nothing()
                 .execute(&mut conn)?;
                 
-            info!("Trade inserted successfully: {:?}", trade.id);
+            if inserted == 0 {
+                info!("Trade {} on {} already stored, skipping", trade.trade_id, trade.market);
+            } else {
+                info!("Trade inserted successfully: {} {}", trade.market, trade.trade_id);
+            }
         }
-        DbMessage::OrderUpdate(order_msg) => {
-            let order = Order {
-                id: uuid::Uuid::new_v4(),
-                executed_qty: bigdecimal::BigDecimal::from_f64(order_msg.executed_qty)
-                    .ok_or("Invalid executed_qty value")?,
-                market: order_msg.market.unwrap_or_default(),
-                price: order_msg.price.unwrap_or_default(),
-                quantity: order_msg.quantity.unwrap_or_default(),
-                side: order_msg.side.unwrap_or_default(),
-                created_at: chrono::Utc::now().naive_utc(),
+        PushToDb::ORDER_UPDATE(order_msg) => {
+            if orders::record(&mut conn, &order_msg)? {
+                info!("Order {} updated", order_msg.order_id);
+            } else {
+                info!("Order {} stored", order_msg.order_id);
+            }
+        }
+        PushToDb::DEPOSIT(deposit_msg) => {
+            let deposit = Deposit {
+                txn_id: deposit_msg.txn_id,
+                user_id: deposit_msg.user_id,
+                asset: deposit_msg.asset,
+                amount: bigdecimal::BigDecimal::from_str(&deposit_msg.amount.to_string())?,
+                created_at: chrono::DateTime::from_timestamp_millis(deposit_msg.timestamp as i64)
+                    .ok_or("Invalid timestamp")?
+                    .naive_utc(),
             };
-            
-            diesel::insert_into(orders::table)
-                .values(&order)
+
+            // the engine credits a txn_id once, a redelivered event changes nothing
+            let inserted = diesel::insert_into(deposits::table)
+                .values(&deposit)
+                .on_conflict_do_nothing()
                 .execute(&mut conn)?;
-                
-            info!("Order inserted successfully: {:?}", order.id);
+
+            if inserted == 0 {
+                info!("Deposit {} already stored, skipping", deposit.txn_id);
+            } else {
+                info!("Deposit inserted successfully: {} {} for {}", deposit.amount, deposit.asset, deposit.user_id);
+            }
+        }
+        PushToDb::WITHDRAWAL(withdrawal_msg) => {
+            if withdrawals::record(&mut conn, &withdrawal_msg)? {
+                info!("Withdrawal {} is now {}", withdrawal_msg.withdrawal_id, withdrawal_msg.status.as_str());
+            } else {
+                info!("Withdrawal {} already {} or past it, skipping", withdrawal_msg.withdrawal_id, withdrawal_msg.status.as_str());
+            }
+        }
+        PushToDb::LEDGER(transaction) => {
+            if ledger::record(&mut conn, &transaction)? {
+                info!("Ledger transaction {} ({} {}) stored", transaction.transaction_id, transaction.reason.as_str(), transaction.reference);
+            } else {
+                info!("Ledger transaction {} already stored, skipping", transaction.transaction_id);
+            }
         }
     }
     
@@ -115,14 +135,15 @@ pub async fn add_db(pool: DbPool) {
     println!("database started");
 
     loop {
-        let result: Option<String> = redis::cmd("BRPOP")
-            .arg("db_processor")
+        // BRPOP replies with the list name alongside the popped value
+        let result: Option<(String, String)> = redis::cmd("BRPOP")
+            .arg(queues::DB_EVENTS)
             .arg(1)
             .query(&mut conn)
             .unwrap_or(None);
 
-        if let Some(message) = result {
-            match serde_json::from_str::<DbMessage>(&message) {
+        if let Some((_, message)) = result {
+            match protocol::decode::<PushToDb>(&message) {
                 Ok(message) => {
                     info!("Received message: {:?}", message);
                     match process_message(message, &pool) {
@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use diesel::{prelude::*, upsert::excluded};
+use log::warn;
+use protocol::messages::{MarketConfig, MarketStatus};
+use rust_decimal::Decimal;
+
+use crate::{model::Market, schema::markets, to_decimal};
+
+/// Every market in the registry, oldest listing first. Rows with an unknown
+/// status or a number that doesn't fit a decimal are left out.
+pub fn load_markets(conn: &mut PgConnection) -> QueryResult<Vec<MarketConfig>> {
+    let rows = markets::table
+        .order((markets::created_at.asc(), markets::symbol.asc()))
+        .load::<Market>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let Some(status) = MarketStatus::parse(&row.status) else {
+                warn!("Skipping market {} with unknown status {}", row.symbol, row.status);
+                return None;
+            };
+            let config = || -> QueryResult<MarketConfig> {
+                Ok(MarketConfig {
+                    base_asset: row.base_asset.clone(),
+                    quote_asset: row.quote_asset.clone(),
+                    tick_size: to_decimal(&row.tick_size)?,
+                    lot_size: to_decimal(&row.lot_size)?,
+                    min_notional: to_decimal(&row.min_notional)?,
+                    maker_fee_rate: to_decimal(&row.maker_fee_rate)?,
+                    taker_fee_rate: to_decimal(&row.taker_fee_rate)?,
+                    status,
+                })
+            };
+            config()
+                .inspect_err(|e| warn!("Skipping market {}: {}", row.symbol, e))
+                .ok()
+        })
+        .collect())
+}
+
+/// Load the registry over a one-off connection, for processes that keep no pool.
+pub fn fetch_markets(database_url: &str) -> Result<Vec<MarketConfig>, Box<dyn std::error::Error>> {
+    let mut conn = PgConnection::establish(database_url)?;
+    Ok(load_markets(&mut conn)?)
+}
+
+pub fn load_market(conn: &mut PgConnection, symbol: &str) -> QueryResult<Option<MarketConfig>> {
+    Ok(load_markets(conn)?.into_iter().find(|market| market.ticker() == symbol))
+}
+
+/// List `config`, or overwrite the registry entry of a market already listed.
+pub fn upsert_market(conn: &mut PgConnection, config: &MarketConfig) -> QueryResult<()> {
+    let row = Market {
+        symbol: config.ticker(),
+        base_asset: config.base_asset.clone(),
+        quote_asset: config.quote_asset.clone(),
+        tick_size: to_big_decimal(config.tick_size),
+        lot_size: to_big_decimal(config.lot_size),
+        min_notional: to_big_decimal(config.min_notional),
+        maker_fee_rate: to_big_decimal(config.maker_fee_rate),
+        taker_fee_rate: to_big_decimal(config.taker_fee_rate),
+        status: config.status.as_str().to_string(),
+        created_at: chrono::Utc::now().naive_utc(),
+    };
+
+    diesel::insert_into(markets::table)
+        .values(&row)
+        .on_conflict(markets::symbol)
+        .do_update()
+        .set((
+            markets::tick_size.eq(excluded(markets::tick_size)),
+            markets::lot_size.eq(excluded(markets::lot_size)),
+            markets::min_notional.eq(excluded(markets::min_notional)),
+            markets::maker_fee_rate.eq(excluded(markets::maker_fee_rate)),
+            markets::taker_fee_rate.eq(excluded(markets::taker_fee_rate)),
+            markets::status.eq(excluded(markets::status)),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Record a status change the engine accepted, returns whether the market is listed.
+pub fn update_status(conn: &mut PgConnection, symbol: &str, status: MarketStatus) -> QueryResult<bool> {
+    let updated = diesel::update(markets::table.find(symbol))
+        .set(markets::status.eq(status.as_str()))
+        .execute(conn)?;
+    Ok(updated > 0)
+}
+
+fn to_big_decimal(value: Decimal) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).unwrap_or_default()
+}
@@ -3,7 +3,7 @@ use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::{Queryable, Insertable};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::schema::{trades, orders, users};
+use crate::schema::{deposits, ledger_entries, markets, trades, orders, users, withdrawals};
 
 #[derive(Debug, Queryable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = users)]
@@ -25,7 +25,14 @@ pub struct Trade {
     pub quantity: String,
     pub quote_quantity: String,
     pub timestamp: NaiveDateTime,
-    pub market: String
+    pub market: String,
+    pub maker_fee: String,
+    pub maker_fee_asset: String,
+    pub taker_fee: String,
+    pub taker_fee_asset: String,
+    pub maker_user_id: String,
+    pub taker_user_id: String,
+    pub trade_id: i64,
 }
 
 #[derive(Debug, Queryable, Insertable, Serialize, Deserialize)]
@@ -38,5 +45,61 @@ pub struct Order {
     pub quantity: String,
     pub side: String,
     pub created_at: NaiveDateTime,
+    pub order_id: String,
+    pub status: String,
 }
 
+
+#[derive(Debug, Queryable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = markets)]
+pub struct Market {
+    pub symbol: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub tick_size: BigDecimal,
+    pub lot_size: BigDecimal,
+    pub min_notional: BigDecimal,
+    pub maker_fee_rate: BigDecimal,
+    pub taker_fee_rate: BigDecimal,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = deposits)]
+pub struct Deposit {
+    pub txn_id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: BigDecimal,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = ledger_entries)]
+pub struct LedgerEntry {
+    pub transaction_id: i64,
+    pub leg: i32,
+    pub sequence: i64,
+    pub reason: String,
+    pub reference: String,
+    pub account: String,
+    pub bucket: String,
+    pub asset: String,
+    pub delta: BigDecimal,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = withdrawals)]
+pub struct Withdrawal {
+    pub withdrawal_id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: BigDecimal,
+    pub address: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
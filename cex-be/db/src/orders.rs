@@ -0,0 +1,64 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use protocol::messages::{ORDERUPDATEDATA, Side};
+
+use crate::{model::Order, schema::orders};
+
+// the fields an update carries, the ones it leaves out keep what the row has
+#[derive(AsChangeset)]
+#[diesel(table_name = orders)]
+struct OrderChanges {
+    executed_qty: BigDecimal,
+    market: Option<String>,
+    price: Option<String>,
+    quantity: Option<String>,
+    side: Option<String>,
+    status: Option<String>,
+}
+
+fn side_str(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+}
+
+/// Apply an ORDER_UPDATE from the engine to the order's row, inserting it the
+/// first time the order is seen. Returns whether an existing row was updated.
+/// The orders hypertable can't hold a unique index on order_id alone, so this
+/// updates first and inserts if nothing matched; the db writer is the only one
+/// writing orders and handles one event at a time.
+pub fn record(conn: &mut PgConnection, update: &ORDERUPDATEDATA) -> Result<bool, Box<dyn std::error::Error>> {
+    let executed_qty = BigDecimal::from_str(&update.exec_qty.to_string())?;
+    let changes = OrderChanges {
+        executed_qty: executed_qty.clone(),
+        market: update.market.clone(),
+        price: update.price.map(|price| price.to_string()),
+        quantity: update.quantity.map(|quantity| quantity.to_string()),
+        side: update.side.map(|side| side_str(side).to_string()),
+        status: update.status.map(|status| status.as_str().to_string()),
+    };
+
+    let updated = diesel::update(orders::table.filter(orders::order_id.eq(&update.order_id)))
+        .set(&changes)
+        .execute(conn)?;
+    if updated > 0 {
+        return Ok(true);
+    }
+
+    let order = Order {
+        id: uuid::Uuid::new_v4(),
+        executed_qty,
+        market: update.market.clone().unwrap_or_default(),
+        price: update.price.map(|price| price.to_string()).unwrap_or_default(),
+        quantity: update.quantity.map(|quantity| quantity.to_string()).unwrap_or_default(),
+        side: update.side.map(side_str).unwrap_or_default().to_string(),
+        created_at: chrono::Utc::now().naive_utc(),
+        order_id: update.order_id.clone(),
+        status: update.status.map(|status| status.as_str()).unwrap_or_default().to_string(),
+    };
+    diesel::insert_into(orders::table).values(&order).execute(conn)?;
+    Ok(false)
+}
@@ -1,5 +1,57 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    deposits (txn_id) {
+        #[max_length = 128]
+        txn_id -> Varchar,
+        #[max_length = 255]
+        user_id -> Varchar,
+        #[max_length = 16]
+        asset -> Varchar,
+        amount -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    ledger_entries (transaction_id, leg) {
+        transaction_id -> Int8,
+        leg -> Int4,
+        sequence -> Int8,
+        #[max_length = 32]
+        reason -> Varchar,
+        #[max_length = 255]
+        reference -> Varchar,
+        #[max_length = 255]
+        account -> Varchar,
+        #[max_length = 16]
+        bucket -> Varchar,
+        #[max_length = 16]
+        asset -> Varchar,
+        delta -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    markets (symbol) {
+        #[max_length = 32]
+        symbol -> Varchar,
+        #[max_length = 16]
+        base_asset -> Varchar,
+        #[max_length = 16]
+        quote_asset -> Varchar,
+        tick_size -> Numeric,
+        lot_size -> Numeric,
+        min_notional -> Numeric,
+        maker_fee_rate -> Numeric,
+        taker_fee_rate -> Numeric,
+        #[max_length = 16]
+        status -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     orders (id, created_at) {
         id -> Uuid,
@@ -13,6 +65,10 @@ diesel::table! {
         #[max_length = 50]
         side -> Varchar,
         created_at -> Timestamp,
+        #[max_length = 64]
+        order_id -> Varchar,
+        #[max_length = 16]
+        status -> Varchar,
     }
 }
 
@@ -29,6 +85,19 @@ diesel::table! {
         timestamp -> Timestamp,
         #[max_length = 255]
         market -> Varchar,
+        #[max_length = 255]
+        maker_fee -> Varchar,
+        #[max_length = 255]
+        maker_fee_asset -> Varchar,
+        #[max_length = 255]
+        taker_fee -> Varchar,
+        #[max_length = 255]
+        taker_fee_asset -> Varchar,
+        #[max_length = 255]
+        maker_user_id -> Varchar,
+        #[max_length = 255]
+        taker_user_id -> Varchar,
+        trade_id -> Int8,
     }
 }
 
@@ -46,4 +115,24 @@ diesel::table! {
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(orders, trades, users,);
+diesel::table! {
+    withdrawals (withdrawal_id) {
+        #[max_length = 128]
+        withdrawal_id -> Varchar,
+        #[max_length = 255]
+        user_id -> Varchar,
+        #[max_length = 16]
+        asset -> Varchar,
+        amount -> Numeric,
+        #[max_length = 255]
+        address -> Varchar,
+        #[max_length = 16]
+        status -> Varchar,
+        #[max_length = 255]
+        tx_hash -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(deposits, ledger_entries, markets, orders, trades, users, withdrawals,);
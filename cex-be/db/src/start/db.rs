@@ -1,4 +1,4 @@
-use db::{self, establish_connection, add_db};
+use db::{self, establish_connection, add_db, fee_tiers::fee_tier_job};
 use dotenv::dotenv;
 use env_logger;
 use log::info;
@@ -9,5 +9,6 @@ async fn main() {
     env_logger::init();
 
     let pool = establish_connection();
+    tokio::spawn(fee_tier_job(pool.clone()));
     add_db(pool).await;
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use protocol::messages::{WithdrawalPayload, WithdrawalStatus};
+
+use crate::{model::Withdrawal, schema::withdrawals};
+
+const STATUSES: [WithdrawalStatus; 5] = [
+    WithdrawalStatus::Pending,
+    WithdrawalStatus::Approved,
+    WithdrawalStatus::Sent,
+    WithdrawalStatus::Failed,
+    WithdrawalStatus::Cancelled,
+];
+
+pub fn load_withdrawal(conn: &mut PgConnection, withdrawal_id: &str) -> QueryResult<Option<Withdrawal>> {
+    withdrawals::table.find(withdrawal_id).first::<Withdrawal>(conn).optional()
+}
+
+/// A user's withdrawals, newest first.
+pub fn user_withdrawals(conn: &mut PgConnection, user_id: &str) -> QueryResult<Vec<Withdrawal>> {
+    withdrawals::table
+        .filter(withdrawals::user_id.eq(user_id))
+        .order(withdrawals::created_at.desc())
+        .load::<Withdrawal>(conn)
+}
+
+/// Move a withdrawal to `status` if its current status allows it, returns
+/// whether it moved. The check and the update are one statement, so two
+/// admins can't both approve, or approve and reject, the same withdrawal.
+pub fn transition(conn: &mut PgConnection, withdrawal_id: &str, status: WithdrawalStatus, tx_hash: Option<&str>) -> QueryResult<bool> {
+    let from: Vec<&str> = STATUSES
+        .iter()
+        .filter(|current| current.can_become(status))
+        .map(|current| current.as_str())
+        .collect();
+
+    let updated = diesel::update(withdrawals::table.find(withdrawal_id))
+        .filter(withdrawals::status.eq_any(from))
+        .set((
+            withdrawals::status.eq(status.as_str()),
+            withdrawals::tx_hash.eq(tx_hash),
+            withdrawals::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(conn)?;
+    Ok(updated > 0)
+}
+
+/// Apply a WITHDRAWAL event from the engine: a new hold inserts the row, a
+/// settlement moves it on. Returns whether anything changed, redelivered events don't.
+pub fn record(conn: &mut PgConnection, withdrawal: &WithdrawalPayload) -> Result<bool, Box<dyn std::error::Error>> {
+    if withdrawal.status != WithdrawalStatus::Pending {
+        return Ok(transition(conn, &withdrawal.withdrawal_id, withdrawal.status, withdrawal.tx_hash.as_deref())?);
+    }
+
+    let requested_at = engine_time(withdrawal.timestamp)?;
+    let row = Withdrawal {
+        withdrawal_id: withdrawal.withdrawal_id.clone(),
+        user_id: withdrawal.user_id.clone(),
+        asset: withdrawal.asset.clone(),
+        amount: BigDecimal::from_str(&withdrawal.amount.to_string())?,
+        address: withdrawal.address.clone(),
+        status: WithdrawalStatus::Pending.as_str().to_string(),
+        tx_hash: None,
+        created_at: requested_at,
+        updated_at: requested_at,
+    };
+    let inserted = diesel::insert_into(withdrawals::table)
+        .values(&row)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
+
+fn engine_time(timestamp: u64) -> Result<NaiveDateTime, Box<dyn std::error::Error>> {
+    Ok(chrono::DateTime::from_timestamp_millis(timestamp as i64)
+        .ok_or("Invalid timestamp")?
+        .naive_utc())
+}
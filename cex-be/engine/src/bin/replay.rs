@@ -0,0 +1,94 @@
+//! Run a journal offline and compare the result with a snapshot.
+//!
+//! replay <journal> [--from <snapshot>] [--expect <snapshot>] [--out <snapshot>]
+//!
+//! Starts from `--from` (or no markets at all, the journal has to list them with
+//! ADD_MARKET first), applies every journal entry after it,
+//! writes the final state to `--out` and diffs it against `--expect`. Replaying
+//! a recorded journal against the snapshot of an older build catches matching
+//! changes that move orders or balances. The engine compacts its journal after
+//! every snapshot, so a live journal only replays `--from` that snapshot; keep
+//! a copy of the journal for longer regression runs.
+
+use std::{env, path::PathBuf, process};
+
+use engine::{engine::Engine, journal, snapshot};
+
+struct Args {
+    journal: PathBuf,
+    from: Option<PathBuf>,
+    expect: Option<PathBuf>,
+    out: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args().skip(1);
+    let mut journal = None;
+    let (mut from, mut expect, mut out) = (None, None, None);
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().map(PathBuf::from).ok_or(format!("{} needs a path", flag));
+        match arg.as_str() {
+            "--from" => from = Some(value("--from")?),
+            "--expect" => expect = Some(value("--expect")?),
+            "--out" => out = Some(value("--out")?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            path if journal.is_none() => journal = Some(PathBuf::from(path)),
+            extra => return Err(format!("unexpected argument {}", extra)),
+        }
+    }
+    Ok(Args {
+        journal: journal.ok_or("missing journal path")?,
+        from,
+        expect,
+        out,
+    })
+}
+
+fn load_snapshot(path: &PathBuf) -> snapshot::EngineSnapshot {
+    match snapshot::load(path) {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => fail(&format!("snapshot {} does not exist", path.display())),
+        Err(e) => fail(&format!("failed to load snapshot {}: {}", path.display(), e)),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("replay: {}", message);
+    process::exit(2);
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        fail(&format!("{}\nusage: replay <journal> [--from <snapshot>] [--expect <snapshot>] [--out <snapshot>]", e))
+    });
+
+    let mut engine = match &args.from {
+        Some(path) => Engine::from_snapshot(load_snapshot(path)),
+        None => Engine::new(),
+    };
+    let entries = journal::read(&args.journal)
+        .unwrap_or_else(|e| fail(&format!("failed to read journal {}: {}", args.journal.display(), e)));
+    let applied = journal::replay(&mut engine, entries)
+        .unwrap_or_else(|e| fail(&format!("replay stopped: {}", e)));
+    println!("Applied {} journal entries, engine at sequence {}", applied, engine.sequence);
+
+    let result = engine.snapshot();
+    if let Some(path) = &args.out {
+        if let Err(e) = snapshot::save(path, &result) {
+            fail(&format!("failed to write {}: {}", path.display(), e));
+        }
+        println!("Wrote final state to {}", path.display());
+    }
+
+    if let Some(path) = &args.expect {
+        let differences = snapshot::diff(&load_snapshot(path), &result);
+        if !differences.is_empty() {
+            for difference in &differences {
+                println!("{}", difference);
+            }
+            println!("{} differences against {}", differences.len(), path.display());
+            process::exit(1);
+        }
+        println!("Books and balances match {}", path.display());
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+// client_order_ids remembered per user, the oldest is forgotten past this
+const RECENT_PER_USER: usize = 1000;
+
+/// The order a client_order_id was last accepted for, by market and engine id.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClientOrder {
+    pub market: String,
+    pub order_id: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct UserClientOrders {
+    orders: HashMap<String, ClientOrder>,
+    // client_order_ids oldest first
+    accepted: VecDeque<String>,
+}
+
+/// Each user's recently accepted client_order_ids, whether their orders are
+/// still open or already filled, cancelled or killed, so a retried submission
+/// is never placed twice.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientOrders {
+    users: HashMap<String, UserClientOrders>,
+}
+
+impl ClientOrders {
+    pub fn get(&self, user_id: &str, client_order_id: &str) -> Option<&ClientOrder> {
+        self.users.get(user_id)?.orders.get(client_order_id)
+    }
+
+    pub fn record(&mut self, user_id: &str, client_order_id: &str, order: ClientOrder) {
+        let user = self.users.entry(user_id.to_string()).or_default();
+        if user.orders.insert(client_order_id.to_string(), order).is_none() {
+            user.accepted.push_back(client_order_id.to_string());
+        }
+        while user.accepted.len() > RECENT_PER_USER {
+            if let Some(oldest) = user.accepted.pop_front() {
+                user.orders.remove(&oldest);
+            }
+        }
+    }
+}
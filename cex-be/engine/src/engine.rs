@@ -1,107 +1,326 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use protocol::{channels, fee_tiers};
 use uuid::Uuid;
 use log::{info, warn, error, debug};
 
 use crate::{
-    orderbook::{Fill, OrderBook, PriceLevel}, redis_manager::RedisManager, types::{DepthPayload, FillResponse, MessageToApi, OpenOrdersPayload, OrderCancelledPayload, OrderPlacedPayload, ProcessInput, PushToDb, Side, ORDERUPDATEDATA, TRADEADDEDDATA}
+    client_orders::{ClientOrder, ClientOrders}, error::EngineError, fees::{self, FeeSchedule, FEE_ACCOUNT}, invariants, ledger::Posting, orderbook::{Fill, OrderBook, PriceLevel, SelfTrade}, redis_manager::RedisManager, timer_wheel::TimerWheel, snapshot::{EngineSnapshot, MarketSnapshot, SNAPSHOT_VERSION}, trigger_book::{StopOrder, TriggerBook}, types::{AmendOrderData, AssetBalance, AssetValuation, BalancesPayload, CancelAllOrdersData, CancelOrderData, CreateOrderData, DeadManSwitchData, DeadManSwitchPayload, DepositPayload, DepthPayload, GetBalancesData, LedgerBucket, LedgerReason, LedgerTransaction, MarketConfig, MarketStatus, MarketStatusPayload, SetMarketStatusData, GetOrderData, FillResponse, InvariantsPayload, MessageToApi, OpenOrdersPayload, OrderAmendedPayload, OrderCancelledPayload, OrderKilledPayload, OrdersCancelledPayload, OrderPartiallyCancelledPayload, OrderPayload, OrderPendingPayload, OrderPlacedPayload, OrderPostOnlyRejectedPayload, OrderRepricedPayload, OrderSelfTradePreventedPayload, OrderStatus, OrderType, ErrorPayload, PortfolioPayload, ONRAMPDATA, ProcessInput, PushToDb, SelfTradeCancelResponse, SettleWithdrawalData, WithdrawData, WithdrawalPayload, WithdrawalStatus, SelfTradePrevention, Side, TimeInForce, ORDERUPDATEDATA, TRADEADDEDDATA}
 };
 
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct UserBalance {
-    available: f64,
-    locked: f64
+    pub available: Decimal,
+    pub locked: Decimal
 }
 
 pub struct Engine{
     pub orderbooks: Vec<OrderBook>,
-    pub balances: HashMap<String, HashMap<String, UserBalance>>
+    pub balances: HashMap<String, HashMap<String, UserBalance>>,
+    // pending stop orders per market
+    pub trigger_books: HashMap<String, TriggerBook>,
+    // number of inputs applied so far, saved with every snapshot
+    pub sequence: u64,
+    // user id -> fee tier level, accounts on the base tier are left out
+    pub fee_tiers: HashMap<String, u32>,
+    // unix ms of the latest input, only ever moves forward and replays the same from the journal
+    pub time: u64,
+    // armed dead man's switches by user id
+    pub dead_man_switches: TimerWheel,
+    // good-til-time deadlines by order id, rebuilt from the books on restore. Orders
+    // that filled or were cancelled first are skipped when their deadline comes up
+    pub order_expiries: TimerWheel,
+    // credited deposits by txn id
+    pub deposits: HashMap<String, DepositPayload>,
+    // withdrawals by id, a pending one holds its amount outside available and locked
+    pub withdrawals: HashMap<String, WithdrawalPayload>,
+    // id of the last ledger transaction posted, every balance change is one
+    pub ledger_sequence: u64,
+    // recently accepted client_order_ids, open or not
+    pub client_orders: ClientOrders,
+    // engine time of the first input applied, the high half of every order id so
+    // an engine started over with no snapshot or journal never hands out an old id
+    pub order_id_epoch: u64
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Order {
-    pub price: f64,
-    pub quantity: f64,
+    pub price: Decimal,
+    pub quantity: Decimal,
     pub order_id: String,
-    pub filled: f64,
+    pub filled: Decimal,
     pub side: Side,
-    pub user_id: String
+    pub user_id: String,
+    pub order_type: OrderType,
+    pub time_in_force: TimeInForce,
+    pub self_trade_prevention: SelfTradePrevention,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    // good-til-time deadline in engine time
+    #[serde(default)]
+    pub expire_at: Option<u64>
+}
+
+// How an accepted CREATE_ORDER ended up, each maps to its own MessageToApi variant
+pub enum OrderOutcome {
+    // resting on the book or fully filled
+    Placed,
+    // IOC / market remainder dropped
+    RemainderCancelled(Decimal),
+    // FOK that could not fill completely, nothing traded
+    Killed { fillable_qty: Decimal },
+    // post-only that would have crossed, nothing traded
+    PostOnlyRejected,
+    // post-only moved behind the touch, then rested
+    Repriced { original_price: Decimal },
+    // stop order parked in the trigger book
+    Pending { trigger_price: Decimal },
+    // met the user's own resting orders, `cancelled_qty` of this order was dropped
+    SelfTradePrevented { cancelled_qty: Decimal, self_trade: SelfTrade },
+    // the client_order_id belongs to an open order, which is returned untouched
+    Duplicate(OrderPayload),
+}
+
+pub struct OrderResult {
+    pub order_id: String,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub executed_qty: Decimal,
+    pub fills: Vec<Fill>,
+    pub outcome: OrderOutcome,
 }
 
 
 impl Engine { 
     pub fn new() -> Self {
         println!("Initializing matching engine...");
-        let mut engine = Self {
+        let engine = Self {
             orderbooks: Vec::new(),
-            balances: HashMap::new()
+            balances: HashMap::new(),
+            trigger_books: HashMap::new(),
+            sequence: 0,
+            fee_tiers: HashMap::new(),
+            time: 0,
+            dead_man_switches: TimerWheel::new(),
+            order_expiries: TimerWheel::new(),
+            deposits: HashMap::new(),
+            withdrawals: HashMap::new(),
+            ledger_sequence: 0,
+            client_orders: ClientOrders::default(),
+            order_id_epoch: 0
         };
-        
-        // Initialize orderbooks for supported markets
-        engine.initialize_markets();
-        
-        println!("Matching engine initialized with {} markets", engine.orderbooks.len());
+
+        // markets come in through ADD_MARKET, the main loop lists the registry's at boot
+        println!("Matching engine initialized");
         engine
     }
 
-    fn initialize_markets(&mut self) {
-        let supported_markets = vec![
-            ("BTC", "USD"),
-            ("ETH", "USD"),
-            ("BTC", "USDT"),
-            ("ETH", "USDT"),
-            ("SOL", "USD"),
-            ("ADA", "USD"),
-            ("DOT", "USD"),
-            ("MATIC", "USD"),
-            ("AVAX", "USD"),
-            ("LINK", "USD"),
-        ];
-
-        for (base_asset, quote_asset) in supported_markets {
-            let orderbook = OrderBook::new(
-                base_asset.to_string(),
-                quote_asset.to_string(),
-                Vec::new(),
-                Vec::new(),
-                Some(0),    
-                Some(0.0),  
+    /// Rebuild the engine from a snapshot. Registry markets missing from it are
+    /// listed by the main loop afterwards.
+    pub fn from_snapshot(snapshot: EngineSnapshot) -> Self {
+        println!("Restoring matching engine from snapshot at sequence {}...", snapshot.sequence);
+        let mut engine = Self {
+            orderbooks: Vec::new(),
+            balances: snapshot.balances,
+            trigger_books: HashMap::new(),
+            sequence: snapshot.sequence,
+            fee_tiers: snapshot.fee_tiers,
+            time: snapshot.time,
+            dead_man_switches: TimerWheel::new(),
+            order_expiries: TimerWheel::new(),
+            deposits: snapshot.deposits,
+            withdrawals: snapshot.withdrawals,
+            ledger_sequence: snapshot.ledger_sequence,
+            client_orders: snapshot.client_orders,
+            order_id_epoch: snapshot.order_id_epoch
+        };
+        for (user_id, deadline) in &snapshot.dead_man_switches {
+            engine.dead_man_switches.arm(user_id, *deadline);
+        }
+
+        for market in snapshot.markets {
+            for order in market.bids.iter().chain(&market.asks) {
+                engine.arm_expiry(order);
+            }
+            let mut orderbook = OrderBook::new(
+                market.base_asset,
+                market.quote_asset,
+                market.bids,
+                market.asks,
+                Some(market.last_trade_id),
+                Some(market.current_price),
+                market.price_scale,
+                market.quantity_scale,
             );
-            
-            println!("Initialized orderbook for {}-{}", base_asset, quote_asset);
-            self.orderbooks.push(orderbook);
+            orderbook.status = market.status;
+            if market.tick_size > Decimal::ZERO && market.lot_size > Decimal::ZERO {
+                orderbook.tick_size = market.tick_size;
+                orderbook.lot_size = market.lot_size;
+            }
+            orderbook.min_notional = market.min_notional;
+            orderbook.fees = FeeSchedule::new(market.maker_fee_rate, market.taker_fee_rate);
+            let mut trigger_book = TriggerBook::new();
+            for stop in market.stops {
+                engine.arm_expiry(&stop.order);
+                trigger_book.add(stop);
+            }
+            println!("Restored orderbook for {}", orderbook.ticker());
+            engine.trigger_books.insert(orderbook.ticker(), trigger_book);
+            engine.orderbooks.push(orderbook);
         }
+
+        println!("Matching engine restored with {} markets", engine.orderbooks.len());
+        engine
+    }
+
+    pub fn snapshot(&self) -> EngineSnapshot {
+        let markets = self.orderbooks.iter().map(|orderbook| MarketSnapshot {
+            base_asset: orderbook.base_asset.clone(),
+            quote_asset: orderbook.quote_asset.clone(),
+            price_scale: orderbook.price_scale,
+            quantity_scale: orderbook.quantity_scale,
+            last_trade_id: orderbook.last_trade_id,
+            current_price: orderbook.current_price,
+            bids: orderbook.resting_orders(Side::Buy),
+            asks: orderbook.resting_orders(Side::Sell),
+            stops: self.trigger_books
+                .get(&orderbook.ticker())
+                .map(|trigger_book| trigger_book.orders().cloned().collect())
+                .unwrap_or_default(),
+            status: orderbook.status,
+            tick_size: orderbook.tick_size,
+            lot_size: orderbook.lot_size,
+            min_notional: orderbook.min_notional,
+            maker_fee_rate: orderbook.fees.maker_rate,
+            taker_fee_rate: orderbook.fees.taker_rate,
+        }).collect();
+
+        EngineSnapshot {
+            version: SNAPSHOT_VERSION,
+            sequence: self.sequence,
+            markets,
+            balances: self.balances.clone(),
+            fee_tiers: self.fee_tiers.clone(),
+            time: self.time,
+            dead_man_switches: self.dead_man_switches.deadlines().clone(),
+            deposits: self.deposits.clone(),
+            withdrawals: self.withdrawals.clone(),
+            ledger_sequence: self.ledger_sequence,
+            client_orders: self.client_orders.clone(),
+            order_id_epoch: self.order_id_epoch,
+        }
+    }
+
+    /// List a market from the registry, or bring a listed one in line with its
+    /// registry entry. A status change goes through `set_market_status`.
+    pub fn add_market(&mut self, config: &MarketConfig) -> Result<MarketConfig, EngineError> {
+        let is_asset = |asset: &str| !asset.is_empty() && asset.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+        if !is_asset(&config.base_asset) || !is_asset(&config.quote_asset) || config.base_asset == config.quote_asset {
+            return Err(EngineError::InvalidMarket(format!("{} is not a valid market", config.ticker())));
+        }
+        if config.tick_size <= Decimal::ZERO || config.lot_size <= Decimal::ZERO || config.min_notional < Decimal::ZERO {
+            return Err(EngineError::InvalidMarket("tick_size and lot_size must be positive, min_notional not negative".to_string()));
+        }
+        if config.maker_fee_rate.abs() >= Decimal::ONE || config.taker_fee_rate.abs() >= Decimal::ONE {
+            return Err(EngineError::InvalidMarket("Fee rates must be fractions between -1 and 1".to_string()));
+        }
+
+        let market = config.ticker();
+        let orderbook_index = match self.orderbooks.iter().position(|o| o.ticker() == market) {
+            Some(index) => index,
+            None => {
+                let mut orderbook = OrderBook::new(
+                    config.base_asset.clone(),
+                    config.quote_asset.clone(),
+                    Vec::new(),
+                    Vec::new(),
+                    Some(0),
+                    Some(Decimal::ZERO),
+                    config.price_scale(),
+                    config.quantity_scale(),
+                );
+                orderbook.status = config.status;
+                self.trigger_books.insert(market.clone(), TriggerBook::new());
+                self.orderbooks.push(orderbook);
+                info!("Listed market {}", market);
+                self.orderbooks.len() - 1
+            }
+        };
+        self.orderbooks[orderbook_index].configure(config);
+        if self.orderbooks[orderbook_index].status != config.status {
+            self.set_market_status(&SetMarketStatusData { market: market.clone(), status: config.status })?;
+        }
+        Ok(self.orderbooks[orderbook_index].config())
+    }
+
+    /// Config of a listed market, as the registry would hold it.
+    pub fn market_config(&self, market: &str) -> Option<MarketConfig> {
+        self.orderbooks.iter().find(|o| o.ticker() == market).map(|orderbook| orderbook.config())
     }
 
     pub fn process(&mut self, msg: ProcessInput){
-        debug!("Processing message from client: {}", msg.client_id);
+        self.sequence += 1;
+        self.time = self.time.max(msg.timestamp);
+        if self.order_id_epoch == 0 {
+            self.order_id_epoch = self.time;
+        }
+        debug!("Processing message {} from client: {}", self.sequence, msg.client_id);
+        // timers that ran out fire before the input, a refresh that arrives late is too late
+        self.fire_dead_man_switches();
+        self.expire_orders();
         match &msg.message {
             crate::types::MessageFromApi::CREATE_ORDER(create_data) => {
-                let market = create_data.market.clone();
-                let price: f64 = create_data.price.parse().unwrap_or(0.0);
-                let quantity: u64 = create_data.quantity.parse().unwrap_or(0);
-                let side = match create_data.side {
-                    Side::Buy => "buy",
-                    Side::Sell => "sell",
-                };
-                let user_id = create_data.user_id.clone();
-
-               match self.create_order(&market, price, quantity, side, &user_id) {
-                    Ok((executed_qty, fills, order_id)) => {
-                        let fill_responses: Vec<FillResponse> = fills.iter().map(|fill| FillResponse {
-                            price: fill.price.clone(),
-                            qty: fill.qty,
-                            trade_id: fill.trade_id,
-                            other_user_id: fill.other_user_id.clone(),
-                            market_order_id: fill.market_order_id.clone(),
-                        }).collect();
-
-                        let response = MessageToApi::ORDER_PLACED(OrderPlacedPayload {
-                            order_id: order_id.clone(),
-                            executed_qty,
-                            fills: fill_responses,
-                        });
-
-                        if let Ok(json) = serde_json::to_string(&response) {
+               match self.create_order(create_data) {
+                    Ok(result) => {
+                        let fill_responses = fill_responses(&create_data.market, create_data.side, &result.fills);
+
+                        let response = match result.outcome {
+                            OrderOutcome::Placed => MessageToApi::ORDER_PLACED(OrderPlacedPayload {
+                                order_id: result.order_id,
+                                executed_qty: result.executed_qty,
+                                fills: fill_responses,
+                            }),
+                            OrderOutcome::RemainderCancelled(cancelled_qty) => MessageToApi::ORDER_PARTIALLY_CANCELLED(OrderPartiallyCancelledPayload {
+                                order_id: result.order_id,
+                                executed_qty: result.executed_qty,
+                                cancelled_qty,
+                                fills: fill_responses,
+                            }),
+                            OrderOutcome::Killed { fillable_qty } => MessageToApi::ORDER_KILLED(OrderKilledPayload {
+                                order_id: result.order_id,
+                                quantity: result.quantity,
+                                fillable_qty,
+                            }),
+                            OrderOutcome::PostOnlyRejected => MessageToApi::ORDER_POST_ONLY_REJECTED(OrderPostOnlyRejectedPayload {
+                                order_id: result.order_id,
+                                price: result.price,
+                            }),
+                            OrderOutcome::Repriced { original_price } => MessageToApi::ORDER_REPRICED(OrderRepricedPayload {
+                                order_id: result.order_id,
+                                original_price,
+                                price: result.price,
+                            }),
+                            OrderOutcome::Pending { trigger_price } => MessageToApi::ORDER_PENDING(OrderPendingPayload {
+                                order_id: result.order_id,
+                                trigger_price,
+                            }),
+                            OrderOutcome::SelfTradePrevented { cancelled_qty, self_trade } => MessageToApi::ORDER_SELF_TRADE_PREVENTED(OrderSelfTradePreventedPayload {
+                                order_id: result.order_id,
+                                executed_qty: result.executed_qty,
+                                cancelled_qty,
+                                fills: fill_responses,
+                                cancelled_orders: self_trade.makers.iter().map(|maker| SelfTradeCancelResponse {
+                                    order_id: maker.order.order_id.clone(),
+                                    cancelled_qty: maker.cancelled_qty,
+                                    remaining_qty: maker.order.quantity - maker.order.filled,
+                                }).collect(),
+                            }),
+                            OrderOutcome::Duplicate(order) => MessageToApi::ORDER_DUPLICATE(order),
+                        };
+
+                        if let Ok(json) = protocol::encode(&response) {
                             if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
                                 let _ = redis_manager.send_to_api(&msg.client_id, &json);
                             }
@@ -109,18 +328,7 @@ impl Engine {
                     }
                     Err(e) => {
                         println!("Error creating order: {}", e);
-                        
-                        let response = MessageToApi::ORDER_CANCELLED(OrderCancelledPayload {
-                            order_id: "".to_string(),
-                            executed_qty: 0.0,
-                            remaining_qty: 0.0,
-                        });
-
-                        if let Ok(json) = serde_json::to_string(&response) {
-                            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
-                                let _ = redis_manager.send_to_api(&msg.client_id, &json);
-                            }
-                        }
+                        self.send_error(&msg.client_id, &e);
                     }
                 }
             },
@@ -128,359 +336,1311 @@ impl Engine {
                 if let crate::types::MessageFromApi::GET_DEPTH(depth_data) = &msg.message {
                     println!("Market: {}", depth_data.market);
                     let market = depth_data.market.clone();
-                    let orderbook = self.orderbooks.iter().find(|o| o.ticker() == market).expect("Orderbook not found");
-
-                    
-                    let response = match std::panic::catch_unwind(|| orderbook.getDepth()) {
-                        Ok(depth) => {
-                            MessageToApi::DEPTH(DepthPayload {
-                                payload: serde_json::to_string(&depth).unwrap_or("{\"bids\":[],\"asks\":[]}".to_string()),
-                            })
-                        }
-                        Err(e) => {
-                            println!("Error getting depth: {:?}", e);
-                            MessageToApi::DEPTH(DepthPayload {
-                                payload: "{\"bids\":[],\"asks\":[]}".to_string(),
-                            })
-                        }
-                    };
+                    if let Some(orderbook) = self.orderbooks.iter().find(|o| o.ticker() == market) {
+                        let response = MessageToApi::DEPTH(DepthPayload {
+                            payload: serde_json::to_string(&orderbook.getDepth()).unwrap_or("{\"bids\":[],\"asks\":[]}".to_string()),
+                        });
 
-                    if let Ok(json) = serde_json::to_string(&response) {
-                        if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
-                            let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                        if let Ok(json) = protocol::encode(&response) {
+                            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                                let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                            }
                         }
+                    } else {
+                        self.send_error(&msg.client_id, &EngineError::UnknownMarket(market));
                     }
                 }
             },
             crate::types::MessageFromApi::CANCEL_ORDER(_) => {
                 if let crate::types::MessageFromApi::CANCEL_ORDER(cancel_data) = &msg.message {
-                    let order_id = &cancel_data.order_id;
-                    let cancel_market = &cancel_data.market;
-                    let cancel_orderbook_index = self.orderbooks
-                        .iter()
-                        .position(|o| o.ticker() == cancel_market.as_str())
-                        .expect("Orderbook not found");
-                    
-                    let (base_asset, quote_asset) = match cancel_market.split_once('-') {
-                        Some((base, quote)) => (base.to_string(), quote.to_string()),
-                        None => {
-                            println!("Invalid market format");
-                            return;
-                        }
-                    };
-                    
-                    let order_info = self.orderbooks[cancel_orderbook_index]
-                        .asks
-                        .iter()
-                        .find(|o| o.order_id == order_id.as_str())
-                        .map(|o| (o.clone(), Side::Sell))
-                        .or_else(|| {
-                            self.orderbooks[cancel_orderbook_index]
-                                .bids
-                                .iter()
-                                .find(|o| o.order_id == order_id.as_str())
-                                .map(|o| (o.clone(), Side::Buy))
-                        });
+                    match self.cancel_order(cancel_data) {
+                        Ok(cancelled) => {
+                            let response = MessageToApi::ORDER_CANCELLED(cancelled);
 
-                        if let Some((order, side)) = order_info {
-                            if side == Side::Buy {
-                                let price = self.orderbooks[cancel_orderbook_index].cancelBid(&order.order_id);
-                                let left_qty = (order.quantity - order.filled) * order.price;
-
-                                self.balances.get_mut(&order.user_id)
-                                    .expect("User not found")
-                                    .get_mut(&quote_asset)
-                                    .expect("Quote asset not found")
-                                    .available += left_qty;
-
-                                self.balances.get_mut(&order.user_id)
-                                    .expect("User not found")
-                                    .get_mut(&quote_asset)
-                                    .expect("Quote asset not found")
-                                    .locked -= left_qty;
-
-                                if let Some(price) = price {
-                                    self.send_updated_depth(price.to_string(), cancel_market.to_string());
-                                }
-                            } else {
-                                let price = self.orderbooks[cancel_orderbook_index].cancelAsk(&order.order_id);
-                                let left_qty = order.quantity - order.filled;
-                                self.balances.get_mut(&order.user_id)
-                                    .expect("User not found")
-                                    .get_mut(&base_asset)
-                                    .expect("Base asset not found")
-                                    .available += left_qty;
-
-                                self.balances.get_mut(&order.user_id)
-                                    .expect("User not found")
-                                    .get_mut(&base_asset)
-                                    .expect("Base asset not found")
-                                    .locked -= left_qty;
-
-                                if let Some(price) = price {
-                                    self.send_updated_depth(price.to_string(), cancel_market.to_string());
+                            if let Ok(json) = protocol::encode(&response) {
+                                if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                                    let _ = redis_manager.send_to_api(&msg.client_id, &json);
                                 }
                             }
-                        } else {
-                            println!("Order not found: {}", order_id);
                         }
+                        Err(e) => {
+                            println!("Error cancelling order: {}", e);
+                            self.send_error(&msg.client_id, &e);
+                        }
+                    }
+                }
+            },
+            crate::types::MessageFromApi::ON_RAMP(ramp_data) => {
+                println!("Ramp data Amount: {} {}, user_id: {}, txn_id: {}", ramp_data.amount, ramp_data.asset, ramp_data.user_id, ramp_data.txn_id);
 
-                        let response = MessageToApi::ORDER_CANCELLED(OrderCancelledPayload {
-                            order_id: order_id.to_string(),
-                            executed_qty: 0.0,
-                            remaining_qty: 0.0,
-                        });
+                let response = match self.on_ramp(ramp_data) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        println!("Error crediting deposit: {}", e);
+                        MessageToApi::ERROR(ErrorPayload::from(&e))
+                    }
+                };
 
-                        if let Ok(json) = serde_json::to_string(&response) {
-                            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
-                                let _ = redis_manager.send_to_api(&msg.client_id, &json);
-                            }
-                        }
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
                     }
-                },
-            crate::types::MessageFromApi::ON_RAMP(_) => {
-                if let crate::types::MessageFromApi::ON_RAMP(ramp_data) = &msg.message {
-                    println!("Ramp data Amount: {}, user_id: {}, txn_id: {}", ramp_data.amount, ramp_data.user_id, ramp_data.txn_id);
+                }
+            },
+            crate::types::MessageFromApi::GET_BALANCES(balances_data) => {
+                let response = MessageToApi::BALANCES(self.get_balances(balances_data));
 
-                    let user_id = ramp_data.user_id.clone();
-                    let amount: f64 = match ramp_data.amount.parse() {
-                        Ok(val) => val,
-                        Err(e) => {
-                            println!("Failed to parse ramp_data.amount ('{}') as f64: {}", ramp_data.amount, e);
-                            0.0
-                        }
-                    };
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            crate::types::MessageFromApi::WITHDRAW(withdraw_data) => {
+                let response = match self.withdraw(withdraw_data) {
+                    Ok(withdrawal) => MessageToApi::WITHDRAWAL(withdrawal),
+                    Err(e) => {
+                        println!("Error holding withdrawal: {}", e);
+                        MessageToApi::ERROR(ErrorPayload::from(&e))
+                    }
+                };
 
-                    self.on_ramp(user_id, amount);
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
                 }
-                if let crate::types::MessageFromApi::GET_DEPTH(depth_data) = &msg.message {
-                    println!("Market: {}", depth_data.market)
+            },
+            crate::types::MessageFromApi::SETTLE_WITHDRAWAL(settle_data) => {
+                let response = match self.settle_withdrawal(settle_data) {
+                    Ok(withdrawal) => MessageToApi::WITHDRAWAL(withdrawal),
+                    Err(e) => {
+                        println!("Error settling withdrawal: {}", e);
+                        MessageToApi::ERROR(ErrorPayload::from(&e))
+                    }
+                };
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            crate::types::MessageFromApi::CANCEL_ALL_ORDERS(cancel_all_data) => {
+                let response = match self.cancel_all_orders(cancel_all_data) {
+                    Ok(cancelled) => MessageToApi::ORDERS_CANCELLED(OrdersCancelledPayload { cancelled }),
+                    Err(e) => MessageToApi::ERROR(ErrorPayload::from(&e)),
+                };
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            crate::types::MessageFromApi::AMEND_ORDER(amend_data) => {
+                let response = match self.amend_order(amend_data) {
+                    Ok(amended) => MessageToApi::ORDER_AMENDED(amended),
+                    Err(e) => {
+                        println!("Error amending order: {}", e);
+                        MessageToApi::ERROR(ErrorPayload::from(&e))
+                    }
+                };
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
                 }
             },
+            crate::types::MessageFromApi::GET_ORDER(get_data) => {
+                let response = match self.get_order(get_data) {
+                    Ok(order) => MessageToApi::ORDER(order),
+                    Err(e) => MessageToApi::ERROR(ErrorPayload::from(&e)),
+                };
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            crate::types::MessageFromApi::SET_DEAD_MAN_SWITCH(switch_data) => {
+                let response = MessageToApi::DEAD_MAN_SWITCH(self.set_dead_man_switch(switch_data));
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            crate::types::MessageFromApi::ADD_MARKET(config) => {
+                let response = match self.add_market(config) {
+                    Ok(listed) => MessageToApi::MARKET_ADDED(listed),
+                    Err(e) => {
+                        println!("Error adding market: {}", e);
+                        MessageToApi::ERROR(ErrorPayload::from(&e))
+                    }
+                };
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            crate::types::MessageFromApi::SET_MARKET_STATUS(status_data) => {
+                let response = match self.set_market_status(status_data) {
+                    Ok(changed) => MessageToApi::MARKET_STATUS(changed),
+                    Err(e) => MessageToApi::ERROR(ErrorPayload::from(&e)),
+                };
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            // only advances the clock, whatever fell due already fired above
+            crate::types::MessageFromApi::TICK => {},
+            crate::types::MessageFromApi::CHECK_INVARIANTS => {
+                let violations = invariants::check(self);
+                info!("Invariant check at input {}: {} violations", self.sequence, violations.len());
+                let response = MessageToApi::INVARIANTS(InvariantsPayload { sequence: self.sequence, violations });
+
+                if let Ok(json) = protocol::encode(&response) {
+                    if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                        let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                    }
+                }
+            },
+            crate::types::MessageFromApi::SET_FEE_TIERS(tier_data) => {
+                self.fee_tiers = tier_data.tiers
+                    .iter()
+                    .filter(|tier| tier.level > 0)
+                    .map(|tier| (tier.user_id.clone(), tier.level))
+                    .collect();
+                info!("Fee tiers updated, {} accounts above the base tier", self.fee_tiers.len());
+            },
             crate::types::MessageFromApi::GET_OPEN_ORDERS(_) => {
                 if let crate::types::MessageFromApi::GET_OPEN_ORDERS(open_order_data) = &msg.message {
-                    let open_order_book = self.orderbooks.iter().find(|o| o.ticker() == open_order_data.market).expect("Orderbook not found");
-                    let open_orders = open_order_book.getOpenOrders(open_order_data.user_id.clone());
+                    if let Some(open_order_book) = self.orderbooks.iter().find(|o| o.ticker() == open_order_data.market) {
+                        let open_orders = open_order_book.getOpenOrders(open_order_data.user_id.clone());
 
-                    let response = MessageToApi::OPEN_ORDERS(OpenOrdersPayload {
-                        payload: open_orders,
-                    });
+                        let response = MessageToApi::OPEN_ORDERS(OpenOrdersPayload {
+                            payload: open_orders,
+                        });
 
-                    if let Ok(json) = serde_json::to_string(&response) {
-                        if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
-                            let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                        if let Ok(json) = protocol::encode(&response) {
+                            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                                let _ = redis_manager.send_to_api(&msg.client_id, &json);
+                            }
                         }
+                    } else {
+                        self.send_error(&msg.client_id, &EngineError::UnknownMarket(open_order_data.market.clone()));
                     }
                 }
             },
         };
-        println!("wassup");
+
+        // debug builds stop at the first input that leaves the books or balances inconsistent
+        if cfg!(debug_assertions) {
+            let violations = invariants::check(self);
+            debug_assert!(violations.is_empty(), "engine invariants broken by input {}: {}", self.sequence, violations.join("; "));
+        }
+    }
+
+    // derived from the epoch and input sequence so replaying the journal hands out the same ids
+    fn next_order_id(&self) -> String {
+        Uuid::from_u64_pair(self.order_id_epoch, self.sequence).to_string()
+    }
+
+    fn send_error(&self, client_id: &str, error: &EngineError) {
+        let response = MessageToApi::ERROR(ErrorPayload::from(error));
+
+        if let Ok(json) = protocol::encode(&response) {
+            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                let _ = redis_manager.send_to_api(client_id, &json);
+            }
+        }
+    }
+
+    /// Engine id of the order a request names, looking a client_order_id up among
+    /// the user's resting and pending orders in the market.
+    fn resolve_order_id(&self, orderbook_index: usize, user_id: &str, order_id: &Option<String>, client_order_id: &Option<String>) -> Result<String, EngineError> {
+        let client_order_id = match (order_id, client_order_id) {
+            (Some(order_id), _) => return Ok(order_id.clone()),
+            (None, Some(client_order_id)) => client_order_id,
+            (None, None) => return Err(EngineError::InvalidOrder("order_id or client_order_id is required".to_string())),
+        };
+        let orderbook = &self.orderbooks[orderbook_index];
+        if let Some(order_id) = orderbook.client_order(user_id, client_order_id) {
+            return Ok(order_id.clone());
+        }
+        self.trigger_books
+            .get(&orderbook.ticker())
+            .and_then(|trigger_book| trigger_book.client_order(user_id, client_order_id))
+            .map(|stop| stop.order.order_id.clone())
+            .ok_or_else(|| EngineError::OrderNotFound(client_order_id.clone()))
+    }
+
+    // new orders, post-only markets only let post-only ones through
+    fn check_accepts_order(&self, orderbook_index: usize, time_in_force: TimeInForce) -> Result<(), EngineError> {
+        let orderbook = &self.orderbooks[orderbook_index];
+        let accepted = match orderbook.status {
+            MarketStatus::PostOnly => time_in_force == TimeInForce::PostOnly,
+            status => status.accepts_orders(),
+        };
+        if !accepted {
+            return Err(EngineError::MarketUnavailable { market: orderbook.ticker(), status: orderbook.status });
+        }
+        Ok(())
+    }
+
+    fn check_accepts_cancels(&self, orderbook_index: usize) -> Result<(), EngineError> {
+        let orderbook = &self.orderbooks[orderbook_index];
+        if !orderbook.status.accepts_cancels() {
+            return Err(EngineError::MarketUnavailable { market: orderbook.ticker(), status: orderbook.status });
+        }
+        Ok(())
+    }
+
+    /// The user's open order with `order_id`, resting or pending.
+    fn open_order(&self, orderbook_index: usize, user_id: &str, order_id: &str) -> Option<OrderPayload> {
+        let orderbook = &self.orderbooks[orderbook_index];
+        let market = orderbook.ticker();
+        let payload = |order: &Order, status, trigger_price| OrderPayload {
+            order_id: order.order_id.clone(),
+            client_order_id: order.client_order_id.clone(),
+            market: market.clone(),
+            side: order.side,
+            order_type: order.order_type,
+            price: matches!(order.order_type, OrderType::Limit | OrderType::StopLimit).then_some(order.price),
+            quantity: order.quantity,
+            executed_qty: order.filled,
+            trigger_price,
+            expire_at: order.expire_at,
+            status,
+        };
+        if let Some(order) = orderbook.get_order(order_id) {
+            return (order.user_id == user_id).then(|| payload(order, OrderStatus::Open, None));
+        }
+        let stop = self.trigger_books.get(&market)?.get(order_id)?;
+        (stop.order.user_id == user_id).then(|| payload(&stop.order, OrderStatus::Pending, Some(stop.trigger_price)))
+    }
+
+    /// Look one of the user's open orders up by engine id or client_order_id.
+    pub fn get_order(&self, get_data: &GetOrderData) -> Result<OrderPayload, EngineError> {
+        let orderbook_index = self.orderbooks
+            .iter()
+            .position(|o| o.ticker() == get_data.market)
+            .ok_or_else(|| EngineError::UnknownMarket(get_data.market.clone()))?;
+        let order_id = self.resolve_order_id(orderbook_index, &get_data.user_id, &get_data.order_id, &get_data.client_order_id)?;
+        // someone else's order is reported as missing rather than confirmed
+        self.open_order(orderbook_index, &get_data.user_id, &order_id)
+            .ok_or(EngineError::OrderNotFound(order_id))
+    }
+
+    /// Take a resting or pending stop order off its book and release what it locked.
+    pub fn cancel_order(&mut self, cancel_data: &CancelOrderData) -> Result<OrderCancelledPayload, EngineError> {
+        let market = cancel_data.market.as_str();
+        let orderbook_index = self.orderbooks
+            .iter()
+            .position(|o| o.ticker() == market)
+            .ok_or_else(|| EngineError::UnknownMarket(market.to_string()))?;
+        self.check_accepts_cancels(orderbook_index)?;
+        let order_id = self.resolve_order_id(orderbook_index, &cancel_data.user_id, &cancel_data.order_id, &cancel_data.client_order_id)?;
+        let order_id = order_id.as_str();
+        let base = self.orderbooks[orderbook_index].base_asset.clone();
+        let quote = self.orderbooks[orderbook_index].quote_asset.clone();
+
+        if let Some(order) = self.orderbooks[orderbook_index].get_order(order_id).cloned() {
+            if order.user_id != cancel_data.user_id {
+                return Err(EngineError::NotOwner { order_id: order_id.to_string() });
+            }
+            let orderbook = &mut self.orderbooks[orderbook_index];
+            let price = match order.side {
+                Side::Buy => orderbook.cancelBid(order_id),
+                Side::Sell => orderbook.cancelAsk(order_id),
+            };
+            let remaining_qty = order.quantity - order.filled;
+            let (locked_asset, amount) = match order.side {
+                Side::Buy => (&quote, remaining_qty * order.price),
+                Side::Sell => (&base, remaining_qty),
+            };
+            self.release_funds(&order.user_id, locked_asset, amount, order_id);
+
+            self.publish_order_update(&order, market, OrderStatus::Cancelled, None);
+            if let Some(price) = price {
+                self.send_updated_depth(price.to_string(), market.to_string());
+            }
+            return Ok(OrderCancelledPayload {
+                order_id: order_id.to_string(),
+                executed_qty: order.filled,
+                remaining_qty,
+            });
+        }
+
+        let trigger_book = self.trigger_books
+            .get_mut(market)
+            .ok_or_else(|| EngineError::UnknownMarket(market.to_string()))?;
+        match trigger_book.get(order_id) {
+            None => return Err(EngineError::OrderNotFound(order_id.to_string())),
+            Some(stop) if stop.order.user_id != cancel_data.user_id => {
+                return Err(EngineError::NotOwner { order_id: order_id.to_string() });
+            }
+            Some(_) => {}
+        }
+        let stop = trigger_book
+            .remove(order_id)
+            .ok_or_else(|| EngineError::OrderNotFound(order_id.to_string()))?;
+
+        // pending stop, give back what was locked when it was placed
+        let locked_asset = if stop.order.side == Side::Buy { &quote } else { &base };
+        self.release_funds(&stop.order.user_id, locked_asset, stop.lock_amount, order_id);
+
+        self.publish_order_update(&stop.order, market, OrderStatus::Cancelled, Some(stop.trigger_price));
+        Ok(OrderCancelledPayload {
+            order_id: order_id.to_string(),
+            executed_qty: Decimal::ZERO,
+            // quote-sized stops have no base quantity to report
+            remaining_qty: if stop.quote_quantity.is_some() { Decimal::ZERO } else { stop.order.quantity },
+        })
+    }
+
+    /// Cancel every open order of a user, narrowed by market and side. Funds are
+    /// released once per asset and depth goes out once per touched price level.
+    /// Markets not taking cancels are skipped, or refused when named.
+    pub fn cancel_all_orders(&mut self, cancel_data: &CancelAllOrdersData) -> Result<Vec<OrderCancelledPayload>, EngineError> {
+        let user_id = cancel_data.user_id.as_str();
+        let orderbook_indexes: Vec<usize> = match &cancel_data.market {
+            Some(market) => {
+                let orderbook_index = self.orderbooks
+                    .iter()
+                    .position(|o| &o.ticker() == market)
+                    .ok_or_else(|| EngineError::UnknownMarket(market.clone()))?;
+                self.check_accepts_cancels(orderbook_index)?;
+                vec![orderbook_index]
+            }
+            None => (0..self.orderbooks.len()).filter(|&index| self.check_accepts_cancels(index).is_ok()).collect(),
+        };
+
+        let mut cancelled = Vec::new();
+        for orderbook_index in orderbook_indexes {
+            cancelled.extend(self.cancel_user_orders(orderbook_index, user_id, cancel_data.side));
+        }
+        info!("Cancelled {} orders for user {}", cancelled.len(), user_id);
+        Ok(cancelled)
+    }
+
+    // every resting and pending order of the user in one market, whatever its status
+    fn cancel_user_orders(&mut self, orderbook_index: usize, user_id: &str, side: Option<Side>) -> Vec<OrderCancelledPayload> {
+        let mut cancelled = Vec::new();
+        let market = self.orderbooks[orderbook_index].ticker();
+        let base = self.orderbooks[orderbook_index].base_asset.clone();
+        let quote = self.orderbooks[orderbook_index].quote_asset.clone();
+        let mut prices: Vec<Decimal> = Vec::new();
+
+        for order_id in self.orderbooks[orderbook_index].user_order_ids(user_id, side) {
+            let orderbook = &mut self.orderbooks[orderbook_index];
+            let Some(order) = orderbook.get_order(&order_id).cloned() else { continue };
+            let price = match order.side {
+                Side::Buy => orderbook.cancelBid(&order_id),
+                Side::Sell => orderbook.cancelAsk(&order_id),
+            };
+            let remaining_qty = order.quantity - order.filled;
+            let (asset, amount) = match order.side {
+                Side::Buy => (&quote, remaining_qty * order.price),
+                Side::Sell => (&base, remaining_qty),
+            };
+            self.release_funds(user_id, asset, amount, &order_id);
+            if let Some(price) = price.filter(|price| !prices.contains(price)) {
+                prices.push(price);
+            }
+            self.publish_order_update(&order, &market, OrderStatus::Cancelled, None);
+            cancelled.push(OrderCancelledPayload { order_id, executed_qty: order.filled, remaining_qty });
+        }
+
+        let stop_ids: Vec<String> = self.trigger_books
+            .get(&market)
+            .map(|trigger_book| trigger_book
+                .orders()
+                .filter(|stop| stop.order.user_id == user_id && side.is_none_or(|side| stop.order.side == side))
+                .map(|stop| stop.order.order_id.clone())
+                .collect())
+            .unwrap_or_default();
+        for order_id in stop_ids {
+            let Some(stop) = self.trigger_books.get_mut(&market).and_then(|trigger_book| trigger_book.remove(&order_id)) else { continue };
+            let asset = if stop.order.side == Side::Buy { &quote } else { &base };
+            self.release_funds(user_id, asset, stop.lock_amount, &order_id);
+            self.publish_order_update(&stop.order, &market, OrderStatus::Cancelled, Some(stop.trigger_price));
+            cancelled.push(OrderCancelledPayload {
+                order_id,
+                executed_qty: Decimal::ZERO,
+                remaining_qty: if stop.quote_quantity.is_some() { Decimal::ZERO } else { stop.order.quantity },
+            });
+        }
+
+        for price in prices {
+            self.send_updated_depth(price.to_string(), market.clone());
+        }
+        cancelled
+    }
+
+    /// Move a market to `status`, publishing the change on its status channel.
+    /// Closing cancels every order still open on it and releases the funds.
+    pub fn set_market_status(&mut self, status_data: &SetMarketStatusData) -> Result<MarketStatusPayload, EngineError> {
+        let market = status_data.market.as_str();
+        let orderbook_index = self.orderbooks
+            .iter()
+            .position(|o| o.ticker() == market)
+            .ok_or_else(|| EngineError::UnknownMarket(market.to_string()))?;
+        let previous = self.orderbooks[orderbook_index].status;
+        self.orderbooks[orderbook_index].status = status_data.status;
+
+        let mut cancelled = 0;
+        if status_data.status == MarketStatus::Closed {
+            let orderbook = &self.orderbooks[orderbook_index];
+            let mut user_ids: Vec<String> = orderbook
+                .resting_orders(Side::Buy)
+                .into_iter()
+                .chain(orderbook.resting_orders(Side::Sell))
+                .map(|order| order.user_id)
+                .chain(self.trigger_books.get(market).into_iter().flat_map(|trigger_book| trigger_book.orders().map(|stop| stop.order.user_id.clone())))
+                .collect();
+            user_ids.sort();
+            user_ids.dedup();
+            for user_id in user_ids {
+                cancelled += self.cancel_user_orders(orderbook_index, &user_id, None).len();
+            }
+        }
+
+        info!("Market {} status {} -> {}, {} orders cancelled", market, previous.as_str(), status_data.status.as_str(), cancelled);
+        let channel = channels::status(market);
+        let status_update = serde_json::json!({
+            "stream": channel,
+            "data": {
+                "e": "marketStatus",
+                "s": market,
+                "X": status_data.status,
+            }
+        });
+        if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+            if let Ok(json) = serde_json::to_string(&status_update) {
+                let _ = redis_manager.publish_ws(&channel, &json);
+            }
+        }
+
+        Ok(MarketStatusPayload { market: market.to_string(), status: status_data.status, cancelled })
+    }
+
+    /// Arm, refresh or with a zero timeout disarm the user's dead man's switch,
+    /// counted from the engine clock.
+    pub fn set_dead_man_switch(&mut self, switch_data: &DeadManSwitchData) -> DeadManSwitchPayload {
+        if switch_data.timeout_secs == 0 {
+            self.dead_man_switches.disarm(&switch_data.user_id);
+            info!("Dead man's switch disarmed for user {}", switch_data.user_id);
+            return DeadManSwitchPayload { timeout_secs: 0, deadline: None };
+        }
+        let deadline = self.time + switch_data.timeout_secs.saturating_mul(1000);
+        self.dead_man_switches.arm(&switch_data.user_id, deadline);
+        DeadManSwitchPayload { timeout_secs: switch_data.timeout_secs, deadline: Some(deadline) }
+    }
+
+    /// Whether a dead man's switch or order expiry ran out by `now`, the main loop then feeds a TICK.
+    pub fn timers_due(&self, now: u64) -> bool {
+        self.dead_man_switches.is_due(now) || self.order_expiries.is_due(now)
+    }
+
+    fn fire_dead_man_switches(&mut self) {
+        for user_id in self.dead_man_switches.expire(self.time) {
+            // runs in every market, halted ones included, as it only takes risk off
+            let cancelled: usize = (0..self.orderbooks.len())
+                .map(|orderbook_index| self.cancel_user_orders(orderbook_index, &user_id, None).len())
+                .sum();
+            info!("Dead man's switch fired for user {}, cancelled {} orders", user_id, cancelled);
+        }
+    }
+
+    fn arm_expiry(&mut self, order: &Order) {
+        if let Some(expire_at) = order.expire_at {
+            self.order_expiries.arm(&order.order_id, expire_at);
+        }
+    }
+
+    fn expire_orders(&mut self) {
+        for order_id in self.order_expiries.expire(self.time) {
+            self.expire_order(&order_id);
+        }
+    }
+
+    /// Take a good-til-time order off its book or trigger book and release what
+    /// it still locks. Nothing happens if it already filled or was cancelled.
+    fn expire_order(&mut self, order_id: &str) {
+        for orderbook_index in 0..self.orderbooks.len() {
+            let market = self.orderbooks[orderbook_index].ticker();
+            let base = self.orderbooks[orderbook_index].base_asset.clone();
+            let quote = self.orderbooks[orderbook_index].quote_asset.clone();
+
+            if let Some(order) = self.orderbooks[orderbook_index].get_order(order_id).cloned() {
+                let orderbook = &mut self.orderbooks[orderbook_index];
+                let price = match order.side {
+                    Side::Buy => orderbook.cancelBid(order_id),
+                    Side::Sell => orderbook.cancelAsk(order_id),
+                };
+                let remaining_qty = order.quantity - order.filled;
+                let (asset, amount) = match order.side {
+                    Side::Buy => (&quote, remaining_qty * order.price),
+                    Side::Sell => (&base, remaining_qty),
+                };
+                self.release_funds(&order.user_id, asset, amount, order_id);
+                if let Some(price) = price {
+                    self.send_updated_depth(price.to_string(), market.clone());
+                }
+                self.publish_order_update(&order, &market, OrderStatus::Expired, None);
+                info!("Order {} expired with {} unfilled", order_id, remaining_qty);
+                return;
+            }
+
+            let Some(stop) = self.trigger_books.get_mut(&market).and_then(|trigger_book| trigger_book.remove(order_id)) else { continue };
+            let asset = if stop.order.side == Side::Buy { &quote } else { &base };
+            self.release_funds(&stop.order.user_id, asset, stop.lock_amount, order_id);
+            self.publish_order_update(&stop.order, &market, OrderStatus::Expired, Some(stop.trigger_price));
+            info!("Stop order {} expired before triggering", order_id);
+            return;
+        }
+    }
+
+    /// Change a resting order's price or quantity in one step, moving only the
+    /// difference in locked funds.
+    pub fn amend_order(&mut self, amend_data: &AmendOrderData) -> Result<OrderAmendedPayload, EngineError> {
+        let market = amend_data.market.as_str();
+        let orderbook_index = self.orderbooks
+            .iter()
+            .position(|o| o.ticker() == market)
+            .ok_or_else(|| EngineError::UnknownMarket(market.to_string()))?;
+        let order_id = self.resolve_order_id(orderbook_index, &amend_data.user_id, &amend_data.order_id, &amend_data.client_order_id)?;
+        let Some(order) = self.orderbooks[orderbook_index].get_order(&order_id).cloned() else {
+            if self.trigger_books.get(market).and_then(|trigger_book| trigger_book.get(&order_id)).is_some() {
+                return Err(EngineError::InvalidOrder("Pending stop orders can't be amended, cancel and place them again".to_string()));
+            }
+            return Err(EngineError::OrderNotFound(order_id));
+        };
+        if order.user_id != amend_data.user_id {
+            return Err(EngineError::NotOwner { order_id });
+        }
+
+        let positive = |value: Option<Decimal>, name: &str| -> Result<Option<Decimal>, EngineError> {
+            match value {
+                Some(v) if v <= Decimal::ZERO => Err(EngineError::InvalidOrder(format!("{} must be greater than 0", name))),
+                v => Ok(v.map(|v| v.normalize())),
+            }
+        };
+        let price = positive(amend_data.price, "Price")?.unwrap_or(order.price);
+        let quantity = positive(amend_data.quantity, "Quantity")?.unwrap_or(order.quantity);
+        if price == order.price && quantity == order.quantity {
+            return Err(EngineError::InvalidOrder("Amend changes neither price nor quantity".to_string()));
+        }
+        let orderbook = &self.orderbooks[orderbook_index];
+        orderbook.check_scale(price, quantity)?;
+        if price * quantity < orderbook.min_notional {
+            return Err(EngineError::InvalidOrder(format!("Order value must be at least {} {}", orderbook.min_notional, orderbook.quote_asset)));
+        }
+        if quantity <= order.filled {
+            return Err(EngineError::InvalidOrder(format!("Quantity must stay above the {} already filled", order.filled)));
+        }
+        let kept_priority = price == order.price && quantity < order.quantity;
+        // shrinking in place only takes risk off, so it goes through wherever cancels do
+        if kept_priority {
+            self.check_accepts_cancels(orderbook_index)?;
+        } else {
+            self.check_accepts_order(orderbook_index, order.time_in_force)?;
+        }
+        if !kept_priority && order.time_in_force == TimeInForce::PostOnly
+            && self.orderbooks[orderbook_index].would_cross(order.side, price)
+        {
+            return Err(EngineError::InvalidOrder("Post-only order would cross at the new price".to_string()));
+        }
+
+        // lock or release only the difference, the rest stays locked throughout
+        let base = self.orderbooks[orderbook_index].base_asset.clone();
+        let quote = self.orderbooks[orderbook_index].quote_asset.clone();
+        let (asset, old_lock, new_lock) = match order.side {
+            Side::Buy => (quote, (order.quantity - order.filled) * order.price, (quantity - order.filled) * price),
+            Side::Sell => (base, order.quantity - order.filled, quantity - order.filled),
+        };
+        let extra = new_lock - old_lock;
+        let available = self.balance_mut(&order.user_id, &asset).available;
+        if available < extra {
+            return Err(EngineError::InsufficientBalance { asset, required: extra, available });
+        }
+        if extra > Decimal::ZERO {
+            self.lock_funds(&order.user_id, &asset, extra, &order_id);
+        } else {
+            self.release_funds(&order.user_id, &asset, -extra, &order_id);
+        }
+
+        if kept_priority {
+            let Some(reduced) = self.orderbooks[orderbook_index].reduce_order(&order_id, quantity).cloned() else {
+                return Err(EngineError::OrderNotFound(order_id));
+            };
+            self.publish_order_update(&reduced, market, OrderStatus::Reduced, None);
+            self.send_updated_depth(price.to_string(), market.to_string());
+            return Ok(OrderAmendedPayload {
+                order_id,
+                price,
+                quantity,
+                executed_qty: reduced.filled,
+                remaining_qty: quantity - reduced.filled,
+                kept_priority,
+                fills: Vec::new(),
+            });
+        }
+
+        // back of the queue at the new price, matching first if it now crosses
+        let Some(mut amended) = self.orderbooks[orderbook_index].take_order(&order_id) else {
+            return Err(EngineError::OrderNotFound(order_id));
+        };
+        amended.price = price;
+        amended.quantity = quantity;
+        self.publish_order_update(&amended, market, OrderStatus::Amended, None);
+        let (executed_qty, fills, _) = self.execute_order(orderbook_index, amended, new_lock, None);
+        if price != order.price {
+            self.send_updated_depth(order.price.to_string(), market.to_string());
+        }
+        self.process_triggers(orderbook_index);
+
+        let remaining_qty = self.orderbooks[orderbook_index]
+            .get_order(&order_id)
+            .map_or(Decimal::ZERO, |resting| resting.quantity - resting.filled);
+        Ok(OrderAmendedPayload {
+            fills: fill_responses(market, order.side, &fills),
+            order_id,
+            price,
+            quantity,
+            executed_qty: order.filled + executed_qty,
+            remaining_qty,
+            kept_priority,
+        })
     }
 
-    pub fn create_order(
-        &mut self,
-        market: &str,
-        price: f64,
-        quantity: u64,
-        side: &str,
-        user_id: &str,
-    ) -> Result<(f64, Vec<Fill>, String), String> {
+    /// Place an order, unless its client_order_id was already accepted. A retry of
+    /// an order still open gets that order back, one that has since filled, been
+    /// cancelled or killed is refused with the order it went to.
+    pub fn create_order(&mut self, create_data: &CreateOrderData) -> Result<OrderResult, EngineError> {
+        let user_id = create_data.user_id.as_str();
+        if let Some(client_order_id) = &create_data.client_order_id {
+            if client_order_id.is_empty() || client_order_id.len() > 64 {
+                return Err(EngineError::InvalidOrder("client_order_id must be 1 to 64 characters".to_string()));
+            }
+            let previous = match self.client_orders.get(user_id, client_order_id) {
+                Some(previous) => Some(previous.clone()),
+                // open orders from snapshots taken before client_order_ids were remembered
+                None => (0..self.orderbooks.len()).find_map(|index| {
+                    let order_id = self.resolve_order_id(index, user_id, &None, &Some(client_order_id.clone())).ok()?;
+                    Some(ClientOrder { market: self.orderbooks[index].ticker(), order_id })
+                }),
+            };
+            if let Some(previous) = previous {
+                let existing = self.orderbooks
+                    .iter()
+                    .position(|o| o.ticker() == previous.market)
+                    .and_then(|index| self.open_order(index, user_id, &previous.order_id));
+                return match existing {
+                    Some(existing) => Ok(OrderResult {
+                        order_id: existing.order_id.clone(),
+                        price: existing.price.unwrap_or_default(),
+                        quantity: existing.quantity,
+                        executed_qty: existing.executed_qty,
+                        fills: Vec::new(),
+                        outcome: OrderOutcome::Duplicate(existing),
+                    }),
+                    None => Err(EngineError::DuplicateClientOrderId {
+                        client_order_id: client_order_id.clone(),
+                        order_id: previous.order_id,
+                    }),
+                };
+            }
+        }
+
+        let result = self.place_order(create_data)?;
+        if let Some(client_order_id) = &create_data.client_order_id {
+            let order = ClientOrder { market: create_data.market.clone(), order_id: result.order_id.clone() };
+            self.client_orders.record(user_id, client_order_id, order);
+        }
+        Ok(result)
+    }
+
+    fn place_order(&mut self, create_data: &CreateOrderData) -> Result<OrderResult, EngineError> {
+        let market = create_data.market.as_str();
+        let user_id = create_data.user_id.as_str();
+        let side_enum = create_data.side;
         let orderbook_index = match self.orderbooks
                             .iter()
                             .position(|o| o.ticker() == market) {
             Some(index) => index,
-            None => return Err("No orderbook found".to_string())
+            None => return Err(EngineError::UnknownMarket(market.to_string()))
         };
 
-        if let Some((base, quote)) = market.split_once('-') {
-            println!("Base: {}", base);   // ex: BTC
-            println!("Quote: {}", quote); // ex: USD
+        // Extract base and quote from market string
+        let (base, quote) = match market.split_once('-') {
+            Some((b, q)) => (b.to_string(), q.to_string()),
+            None => return Err(EngineError::UnknownMarket(market.to_string())),
+        };
+
+        let positive = |value: Option<Decimal>, name: &str| -> Result<Option<Decimal>, EngineError> {
+            match value {
+                Some(v) if v <= Decimal::ZERO => Err(EngineError::InvalidOrder(format!("{} must be greater than 0", name))),
+                // normalize so 100.5 and 100.50 land on the same price level
+                v => Ok(v.map(|v| v.normalize())),
+            }
+        };
+        let limit_price = positive(create_data.price, "Price")?;
+        let quantity = positive(create_data.quantity, "Quantity")?;
+        let quote_quantity = positive(create_data.quote_quantity, "Quote quantity")?;
+        let worst_price = positive(create_data.worst_price, "Worst price")?;
+        let trigger_price = positive(create_data.trigger_price, "Trigger price")?;
+
+        let is_stop = matches!(create_data.order_type, OrderType::StopMarket | OrderType::StopLimit);
+        if is_stop != trigger_price.is_some() {
+            return Err(EngineError::InvalidOrder("trigger_price is required for stop orders and only valid on them".to_string()));
+        }
+        if let Some(trigger_price) = trigger_price {
+            self.orderbooks[orderbook_index].check_scale(trigger_price, Decimal::ZERO)?;
+        }
 
-            let btc = base.to_string();
-            let usd = quote.to_string();
+        // price the order rests or sweeps up to, and the funds locked for it
+        let (mut price, quantity, mut lock_amount) = match create_data.order_type {
+            OrderType::Limit | OrderType::StopLimit => {
+                let (Some(price), Some(quantity)) = (limit_price, quantity) else {
+                    return Err(EngineError::InvalidOrder("Limit orders need a price and a quantity".to_string()));
+                };
+                if quote_quantity.is_some() || worst_price.is_some() {
+                    return Err(EngineError::InvalidOrder("quote_quantity and worst_price only apply to market orders".to_string()));
+                }
+                self.orderbooks[orderbook_index].check_scale(price, quantity)?;
+                let lock_amount = match side_enum {
+                    Side::Buy => quantity * price,
+                    Side::Sell => quantity,
+                };
+                (price, quantity, lock_amount)
+            }
+            OrderType::Market | OrderType::StopMarket => {
+                if limit_price.is_some() {
+                    return Err(EngineError::InvalidOrder("Market orders take worst_price instead of price".to_string()));
+                }
+                let orderbook = &self.orderbooks[orderbook_index];
+                let bound = match side_enum {
+                    Side::Buy => worst_price.unwrap_or(Decimal::MAX),
+                    Side::Sell => worst_price.unwrap_or(Decimal::ZERO),
+                };
+                if let Some(worst_price) = worst_price {
+                    orderbook.check_scale(worst_price, Decimal::ZERO)?;
+                }
+                match (side_enum, quantity, quote_quantity) {
+                    (Side::Buy, Some(quantity), None) => {
+                        orderbook.check_scale(Decimal::ZERO, quantity)?;
+                        // a stop can't know the book it will meet, so its lock needs a price cap
+                        let cost = match (create_data.order_type, worst_price) {
+                            (OrderType::StopMarket, Some(worst_price)) => quantity * worst_price,
+                            (OrderType::StopMarket, None) => {
+                                return Err(EngineError::InvalidOrder("Stop-market buys sized by quantity need a worst_price".to_string()));
+                            }
+                            _ => orderbook.sweep_cost(quantity, bound, user_id, create_data.self_trade_prevention),
+                        };
+                        (bound, quantity, cost)
+                    }
+                    // spend a quote amount, the base quantity is whatever it buys
+                    (Side::Buy, None, Some(quote_quantity)) => (bound, Decimal::MAX, quote_quantity),
+                    (Side::Sell, Some(quantity), None) => {
+                        orderbook.check_scale(Decimal::ZERO, quantity)?;
+                        (bound, quantity, quantity)
+                    }
+                    (Side::Sell, _, Some(_)) => {
+                        return Err(EngineError::InvalidSide("quote_quantity is only supported on market buys".to_string()));
+                    }
+                    (Side::Buy, _, _) => return Err(EngineError::InvalidOrder("Market buys need exactly one of quantity or quote_quantity".to_string())),
+                    (Side::Sell, _, _) => return Err(EngineError::InvalidOrder("Market sells need a quantity".to_string())),
+                }
+            }
+        };
 
-            println!("btc = {}, usd = {}", btc, usd);
+        // market orders sized by quantity can't know their notional up front
+        let min_notional = self.orderbooks[orderbook_index].min_notional;
+        let notional = match create_data.order_type {
+            OrderType::Limit | OrderType::StopLimit => Some(price * quantity),
+            OrderType::Market | OrderType::StopMarket => quote_quantity,
+        };
+        if notional.is_some_and(|notional| notional < min_notional) {
+            return Err(EngineError::InvalidOrder(format!("Order value must be at least {} {}", min_notional, quote)));
         }
 
-        let side_enum = match side {
-            "buy" => Side::Buy,
-            "sell" => Side::Sell,
-            _ => return Err("Invalid side".to_string())
+        let time_in_force = create_data.time_in_force;
+        match (create_data.order_type, time_in_force) {
+            (OrderType::StopMarket | OrderType::StopLimit, TimeInForce::Fok | TimeInForce::PostOnly) => {
+                return Err(EngineError::InvalidOrder("Stop orders only support GTC and IOC".to_string()));
+            }
+            (OrderType::Market, TimeInForce::PostOnly) => return Err(EngineError::InvalidOrder("Market orders cannot be post-only".to_string())),
+            (OrderType::Market, TimeInForce::Fok) if quote_quantity.is_some() => {
+                return Err(EngineError::InvalidOrder("Fill-or-kill market orders must be sized by quantity".to_string()));
+            }
+            _ => {}
+        }
+        if create_data.reprice_post_only && time_in_force != TimeInForce::PostOnly {
+            return Err(EngineError::InvalidOrder("reprice_post_only only applies to post-only orders".to_string()));
+        }
+        self.check_accepts_order(orderbook_index, time_in_force)?;
+        if let Some(expire_at) = create_data.expire_at {
+            if matches!(create_data.order_type, OrderType::Market) || matches!(time_in_force, TimeInForce::Ioc | TimeInForce::Fok) {
+                return Err(EngineError::InvalidOrder("expire_at only applies to orders that can rest".to_string()));
+            }
+            if expire_at <= self.time {
+                return Err(EngineError::InvalidOrder("expire_at is already in the past".to_string()));
+            }
+        }
+
+        let new_order_id = self.next_order_id();
+        let rejected = |outcome| OrderResult {
+            order_id: new_order_id.clone(),
+            price,
+            quantity,
+            executed_qty: Decimal::ZERO,
+            fills: Vec::new(),
+            outcome,
         };
 
-        // do check and lock funds
-        if let Some((base, quote)) = market.split_once('-') {
-            self.check_and_lock_funds(base.to_string(), quote.to_string(), side_enum.clone(), user_id.to_string(), price.to_string(), quantity);
+        if let Some(trigger_price) = trigger_price {
+            let last_price = self.orderbooks[orderbook_index].current_price;
+            let reached = last_price > Decimal::ZERO && match side_enum {
+                Side::Buy => last_price >= trigger_price,
+                Side::Sell => last_price <= trigger_price,
+            };
+            if reached {
+                return Err(EngineError::InvalidOrder("Trigger price has already been reached".to_string()));
+            }
+
+            self.check_and_lock_funds(base.clone(), quote.clone(), side_enum, user_id.to_string(), lock_amount, &new_order_id)?;
+            let order = Order {
+                price,
+                quantity,
+                order_id: new_order_id.clone(),
+                filled: Decimal::ZERO,
+                side: side_enum,
+                user_id: user_id.to_string(),
+                order_type: create_data.order_type,
+                time_in_force,
+                self_trade_prevention: create_data.self_trade_prevention,
+                client_order_id: create_data.client_order_id.clone(),
+                expire_at: create_data.expire_at,
+            };
+            self.arm_expiry(&order);
+            self.publish_order_update(&order, market, OrderStatus::Pending, Some(trigger_price));
+            if let Some(trigger_book) = self.trigger_books.get_mut(market) {
+                trigger_book.add(StopOrder { order, trigger_price, lock_amount, quote_quantity });
+            }
+            return Ok(rejected(OrderOutcome::Pending { trigger_price }));
+        }
+
+        // FOK and post-only are decided before any funds are locked
+        let orderbook = &self.orderbooks[orderbook_index];
+        let mut outcome = OrderOutcome::Placed;
+        if time_in_force == TimeInForce::Fok {
+            let fillable_qty = orderbook.fillable_qty(side_enum, price, quantity, user_id, create_data.self_trade_prevention);
+            if fillable_qty < quantity {
+                return Ok(rejected(OrderOutcome::Killed { fillable_qty }));
+            }
+        }
+        if time_in_force == TimeInForce::PostOnly && orderbook.would_cross(side_enum, price) {
+            if !create_data.reprice_post_only {
+                return Ok(rejected(OrderOutcome::PostOnlyRejected));
+            }
+            // one tick behind the best opposite price
+            let tick = orderbook.tick_size;
+            let repriced = match side_enum {
+                Side::Buy => orderbook.best_ask().map(|ask| ask - tick),
+                Side::Sell => orderbook.best_bid().map(|bid| bid + tick),
+            };
+            match repriced {
+                Some(repriced) if repriced > Decimal::ZERO => {
+                    outcome = OrderOutcome::Repriced { original_price: price };
+                    price = repriced.normalize();
+                    if side_enum == Side::Buy {
+                        lock_amount = quantity * price;
+                    }
+                }
+                _ => return Ok(rejected(OrderOutcome::PostOnlyRejected)),
+            }
         }
 
-        let new_order_id = Uuid::new_v4().to_string();
+        // do check and lock funds
+        self.check_and_lock_funds(base.clone(), quote.clone(), side_enum, user_id.to_string(), lock_amount, &new_order_id)?;
+
         let order = Order { 
             price: price, 
-            quantity: quantity as f64, 
+            quantity: quantity, 
             order_id: new_order_id.clone(), 
-            filled: 0.0, 
+            filled: Decimal::ZERO, 
             side: side_enum.clone(), 
-            user_id: user_id.to_string() 
+            user_id: user_id.to_string(),
+            order_type: create_data.order_type,
+            time_in_force,
+            self_trade_prevention: create_data.self_trade_prevention,
+            client_order_id: create_data.client_order_id.clone(),
+            expire_at: create_data.expire_at,
         };
-        let order_for_update = order.clone();
+        self.arm_expiry(&order);
+        let (executed_qty, fills, executed) = self.execute_order(orderbook_index, order, lock_amount, quote_quantity);
+        if !matches!(executed, OrderOutcome::Placed) {
+            outcome = executed;
+        }
+        self.process_triggers(orderbook_index);
 
-        // Extract base and quote from market string
-        let (base, quote) = match market.split_once('-') {
-            Some((b, q)) => (b.to_string(), q.to_string()),
-            None => return Err("Invalid market format".to_string()),
-        };
+        Ok(OrderResult {
+            order_id: new_order_id,
+            price,
+            quantity,
+            executed_qty,
+            fills,
+            outcome,
+        })
+    }
 
-        let (executed_qty, fills) = self.orderbooks[orderbook_index].addOrder(order);
+    /// Match an order whose funds are already locked, settle the fills and
+    /// publish the resulting trades and depth.
+    fn execute_order(&mut self, orderbook_index: usize, order: Order, lock_amount: Decimal, quote_quantity: Option<Decimal>) -> (Decimal, Vec<Fill>, OrderOutcome) {
+        let market = self.orderbooks[orderbook_index].ticker();
+        let base = self.orderbooks[orderbook_index].base_asset.clone();
+        let quote = self.orderbooks[orderbook_index].quote_asset.clone();
+        let user_id = order.user_id.clone();
+        let side_enum = order.side;
+        let order_type = order.order_type;
+        let price = order.price;
+        let quantity = order.quantity;
+        // nonzero for an amended order coming back to the book
+        let already_filled = order.filled;
+
+        let rests = OrderBook::can_rest(&order);
+        let mut order_for_update = order.clone();
+
+        let (executed_qty, mut fills, self_trade) = match order_type {
+            OrderType::Market => self.orderbooks[orderbook_index].sweep(order, quote_quantity),
+            _ => self.orderbooks[orderbook_index].addOrder(order),
+        };
+        self.charge_fees(orderbook_index, side_enum, &user_id, &mut fills);
+        if order_type == OrderType::Market {
+            order_for_update.quantity = executed_qty;
+        } else {
+            order_for_update.quantity -= self_trade.taker_cancelled;
+        }
         self.update_balances(
-            user_id.to_string(),
+            user_id.clone(),
             base.clone(),
             quote.clone(),
             side_enum,
-            executed_qty,
             fills.clone(),
         );
+
+        // whatever was locked and neither spent nor still backing a resting order goes back
+        let open_qty = quantity - already_filled - executed_qty - self_trade.taker_cancelled;
+        let resting_lock = match (rests, side_enum) {
+            (true, Side::Buy) => open_qty * price,
+            (true, Side::Sell) => open_qty,
+            (false, _) => Decimal::ZERO,
+        };
+        // a quote-sized market buy has no base quantity left to cancel
+        let cancelled_qty = match (quote_quantity, rests) {
+            (Some(_), _) => Decimal::ZERO,
+            (None, true) => self_trade.taker_cancelled,
+            (None, false) => quantity - already_filled - executed_qty,
+        };
+        let mut outcome = OrderOutcome::Placed;
+        if self_trade.taker_cancelled > Decimal::ZERO || !self_trade.makers.is_empty() {
+            self.release_self_trade_makers(&self_trade, &market, &base, &quote);
+            outcome = OrderOutcome::SelfTradePrevented { cancelled_qty, self_trade };
+        } else if cancelled_qty > Decimal::ZERO {
+            outcome = OrderOutcome::RemainderCancelled(cancelled_qty);
+        }
+        let used = match side_enum {
+            Side::Buy => fills.iter().map(|fill| fill.price * fill.qty).sum::<Decimal>(),
+            Side::Sell => executed_qty,
+        };
+        let leftover = lock_amount - used - resting_lock;
+        if leftover > Decimal::ZERO {
+            let locked_asset = if side_enum == Side::Buy { &quote } else { &base };
+            self.release_funds(&user_id, locked_asset, leftover, &order_for_update.order_id);
+        }
+
         // create db trades
-        self.create_db_trades(fills.clone(), market, user_id.to_string());
-        self.update_db_trades(
-            order_for_update,
-            executed_qty,
-            fills.clone(),
-            market.to_string(),
-            user_id.to_string(),
-        );
+        self.create_db_trades(fills.clone(), &market, side_enum, user_id.clone());
+        order_for_update.filled = already_filled + executed_qty;
+        let status = if rests && open_qty > Decimal::ZERO {
+            OrderStatus::Open
+        } else if order_for_update.filled >= order_for_update.quantity {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::Cancelled
+        };
+        self.update_db_trades(order_for_update, status, fills.clone(), market.clone());
         self.publish_ws_depth_update(
             fills.clone(),
             price,
             side_enum.clone(),
-            market.to_string(),
+            market.clone(),
         );
         self.publish_ws_trades(
             fills.clone(),
-            user_id.to_string(),
-            market.to_string(),
-        );
-        println!(
-            "Creating order: market={}, price={}, quantity={}, side={}, user_id={}",
-            market, price, quantity, side, user_id
+            side_enum,
+            market.clone(),
         );
-        Ok((executed_qty, fills, new_order_id))
+        (executed_qty, fills, outcome)
     }
 
-    // check and lock funds
-    // baseAsset = "BTC" quoteAsset = "USDC" side = "buy" price = "20000" quantity = "0.5" userId = "u1"
-    pub fn check_and_lock_funds(&mut self, baseAsset: String, quoteAsset: String, side: Side, user_id: String, price: String, quantity: u64) {
-        if side == Side::Buy {
-            // self.balances: HashMap<String, HashMap<String, Balance>>
-            // and Balance { available: f64, locked: f64 }
-            let user_balances = self.balances.get_mut(&user_id).expect("User not found");
-            let quote_balance = user_balances.get_mut(&quoteAsset).expect("Quote asset not found");
-            let price_f64: f64 = price.parse().expect("Invalid price");
-
-            // balances.get("u1") = {
-            //     USDT: { available: 15000, locked: 0 },
-            //     BTC: { available: 2, locked: 0 }
-            //  }
-            // Required funds = quantity * price = 0.5 * 20000 = 10000 USDT
-            let required = quantity as f64 * price_f64;
-            if quote_balance.available < required {
-                panic!("Insufficient funds");
-            }
-
-            // USDT.available = 15000 - 10000 = 5000
-            quote_balance.available -= required;
-            // USDT.locked = 0 + 10000 = 10000
-            quote_balance.locked += required;
-        } else {
-            let user_balances = self.balances.get_mut(&user_id).expect("User not found");
-            let base_balance = user_balances.get_mut(&baseAsset).expect("Base asset not found");
-            // Check if user has enough BTC (base asset)
-            // {
-            //    USDT: { available: 5000, locked: 10000 },
-            //    BTC: { available: 2, locked: 0 }
-            // }
-            if base_balance.available < quantity as f64 {
-                panic!("Insufficient funds");
+    fn charge_fees(&self, orderbook_index: usize, taker_side: Side, taker_id: &str, fills: &mut [Fill]) {
+        let orderbook = &self.orderbooks[orderbook_index];
+        let taker = self.fee_schedule(orderbook_index, taker_id);
+        for fill in fills {
+            let maker = self.fee_schedule(orderbook_index, &fill.other_user_id);
+            fees::charge(fill, taker_side, &taker, &maker, orderbook.price_scale, orderbook.quantity_scale);
+        }
+    }
+
+    /// The market's rates with the user's tier discount applied.
+    fn fee_schedule(&self, orderbook_index: usize, user_id: &str) -> FeeSchedule {
+        let level = self.fee_tiers.get(user_id).copied().unwrap_or(0);
+        self.orderbooks[orderbook_index].fees.discounted(fee_tiers::tier(level).discount)
+    }
+
+    /// Release the stop orders the last trade price has reached. Their own
+    /// fills move the price again, so keep going until nothing else fires.
+    fn process_triggers(&mut self, orderbook_index: usize) {
+        let market = self.orderbooks[orderbook_index].ticker();
+        loop {
+            let last_price = self.orderbooks[orderbook_index].current_price;
+            if last_price <= Decimal::ZERO {
+                return;
+            }
+            let Some(trigger_book) = self.trigger_books.get_mut(&market) else { return };
+            let triggered = trigger_book.take_triggered(last_price);
+            if triggered.is_empty() {
+                return;
             }
 
-            // BTC.available = 2 - 0.5 = 1.5
-            base_balance.available -= quantity as f64;
-            // BTC.locked = 0 + 0.5 = 0.5
-            base_balance.locked += quantity as f64;
+            for stop in triggered {
+                let mut order = stop.order;
+                order.order_type = match order.order_type {
+                    OrderType::StopLimit => OrderType::Limit,
+                    _ => OrderType::Market,
+                };
+                debug!("Stop order {} triggered at {} (trigger {})", order.order_id, last_price, stop.trigger_price);
+                self.publish_order_update(&order, &market, OrderStatus::Triggered, Some(stop.trigger_price));
+                self.execute_order(orderbook_index, order, stop.lock_amount, stop.quote_quantity);
+            }
         }
     }
 
-    pub fn update_balances(&mut self, user_id: String, base: String, quote: String, side: Side, executed_qty: f64, fills: Vec<Fill>) {
-        if side == Side::Buy {
-            fills.iter().for_each(|fill| {
-                let price_f64: f64 = fill.price.parse().expect("Invalid price");
-                let qty_f64: f64 = fill.qty as f64;
-
-                let other_user_quote_balance = self.balances
-                    .get_mut(&fill.other_user_id)
-                    .expect("Other user not found")
-                    .get_mut(&quote)
-                    .expect("Quote asset not found");
+    // order lifecycle changes the engine makes on its own go to the DB queue and to the owner's order channel
+    pub fn publish_order_update(&mut self, order: &Order, market: &str, status: OrderStatus, trigger_price: Option<Decimal>) {
+        let db_update = PushToDb::ORDER_UPDATE(ORDERUPDATEDATA {
+            order_id: order.order_id.clone(),
+            exec_qty: order.filled,
+            market: Some(market.to_string()),
+            price: (order.order_type != OrderType::Market && order.order_type != OrderType::StopMarket).then_some(order.price),
+            quantity: Some(order.quantity),
+            side: Some(order.side),
+            status: Some(status),
+        });
 
-                other_user_quote_balance.available += price_f64 * qty_f64;
-                other_user_quote_balance.locked -= price_f64 * qty_f64;
+        let channel = channels::order(&order.user_id);
+        let order_data = serde_json::json!({
+            "stream": channel,
+            "data": {
+                "e": "orderUpdate",
+                "i": order.order_id,
+                "c": order.client_order_id,
+                "s": market,
+                "S": order.side,
+                "o": order.order_type,
+                "q": order.quantity,
+                "P": trigger_price,
+                "X": status,
+            }
+        });
 
-                let user_base_balance = self.balances
-                    .get_mut(&user_id)
-                    .expect("User not found")
-                    .get_mut(&base)
-                    .expect("Base asset not found");
+        if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+            if let Ok(json) = protocol::encode(&db_update) {
+                let _ = redis_manager.push_db(&json);
+            }
+            if let Ok(json) = serde_json::to_string(&order_data) {
+                let _ = redis_manager.publish_ws(&channel, &json);
+            }
+        }
+    }
 
-                user_base_balance.locked -= qty_f64;
+    /// Give back what backed the resting orders self-trade prevention cut, and
+    /// tell the owner and the book watchers about them.
+    fn release_self_trade_makers(&mut self, self_trade: &SelfTrade, market: &str, base: &str, quote: &str) {
+        let mut prices: Vec<Decimal> = Vec::new();
+        for maker in &self_trade.makers {
+            let (asset, amount) = match maker.order.side {
+                Side::Buy => (quote, maker.cancelled_qty * maker.order.price),
+                Side::Sell => (base, maker.cancelled_qty),
+            };
+            self.release_funds(&maker.order.user_id, asset, amount, &maker.order.order_id);
+
+            let status = if maker.order.filled >= maker.order.quantity {
+                OrderStatus::Cancelled
+            } else {
+                OrderStatus::Reduced
+            };
+            debug!("Self-trade prevention: order {} {:?} by {}", maker.order.order_id, status, maker.cancelled_qty);
+            self.publish_order_update(&maker.order, market, status, None);
+            if !prices.contains(&maker.order.price) {
+                prices.push(maker.order.price);
+            }
+        }
+        for price in prices {
+            self.send_updated_depth(price.to_string(), market.to_string());
+        }
+    }
 
-                user_base_balance.available += qty_f64;
+    // check and lock funds
+    // baseAsset = "BTC" quoteAsset = "USDC" side = "buy" required = "10000" userId = "u1"
+    // `required` is in quote for buys and in base for sells, `order_id` is the order it backs
+    pub fn check_and_lock_funds(&mut self, baseAsset: String, quoteAsset: String, side: Side, user_id: String, required: Decimal, order_id: &str) -> Result<(), EngineError> {
+        // buys lock quote, sells lock base
+        let asset = if side == Side::Buy { quoteAsset } else { baseAsset };
+
+        // self.balances: HashMap<String, HashMap<String, Balance>>
+        // and Balance { available: Decimal, locked: Decimal }
+        let user_balances = self.balances
+            .get(&user_id)
+            .ok_or_else(|| EngineError::UnknownUser(user_id.clone()))?;
+        let balance = user_balances
+            .get(&asset)
+            .ok_or_else(|| EngineError::UnknownAsset { user_id: user_id.clone(), asset: asset.clone() })?;
+
+        // balances.get("u1") = {
+        //     USDT: { available: 15000, locked: 0 },
+        //     BTC: { available: 2, locked: 0 }
+        //  }
+        // Required funds = quantity * price = 0.5 * 20000 = 10000 USDT
+        if balance.available < required {
+            return Err(EngineError::InsufficientBalance {
+                asset,
+                required,
+                available: balance.available,
             });
-    } else {
-        fills.iter().for_each(|fill| {
-            let price_f64: f64 = fill.price.parse().expect("Invalid price");
-            let qty_f64: f64 = fill.qty as f64;
-
-            let other_user_quote_balance = self.balances
-                .get_mut(&fill.other_user_id)
-                .expect("Other user not found")
-                .get_mut(&quote)
-                .expect("Quote asset not found");
-
-            other_user_quote_balance.locked -= price_f64 * qty_f64;
-            other_user_quote_balance.available += price_f64 * qty_f64;
-
-            let user_base_balance = self.balances
-                .get_mut(&user_id)
-                .expect("User not found")
-                .get_mut(&base)
-                .expect("Base asset not found");
-
-            user_base_balance.locked -= qty_f64;
-            user_base_balance.available += qty_f64;
-        });
+        }
+
+        // USDT.available = 15000 - 10000 = 5000
+        // USDT.locked = 0 + 10000 = 10000
+        self.lock_funds(&user_id, &asset, required, order_id);
+        Ok(())
+    }
+
+    /// Balance of `asset` for `user_id`, created empty if the user has never held it.
+    fn balance_mut(&mut self, user_id: &str, asset: &str) -> &mut UserBalance {
+        self.balances
+            .entry(user_id.to_string())
+            .or_default()
+            .entry(asset.to_string())
+            .or_default()
+    }
+
+    /// Apply a ledger transaction to the balances and stream it to the db.
+    /// Every balance change goes through here.
+    fn post(&mut self, posting: Posting) {
+        if posting.entries.is_empty() {
+            return;
+        }
+        if let Some((asset, sum)) = posting.imbalance() {
+            error!("Unbalanced {} posting for {}: {} legs sum to {}", posting.reason.as_str(), posting.reference, asset, sum);
+            debug_assert!(false, "unbalanced ledger posting");
+        }
+        for entry in &posting.entries {
+            match entry.bucket {
+                LedgerBucket::Available => self.balance_mut(&entry.account, &entry.asset).available += entry.delta,
+                LedgerBucket::Locked => self.balance_mut(&entry.account, &entry.asset).locked += entry.delta,
+                // withdrawal holds are kept on the withdrawal, and the outside world has no balance
+                LedgerBucket::Withdrawing | LedgerBucket::External => {}
+            }
+        }
+
+        self.ledger_sequence += 1;
+        let transaction = LedgerTransaction {
+            transaction_id: self.ledger_sequence,
+            sequence: self.sequence,
+            reason: posting.reason,
+            reference: posting.reference,
+            timestamp: self.time,
+            entries: posting.entries,
+        };
+        if let Ok(json) = protocol::encode(&PushToDb::LEDGER(transaction)) {
+            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                let _ = redis_manager.push_db(&json);
+            }
+        }
+    }
+
+    fn lock_funds(&mut self, user_id: &str, asset: &str, amount: Decimal, order_id: &str) {
+        self.post(Posting::new(LedgerReason::OrderLock, order_id)
+            .transfer(user_id, asset, LedgerBucket::Available, LedgerBucket::Locked, amount));
+    }
+
+    fn release_funds(&mut self, user_id: &str, asset: &str, amount: Decimal, order_id: &str) {
+        self.post(Posting::new(LedgerReason::OrderRelease, order_id)
+            .transfer(user_id, asset, LedgerBucket::Locked, LedgerBucket::Available, amount));
+    }
+
+    // each side is credited what it received less its fee, the fees go to FEE_ACCOUNT.
+    // Takers pay from their locked funds, any unspent lock is released by the caller
+    pub fn update_balances(&mut self, user_id: String, base: String, quote: String, side: Side, fills: Vec<Fill>) {
+        let market = format!("{}-{}", base, quote);
+        for fill in &fills {
+            let quote_qty = fill.price * fill.qty;
+            let (buyer, seller, buyer_fee, seller_fee) = match side {
+                Side::Buy => (user_id.as_str(), fill.other_user_id.as_str(), fill.taker_fee, fill.maker_fee),
+                Side::Sell => (fill.other_user_id.as_str(), user_id.as_str(), fill.maker_fee, fill.taker_fee),
+            };
+            // the buyer's quote was locked at the fill price, the seller's base by quantity
+            let posting = Posting::new(LedgerReason::Trade, format!("{}:{}", market, fill.trade_id))
+                .entry(buyer, LedgerBucket::Locked, &quote, -quote_qty)
+                .entry(seller, LedgerBucket::Available, &quote, quote_qty - seller_fee)
+                .entry(FEE_ACCOUNT, LedgerBucket::Available, &quote, seller_fee)
+                .entry(seller, LedgerBucket::Locked, &base, -fill.qty)
+                .entry(buyer, LedgerBucket::Available, &base, fill.qty - buyer_fee)
+                .entry(FEE_ACCOUNT, LedgerBucket::Available, &base, buyer_fee);
+            self.post(posting);
         }
     }
 
-    pub fn create_db_trades(&mut self, fills: Vec<Fill>, market: &str, user_id: String) {
+    pub fn create_db_trades(&mut self, fills: Vec<Fill>, market: &str, side: Side, user_id: String) {
+        let (taker_fee_asset, maker_fee_asset) = match market.split_once('-') {
+            Some((base, quote)) => fees::fee_assets(side, base, quote),
+            None => ("", ""),
+        };
         //TODO: implement
         fills.iter().for_each(|fills| {
-            println!("Trade ID: {}", fills.trade_id);
-            println!("Price: {}", fills.price);
-            println!("Qty: {}", fills.qty);
-            println!("Other User ID: {}", fills.other_user_id);
-            println!("Market Order ID: {}", fills.market_order_id);
-
             //redis manager call type trade added
             let response = PushToDb::TRADE_ADDED(TRADEADDEDDATA {
                 market: market.to_string(),
-                id: fills.trade_id.to_string(),
-                is_buyer_maker: fills.other_user_id == user_id,
-                price: fills.price.to_string(),
-                quantity: fills.qty.to_string(),
-                quote_quantity: ((fills.qty as f64) * fills.price.parse::<f64>().unwrap_or(0.0)).to_string(),
-                timestamp: chrono::Utc::now().timestamp(),
+                trade_id: fills.trade_id,
+                // the buyer made the book when the taker was selling
+                is_buyer_maker: side == Side::Sell,
+                price: fills.price,
+                quantity: fills.qty,
+                quote_quantity: fills.qty * fills.price,
+                // engine time, so a trade sent again on recovery keeps its key
+                timestamp: (self.time / 1000) as i64,
+                maker_fee: fills.maker_fee,
+                maker_fee_asset: maker_fee_asset.to_string(),
+                taker_fee: fills.taker_fee,
+                taker_fee_asset: taker_fee_asset.to_string(),
+                maker_user_id: fills.other_user_id.clone(),
+                taker_user_id: user_id.clone(),
             });
 
-            if let Ok(json) = serde_json::to_string(&response) {
+            if let Ok(json) = protocol::encode(&response) {
                 if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
                     let _ = redis_manager.push_db(&json);
                 }
@@ -488,55 +1648,49 @@ impl Engine {
         })
     }
 
-    pub fn update_db_trades(&mut self, order: Order, executed_qty: f64, fills: Vec<Fill>, market: String, user_id: String) {
-
-        //redis manager call type ORDER_UPDATE here too
-        let response = PushToDb::ORDER_UPDATE(ORDERUPDATEDATA {
+    // the taker's order row and the row of every maker it hit, by engine order id
+    pub fn update_db_trades(&mut self, order: Order, status: OrderStatus, fills: Vec<Fill>, market: String) {
+        let maker_side = match order.side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        let mut updates = vec![ORDERUPDATEDATA {
             order_id: order.order_id.clone(),
-            exec_qty: executed_qty,
-            market: Some(market.to_string()),
-            price: Some(order.price.to_string()),
-            quantity: Some(order.quantity.to_string()),
+            exec_qty: order.filled,
+            market: Some(market.clone()),
+            price: (order.order_type == OrderType::Limit).then_some(order.price),
+            quantity: Some(order.quantity),
             side: Some(order.side),
-        });
-
-
-        //TODO: implement
-        fills.iter().for_each(|fill| {
-            println!("Trade ID: {}", fill.trade_id);
-            println!("Price: {}", fill.price);
-            println!("Qty: {}", fill.qty);
-            println!("Other User ID: {}", fill.other_user_id);
-            println!("Market Order ID: {}", fill.market_order_id);
-            println!("Order ID: {}", order.order_id);
-            println!("Executed Qty: {}", executed_qty);
-            println!("Market: {}", market);
-            println!("User ID: {}", user_id);
-
-
-            //redis manager call type ORDER_UPDATE
-            let response = PushToDb::ORDER_UPDATE(ORDERUPDATEDATA {
-                order_id: fill.market_order_id.clone(),
-                exec_qty: fill.qty as f64,
-                market: None,
-                price: None,
-                quantity: None,
-                side: None,
-            });
-
-
-        })
+            status: Some(status),
+        }];
+        updates.extend(fills.iter().map(|fill| ORDERUPDATEDATA {
+            order_id: fill.market_order_id.clone(),
+            exec_qty: fill.maker_filled,
+            market: Some(market.clone()),
+            price: Some(fill.price),
+            quantity: Some(fill.maker_quantity),
+            side: Some(maker_side),
+            status: Some(if fill.maker_filled >= fill.maker_quantity { OrderStatus::Filled } else { OrderStatus::Open }),
+        }));
+
+        if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+            for update in updates {
+                if let Ok(json) = protocol::encode(&PushToDb::ORDER_UPDATE(update)) {
+                    let _ = redis_manager.push_db(&json);
+                }
+            }
+        }
     }
 
-    pub fn publish_ws_trades(&mut self, fills: Vec<Fill>, user_id: String, market: String) {
+    pub fn publish_ws_trades(&mut self, fills: Vec<Fill>, side: Side, market: String) {
         fills.iter().for_each(|fill| {
-            let channel = format!("trade@{}", market);
+            let channel = channels::trade(&market);
             let trade_data = serde_json::json!({
-                "stream": format!("trade@{}", market),
+                "stream": channel,
                 "data": {
                     "e": "trade",
                     "t": fill.trade_id,
-                    "m": fill.other_user_id == user_id,
+                    "m": side == Side::Sell,
                     "p": fill.price,
                     "q": fill.qty.to_string(),
                     "s": market,
@@ -551,7 +1705,7 @@ impl Engine {
         })
     }
 
-    pub fn publish_ws_depth_update(&mut self, fills: Vec<Fill>, price: f64, side: Side, market: String) {
+    pub fn publish_ws_depth_update(&mut self, fills: Vec<Fill>, price: Decimal, side: Side, market: String) {
         println!("Price: {}", price);
         // println!("Side: {}", side);
         println!("Market: {}", market);
@@ -572,9 +1726,9 @@ impl Engine {
             let updated_bids = depth.bids.iter().find(|bid| bid.price == price.to_string());
 
             // redis manager call publishMessage
-            let channel = format!("depth@{}", market);
+            let channel = channels::depth(&market);
             let depth_data = serde_json::json!({
-                "stream": format!("depth@{}", market),
+                "stream": channel,
                 "data": {
                     "a": updated_asks,
                     "b": updated_bids.map_or_else(|| serde_json::Value::Array(vec![]), |bid| serde_json::json!([bid])),
@@ -595,9 +1749,9 @@ impl Engine {
             let updated_asks = depth.asks.iter().find(|ask| ask.price == price.to_string());
 
             // redis manager call publishMessage
-            let channel = format!("depth@{}", market);
+            let channel = channels::depth(&market);
             let depth_data = serde_json::json!({
-                "stream": format!("depth@{}", market),
+                "stream": channel,
                 "data": {
                     "a": updated_asks.map_or_else(|| serde_json::Value::Array(vec![]), |ask| serde_json::json!([ask])),
                     "b": updated_bids,
@@ -616,10 +1770,11 @@ impl Engine {
     pub fn send_updated_depth(&mut self, price: String, market: String) {
         println!("Price: {}", price);
         println!("Market: {}", market);
-        let orderbook = self.orderbooks
+        let Some(orderbook) = self.orderbooks
             .iter()
-            .find(|o| o.ticker() == market)
-            .expect("Orderbook not found");
+            .find(|o| o.ticker() == market) else {
+            return;
+        };
         let depth = orderbook.getDepth();
         let updated_asks: Vec<&PriceLevel> = depth.asks.iter()
             .filter(|ask| ask.price == price)
@@ -629,9 +1784,9 @@ impl Engine {
             .collect();
             
         // redis manager call publishMessage
-        let channel = format!("depth@{}", market);
+        let channel = channels::depth(&market);
         let depth_data = serde_json::json!({
-            "stream": format!("depth@{}", market),
+            "stream": channel,
             "data": {
                 "a": if updated_asks.len() > 0 {
                     serde_json::to_value(
@@ -659,34 +1814,208 @@ impl Engine {
 
     }
 
-    pub fn on_ramp(&mut self, user_id: String, amount: f64) {
-        println!("User ID: {}", user_id);
-        println!("Amount: {}", amount);
+    /// Credit a deposit of any asset traded on a listed market. A txn_id seen
+    /// before is not credited again, the original deposit is returned instead.
+    pub fn on_ramp(&mut self, ramp_data: &ONRAMPDATA) -> Result<MessageToApi, EngineError> {
+        if ramp_data.txn_id.is_empty() || ramp_data.txn_id.len() > 128 {
+            return Err(EngineError::InvalidDeposit("txn_id must be 1 to 128 characters".to_string()));
+        }
+        if let Some(existing) = self.deposits.get(&ramp_data.txn_id) {
+            if existing.user_id != ramp_data.user_id || existing.asset != ramp_data.asset || existing.amount != ramp_data.amount {
+                return Err(EngineError::InvalidDeposit(format!("txn_id {} was already credited with a different deposit", ramp_data.txn_id)));
+            }
+            return Ok(MessageToApi::DEPOSIT_DUPLICATE(existing.clone()));
+        }
+        if ramp_data.amount <= Decimal::ZERO {
+            return Err(EngineError::InvalidDeposit("Deposit amount must be greater than 0".to_string()));
+        }
+        if !self.orderbooks.iter().any(|o| o.base_asset == ramp_data.asset || o.quote_asset == ramp_data.asset) {
+            return Err(EngineError::InvalidDeposit(format!("{} is not traded on any market", ramp_data.asset)));
+        }
 
-        let base_currency = "USD".to_string(); 
-        match self.balances.get_mut(&user_id) {
-            None => {
-                let mut currency_map = std::collections::HashMap::new();
-                currency_map.insert(
-                    base_currency.clone(),
-                    UserBalance {
-                        available: amount,
-                        locked: 0.0,
-                    },
-                );
-                self.balances.insert(user_id.clone(), currency_map);
+        self.post(Posting::new(LedgerReason::Deposit, ramp_data.txn_id.as_str())
+            .transfer(&ramp_data.user_id, &ramp_data.asset, LedgerBucket::External, LedgerBucket::Available, ramp_data.amount));
+        let deposit = DepositPayload {
+            txn_id: ramp_data.txn_id.clone(),
+            user_id: ramp_data.user_id.clone(),
+            asset: ramp_data.asset.clone(),
+            amount: ramp_data.amount,
+            timestamp: self.time,
+        };
+        self.deposits.insert(deposit.txn_id.clone(), deposit.clone());
+
+        if let Ok(json) = protocol::encode(&PushToDb::DEPOSIT(deposit.clone())) {
+            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                let _ = redis_manager.push_db(&json);
+            }
+        }
+        Ok(MessageToApi::DEPOSITED(deposit))
+    }
+
+    /// A user's balances, and with a quote asset their value in it at the last
+    /// trade prices. A user that never held anything just has no balances.
+    pub fn get_balances(&self, balances_data: &GetBalancesData) -> BalancesPayload {
+        let empty = |asset: &str| AssetBalance {
+            asset: asset.to_string(),
+            available: Decimal::ZERO,
+            locked: Decimal::ZERO,
+            withdrawing: Decimal::ZERO,
+        };
+        let mut assets: BTreeMap<String, AssetBalance> = BTreeMap::new();
+        for (asset, balance) in self.balances.get(&balances_data.user_id).into_iter().flatten() {
+            let held = assets.entry(asset.clone()).or_insert_with(|| empty(asset));
+            held.available = balance.available;
+            held.locked = balance.locked;
+        }
+        for withdrawal in self.withdrawals.values() {
+            if withdrawal.user_id == balances_data.user_id && withdrawal.status == WithdrawalStatus::Pending {
+                assets.entry(withdrawal.asset.clone()).or_insert_with(|| empty(&withdrawal.asset)).withdrawing += withdrawal.amount;
+            }
+        }
+        let balances: Vec<AssetBalance> = assets
+            .into_values()
+            .filter(|balance| !(balance.available + balance.locked + balance.withdrawing).is_zero())
+            .collect();
+
+        let portfolio = balances_data.quote_asset.as_ref().map(|quote_asset| {
+            let assets: Vec<AssetValuation> = balances.iter().map(|balance| {
+                let total = balance.available + balance.locked + balance.withdrawing;
+                let price = self.last_price(&balance.asset, quote_asset);
+                AssetValuation {
+                    asset: balance.asset.clone(),
+                    total,
+                    price,
+                    value: price.map(|price| (total * price).normalize()),
+                }
+            }).collect();
+            PortfolioPayload {
+                quote_asset: quote_asset.clone(),
+                total_value: assets.iter().filter_map(|asset| asset.value).sum(),
+                assets,
             }
-            Some(user_balance) => {
-                user_balance
-                    .entry(base_currency.clone())
-                    .and_modify(|bal| bal.available += amount)
-                    .or_insert(UserBalance {
-                        available: amount,
-                        locked: 0.0,
-                    });
+        });
+
+        BalancesPayload { balances, portfolio }
+    }
+
+    /// Last trade price of `asset` in `quote`, through the inverse market if only that one trades.
+    fn last_price(&self, asset: &str, quote: &str) -> Option<Decimal> {
+        if asset == quote {
+            return Some(Decimal::ONE);
+        }
+        let traded = |base: &str, quote: &str| {
+            self.orderbooks
+                .iter()
+                .find(|o| o.base_asset == base && o.quote_asset == quote && o.current_price > Decimal::ZERO)
+                .map(|orderbook| orderbook.current_price)
+        };
+        traded(asset, quote).or_else(|| traded(quote, asset).map(|price| (Decimal::ONE / price).normalize()))
+    }
+
+    /// Move `amount` out of available into a hold until the withdrawal is settled.
+    /// A withdrawal id seen before is not held again, the original is returned.
+    pub fn withdraw(&mut self, withdraw_data: &WithdrawData) -> Result<WithdrawalPayload, EngineError> {
+        if withdraw_data.withdrawal_id.is_empty() || withdraw_data.withdrawal_id.len() > 128 {
+            return Err(EngineError::InvalidWithdrawal("withdrawal_id must be 1 to 128 characters".to_string()));
+        }
+        if let Some(existing) = self.withdrawals.get(&withdraw_data.withdrawal_id) {
+            if existing.user_id != withdraw_data.user_id || existing.asset != withdraw_data.asset || existing.amount != withdraw_data.amount {
+                return Err(EngineError::InvalidWithdrawal(format!("withdrawal_id {} was already used for a different withdrawal", withdraw_data.withdrawal_id)));
             }
+            return Ok(existing.clone());
+        }
+        if withdraw_data.amount <= Decimal::ZERO {
+            return Err(EngineError::InvalidWithdrawal("Withdrawal amount must be greater than 0".to_string()));
+        }
+        if withdraw_data.address.is_empty() {
+            return Err(EngineError::InvalidWithdrawal("Withdrawals need an address".to_string()));
+        }
+
+        let balance = self.balances
+            .get(&withdraw_data.user_id)
+            .ok_or_else(|| EngineError::UnknownUser(withdraw_data.user_id.clone()))?
+            .get(&withdraw_data.asset)
+            .ok_or_else(|| EngineError::UnknownAsset { user_id: withdraw_data.user_id.clone(), asset: withdraw_data.asset.clone() })?;
+        if balance.available < withdraw_data.amount {
+            return Err(EngineError::InsufficientBalance {
+                asset: withdraw_data.asset.clone(),
+                required: withdraw_data.amount,
+                available: balance.available,
+            });
         }
+        self.post(Posting::new(LedgerReason::WithdrawalHold, withdraw_data.withdrawal_id.as_str())
+            .transfer(&withdraw_data.user_id, &withdraw_data.asset, LedgerBucket::Available, LedgerBucket::Withdrawing, withdraw_data.amount));
+
+        let withdrawal = WithdrawalPayload {
+            withdrawal_id: withdraw_data.withdrawal_id.clone(),
+            user_id: withdraw_data.user_id.clone(),
+            asset: withdraw_data.asset.clone(),
+            amount: withdraw_data.amount,
+            address: withdraw_data.address.clone(),
+            status: WithdrawalStatus::Pending,
+            tx_hash: None,
+            timestamp: self.time,
+        };
+        self.withdrawals.insert(withdrawal.withdrawal_id.clone(), withdrawal.clone());
+        self.publish_withdrawal(&withdrawal);
+        Ok(withdrawal)
+    }
+
+    /// Burn the hold of a sent withdrawal, or hand it back to available when the
+    /// withdrawal failed or was cancelled. Settling again the same way is a no-op.
+    pub fn settle_withdrawal(&mut self, settle_data: &SettleWithdrawalData) -> Result<WithdrawalPayload, EngineError> {
+        if !settle_data.status.is_settled() {
+            return Err(EngineError::InvalidWithdrawal("Withdrawals settle as sent, failed or cancelled".to_string()));
+        }
+        let withdrawal = self.withdrawals
+            .get(&settle_data.withdrawal_id)
+            .ok_or_else(|| EngineError::WithdrawalNotFound(settle_data.withdrawal_id.clone()))?;
+        if withdrawal.status == settle_data.status {
+            return Ok(withdrawal.clone());
+        }
+        if withdrawal.status.is_settled() {
+            return Err(EngineError::InvalidWithdrawal(format!(
+                "Withdrawal {} is already {}", settle_data.withdrawal_id, withdrawal.status.as_str()
+            )));
+        }
+
+        let mut withdrawal = withdrawal.clone();
+        let (reason, to) = match settle_data.status {
+            WithdrawalStatus::Sent => (LedgerReason::WithdrawalSent, LedgerBucket::External),
+            _ => (LedgerReason::WithdrawalReleased, LedgerBucket::Available),
+        };
+        self.post(Posting::new(reason, withdrawal.withdrawal_id.as_str())
+            .transfer(&withdrawal.user_id, &withdrawal.asset, LedgerBucket::Withdrawing, to, withdrawal.amount));
+        withdrawal.status = settle_data.status;
+        withdrawal.tx_hash = settle_data.tx_hash.clone();
+        withdrawal.timestamp = self.time;
+        self.withdrawals.insert(withdrawal.withdrawal_id.clone(), withdrawal.clone());
+        self.publish_withdrawal(&withdrawal);
+        Ok(withdrawal)
     }
 
+    fn publish_withdrawal(&self, withdrawal: &WithdrawalPayload) {
+        if let Ok(json) = protocol::encode(&PushToDb::WITHDRAWAL(withdrawal.clone())) {
+            if let Some(redis_manager) = RedisManager::get_instance().try_lock() {
+                let _ = redis_manager.push_db(&json);
+            }
+        }
+    }
+}
 
-}
\ No newline at end of file
+/// What the taker of `fills` is told about each of them.
+fn fill_responses(market: &str, taker_side: Side, fills: &[Fill]) -> Vec<FillResponse> {
+    let fee_asset = market
+        .split_once('-')
+        .map(|(base, quote)| fees::fee_assets(taker_side, base, quote).0.to_string())
+        .unwrap_or_default();
+    fills.iter().map(|fill| FillResponse {
+        price: fill.price,
+        qty: fill.qty,
+        trade_id: fill.trade_id,
+        other_user_id: fill.other_user_id.clone(),
+        market_order_id: fill.market_order_id.clone(),
+        fee: fill.taker_fee,
+        fee_asset: fee_asset.clone(),
+    }).collect()
+}
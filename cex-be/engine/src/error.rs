@@ -0,0 +1,90 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
+
+use crate::types::{ErrorPayload, MarketStatus};
+
+/// Why the engine refused a request. Sent back to the caller as
+/// `MessageToApi::ERROR` instead of taking the whole engine down.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    UnknownMarket(String),
+    InsufficientBalance { asset: String, required: Decimal, available: Decimal },
+    UnknownUser(String),
+    UnknownAsset { user_id: String, asset: String },
+    // the request does not make sense for the order's side
+    InvalidSide(String),
+    OrderNotFound(String),
+    // the client_order_id was already used for an order that is no longer open
+    DuplicateClientOrderId { client_order_id: String, order_id: String },
+    NotOwner { order_id: String },
+    // any other malformed order: missing fields, bad precision, conflicting options
+    InvalidOrder(String),
+    // a market config the registry shouldn't have handed over
+    InvalidMarket(String),
+    // a deposit that can't be credited: bad amount or asset, or a txn_id reused for different funds
+    InvalidDeposit(String),
+    WithdrawalNotFound(String),
+    // a withdrawal that can't be held or settled as asked
+    InvalidWithdrawal(String),
+    // the market's status doesn't allow the request right now
+    MarketUnavailable { market: String, status: MarketStatus },
+}
+
+impl EngineError {
+    /// Stable code the API maps to an HTTP status.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EngineError::UnknownMarket(_) => "UNKNOWN_MARKET",
+            EngineError::InsufficientBalance { .. } => "INSUFFICIENT_BALANCE",
+            EngineError::UnknownUser(_) => "UNKNOWN_USER",
+            EngineError::UnknownAsset { .. } => "UNKNOWN_ASSET",
+            EngineError::InvalidSide(_) => "INVALID_SIDE",
+            EngineError::OrderNotFound(_) => "ORDER_NOT_FOUND",
+            EngineError::DuplicateClientOrderId { .. } => "DUPLICATE_CLIENT_ORDER_ID",
+            EngineError::NotOwner { .. } => "NOT_OWNER",
+            EngineError::InvalidOrder(_) => "INVALID_ORDER",
+            EngineError::InvalidMarket(_) => "INVALID_MARKET",
+            EngineError::InvalidDeposit(_) => "INVALID_DEPOSIT",
+            EngineError::WithdrawalNotFound(_) => "WITHDRAWAL_NOT_FOUND",
+            EngineError::InvalidWithdrawal(_) => "INVALID_WITHDRAWAL",
+            EngineError::MarketUnavailable { .. } => "MARKET_UNAVAILABLE",
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::UnknownMarket(market) => write!(f, "Unknown market {}", market),
+            EngineError::InsufficientBalance { asset, required, available } => {
+                write!(f, "Insufficient {} balance: required {}, available {}", asset, required, available)
+            }
+            EngineError::UnknownUser(user_id) => write!(f, "Unknown user {}", user_id),
+            EngineError::UnknownAsset { user_id, asset } => write!(f, "User {} holds no {}", user_id, asset),
+            EngineError::InvalidSide(reason) => write!(f, "{}", reason),
+            EngineError::OrderNotFound(order_id) => write!(f, "Order {} not found", order_id),
+            EngineError::DuplicateClientOrderId { client_order_id, order_id } => {
+                write!(f, "client_order_id {} was already used for order {}", client_order_id, order_id)
+            }
+            EngineError::NotOwner { order_id } => write!(f, "Order {} belongs to another user", order_id),
+            EngineError::InvalidOrder(reason) => write!(f, "{}", reason),
+            EngineError::InvalidMarket(reason) => write!(f, "{}", reason),
+            EngineError::InvalidDeposit(reason) => write!(f, "{}", reason),
+            EngineError::WithdrawalNotFound(withdrawal_id) => write!(f, "Withdrawal {} not found", withdrawal_id),
+            EngineError::InvalidWithdrawal(reason) => write!(f, "{}", reason),
+            EngineError::MarketUnavailable { market, status } => write!(f, "Market {} is {}", market, status.as_str()),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<&EngineError> for ErrorPayload {
+    fn from(error: &EngineError) -> Self {
+        ErrorPayload {
+            code: error.code().to_string(),
+            message: error.to_string(),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::{orderbook::Fill, types::Side};
+
+/// Balances key the exchange's fee income is credited to, like any other user.
+/// Maker rebates are paid out of it, so an asset only ever rebated can go negative.
+pub const FEE_ACCOUNT: &str = "exchange_fees";
+
+/// Maker and taker rates of one market, as a fraction of what each side
+/// receives. A negative maker rate is a rebate.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeSchedule {
+    pub maker_rate: Decimal,
+    pub taker_rate: Decimal,
+}
+
+impl FeeSchedule {
+    pub fn new(maker_rate: Decimal, taker_rate: Decimal) -> Self {
+        Self { maker_rate, taker_rate }
+    }
+
+    /// Rates after an account tier's discount, which only lowers fees.
+    pub fn discounted(&self, discount: Decimal) -> Self {
+        let apply = |rate: Decimal| if rate > Decimal::ZERO { rate * (Decimal::ONE - discount) } else { rate };
+        Self::new(apply(self.maker_rate), apply(self.taker_rate))
+    }
+}
+
+/// Set `taker_fee` and `maker_fee` on a fill of an order on `taker_side`.
+/// Buyers pay in base, sellers in quote, at the scale that asset settles in.
+pub fn charge(fill: &mut Fill, taker_side: Side, taker: &FeeSchedule, maker: &FeeSchedule, price_scale: u32, quantity_scale: u32) {
+    let quote_qty = fill.price * fill.qty;
+    let quote_scale = price_scale + quantity_scale;
+    let (taker_received, taker_scale, maker_received, maker_scale) = match taker_side {
+        Side::Buy => (fill.qty, quantity_scale, quote_qty, quote_scale),
+        Side::Sell => (quote_qty, quote_scale, fill.qty, quantity_scale),
+    };
+    fill.taker_fee = fee(taker.taker_rate, taker_received, taker_scale);
+    fill.maker_fee = fee(maker.maker_rate, maker_received, maker_scale);
+}
+
+/// Assets the taker and maker of a fill pay their fees in, in that order.
+pub fn fee_assets<'a>(taker_side: Side, base: &'a str, quote: &'a str) -> (&'a str, &'a str) {
+    match taker_side {
+        Side::Buy => (base, quote),
+        Side::Sell => (quote, base),
+    }
+}
+
+// rounded up, so fees never fall short of the rate and rebates never exceed it
+fn fee(rate: Decimal, received: Decimal, scale: u32) -> Decimal {
+    (received * rate).round_dp_with_strategy(scale, RoundingStrategy::ToPositiveInfinity)
+}
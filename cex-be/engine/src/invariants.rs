@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rust_decimal::Decimal;
+
+use crate::{engine::Engine, fees::FEE_ACCOUNT, types::{Side, WithdrawalStatus}};
+
+/// Every way the engine's state is inconsistent, empty when it is sound. Funds
+/// are conserved per asset, locks back exactly the open orders, no order is
+/// overfilled and no book is crossed.
+pub fn check(engine: &Engine) -> Vec<String> {
+    let mut violations = Vec::new();
+    check_conservation(engine, &mut violations);
+    check_orders(engine, &mut violations);
+    violations
+}
+
+// accounts hold what came in through deposits, less what left through
+// withdrawals (sent or still held) and what was paid to the fee account
+fn check_conservation(engine: &Engine, violations: &mut Vec<String>) {
+    let mut deposited: BTreeMap<&str, Decimal> = BTreeMap::new();
+    for deposit in engine.deposits.values() {
+        *deposited.entry(&deposit.asset).or_default() += deposit.amount;
+    }
+    let mut withdrawn: BTreeMap<&str, Decimal> = BTreeMap::new();
+    for withdrawal in engine.withdrawals.values() {
+        // failed and cancelled withdrawals were handed back
+        if matches!(withdrawal.status, WithdrawalStatus::Pending | WithdrawalStatus::Approved | WithdrawalStatus::Sent) {
+            *withdrawn.entry(&withdrawal.asset).or_default() += withdrawal.amount;
+        }
+    }
+    let mut accounts: BTreeMap<&str, Decimal> = BTreeMap::new();
+    let mut fees: BTreeMap<&str, Decimal> = BTreeMap::new();
+    for (user_id, assets) in &engine.balances {
+        let totals = if user_id == FEE_ACCOUNT { &mut fees } else { &mut accounts };
+        for (asset, balance) in assets {
+            *totals.entry(asset).or_default() += balance.available + balance.locked;
+        }
+    }
+
+    let assets: BTreeSet<&str> = deposited.keys().chain(withdrawn.keys()).chain(accounts.keys()).chain(fees.keys()).copied().collect();
+    for asset in assets {
+        let amount = |totals: &BTreeMap<&str, Decimal>| totals.get(asset).copied().unwrap_or_default();
+        let expected = amount(&deposited) - amount(&withdrawn) - amount(&fees);
+        if amount(&accounts) != expected {
+            violations.push(format!(
+                "{}: accounts hold {}, deposits {} less withdrawals {} and fees {} leave {}",
+                asset, amount(&accounts), amount(&deposited), amount(&withdrawn), amount(&fees), expected
+            ));
+        }
+    }
+}
+
+fn check_orders(engine: &Engine, violations: &mut Vec<String>) {
+    // (user id, asset) -> what its open orders need locked
+    let mut backing: BTreeMap<(String, String), Decimal> = BTreeMap::new();
+
+    for orderbook in &engine.orderbooks {
+        let market = orderbook.ticker();
+        if let (Some(bid), Some(ask)) = (orderbook.best_bid(), orderbook.best_ask()) {
+            if bid >= ask {
+                violations.push(format!("{} book is crossed: best bid {} >= best ask {}", market, bid, ask));
+            }
+        }
+
+        for side in [Side::Buy, Side::Sell] {
+            for order in orderbook.resting_orders(side) {
+                if order.filled > order.quantity {
+                    violations.push(format!("{} order {} filled {} of {}", market, order.order_id, order.filled, order.quantity));
+                }
+                let unfilled = order.quantity - order.filled;
+                let (asset, amount) = match side {
+                    Side::Buy => (&orderbook.quote_asset, unfilled * order.price),
+                    Side::Sell => (&orderbook.base_asset, unfilled),
+                };
+                *backing.entry((order.user_id.clone(), asset.clone())).or_default() += amount;
+            }
+        }
+
+        for stop in engine.trigger_books.get(&market).into_iter().flat_map(|trigger_book| trigger_book.orders()) {
+            if stop.order.filled > stop.order.quantity {
+                violations.push(format!("{} stop order {} filled {} of {}", market, stop.order.order_id, stop.order.filled, stop.order.quantity));
+            }
+            let asset = if stop.order.side == Side::Buy { &orderbook.quote_asset } else { &orderbook.base_asset };
+            *backing.entry((stop.order.user_id.clone(), asset.clone())).or_default() += stop.lock_amount;
+        }
+    }
+
+    let mut locked: BTreeMap<(String, String), Decimal> = BTreeMap::new();
+    for (user_id, assets) in &engine.balances {
+        for (asset, balance) in assets {
+            if !balance.locked.is_zero() {
+                locked.insert((user_id.clone(), asset.clone()), balance.locked);
+            }
+        }
+    }
+    let keys: BTreeSet<&(String, String)> = backing.keys().chain(locked.keys()).collect();
+    for key in keys {
+        let amount = |totals: &BTreeMap<(String, String), Decimal>| totals.get(key).copied().unwrap_or_default();
+        if amount(&locked) != amount(&backing) {
+            violations.push(format!(
+                "{} {}: locked {} but open orders back {}",
+                key.0, key.1, amount(&locked), amount(&backing)
+            ));
+        }
+    }
+}
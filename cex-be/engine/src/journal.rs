@@ -0,0 +1,172 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}};
+
+use log::warn;
+
+use crate::{engine::Engine, redis_manager::RedisManager, types::ProcessInput};
+
+/// Append-only log of every input the engine accepted, written before the
+/// input is applied. Snapshot plus journal tail rebuilds the exact state.
+///
+/// One entry per line: `<sequence> <crc32 hex> <ProcessInput json>`, the
+/// checksum covering both the sequence and the json.
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+}
+
+pub struct JournalEntry {
+    pub sequence: u64,
+    pub input: ProcessInput,
+}
+
+impl Journal {
+    /// Open `path` for appending and return the entries already in it. A torn
+    /// or corrupt last line from a crash mid-write is cut off so new entries
+    /// start clean, the engine never applied it.
+    pub fn open(path: &Path) -> io::Result<(Self, Vec<JournalEntry>)> {
+        let (entries, valid_len) = read_entries(path)?;
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() > valid_len {
+            warn!("Dropping torn entry at the end of journal {}", path.display());
+            file.set_len(valid_len)?;
+        }
+        Ok((Self { file, path: path.to_path_buf() }, entries))
+    }
+
+    pub fn append(&mut self, sequence: u64, input: &ProcessInput) -> io::Result<()> {
+        self.file.write_all(encode_line(sequence, input)?.as_bytes())?;
+        // the input must be on disk before the engine acts on it
+        self.file.sync_data()
+    }
+
+    /// Drop the entries up to and including `through` once a snapshot covers
+    /// them, returns how many were dropped. Rewritten through a temporary file
+    /// like the snapshot, a crash mid-way leaves the full journal.
+    pub fn compact(&mut self, through: u64) -> io::Result<usize> {
+        let entries = read(&self.path)?;
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        let mut dropped = 0;
+        for entry in &entries {
+            if entry.sequence <= through {
+                dropped += 1;
+                continue;
+            }
+            file.write_all(encode_line(entry.sequence, &entry.input)?.as_bytes())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        // already positioned at the end of what was kept, so appends go on from there
+        self.file = file;
+        Ok(dropped)
+    }
+}
+
+/// Every complete entry in the journal at `path`, empty if there is none.
+pub fn read(path: &Path) -> io::Result<Vec<JournalEntry>> {
+    read_entries(path).map(|(entries, _)| entries)
+}
+
+/// Apply the entries after the engine's current sequence. Nothing is published
+/// while replaying, those messages already went out the first time round.
+pub fn replay(engine: &mut Engine, entries: Vec<JournalEntry>) -> io::Result<u64> {
+    let was_muted = RedisManager::is_muted();
+    RedisManager::set_muted(true);
+    let mut applied = 0;
+    let start = engine.sequence;
+    let result = entries
+        .into_iter()
+        .filter(|entry| entry.sequence > start)
+        .try_for_each(|entry| {
+            applied += 1;
+            apply(engine, entry)
+        });
+    RedisManager::set_muted(was_muted);
+    result.map(|()| applied)
+}
+
+/// Replay on startup. Inputs are journaled before they are applied, so the
+/// engine may have stopped after journaling the last one and before all of
+/// its messages went out. That entry is applied with publishing on and sends
+/// them again; the db writer skips what it already stored.
+pub fn recover(engine: &mut Engine, mut entries: Vec<JournalEntry>) -> io::Result<u64> {
+    let last = entries.pop_if(|entry| entry.sequence > engine.sequence);
+    let mut applied = replay(engine, entries)?;
+    if let Some(entry) = last {
+        apply(engine, entry)?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+fn apply(engine: &mut Engine, entry: JournalEntry) -> io::Result<()> {
+    if entry.sequence != engine.sequence + 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("journal jumps from sequence {} to {}", engine.sequence, entry.sequence),
+        ));
+    }
+    engine.process(entry.input);
+    Ok(())
+}
+
+fn encode_line(sequence: u64, input: &ProcessInput) -> io::Result<String> {
+    let json = serde_json::to_string(input).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(format!("{} {:08x} {}\n", sequence, checksum(sequence, &json), json))
+}
+
+fn checksum(sequence: u64, json: &str) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&sequence.to_le_bytes());
+    hasher.update(json.as_bytes());
+    hasher.finalize()
+}
+
+// the entries plus the byte length they cover, anything after that is a torn write
+fn read_entries(path: &Path) -> io::Result<(Vec<JournalEntry>, u64)> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e),
+    };
+    let mut entries: Vec<JournalEntry> = Vec::new();
+    let mut valid_len = 0;
+    let lines: Vec<&[u8]> = contents.split_inclusive(|byte| *byte == b'\n').collect();
+    for (index, line) in lines.iter().enumerate() {
+        let Some(line) = line.strip_suffix(b"\n") else {
+            break; // no newline, the write never finished
+        };
+        let line_no = index + 1;
+        match parse_line(line, entries.last()) {
+            Ok(entry) => entries.push(entry),
+            // a crash can leave garbage where the last entry was being written
+            Err(reason) if line_no == lines.len() => {
+                warn!("Journal line {} is unreadable ({}), treating it as torn", line_no, reason);
+                break;
+            }
+            Err(reason) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("journal line {}: {}", line_no, reason))),
+        }
+        valid_len += line.len() as u64 + 1;
+    }
+    Ok((entries, valid_len))
+}
+
+fn parse_line(line: &[u8], previous: Option<&JournalEntry>) -> Result<JournalEntry, String> {
+    let line = std::str::from_utf8(line).map_err(|_| "not utf-8".to_string())?;
+    let mut parts = line.splitn(3, ' ');
+    let (Some(sequence), Some(crc), Some(json)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err("malformed entry".to_string());
+    };
+    let sequence: u64 = sequence.parse().map_err(|_| "bad sequence".to_string())?;
+    let crc = u32::from_str_radix(crc, 16).map_err(|_| "bad checksum".to_string())?;
+    if crc != checksum(sequence, json) {
+        return Err("checksum mismatch".to_string());
+    }
+    if let Some(previous) = previous {
+        if sequence != previous.sequence + 1 {
+            return Err(format!("sequence {} follows {}", sequence, previous.sequence));
+        }
+    }
+    let input = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    Ok(JournalEntry { sequence, input })
+}
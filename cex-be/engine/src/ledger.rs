@@ -0,0 +1,48 @@
+use rust_decimal::Decimal;
+
+use crate::types::{LedgerBucket, LedgerEntry, LedgerReason};
+
+/// One ledger transaction being put together, handed to `Engine::post` which
+/// applies it to the balances and streams it to the db.
+#[derive(Clone, Debug)]
+pub struct Posting {
+    pub reason: LedgerReason,
+    pub reference: String,
+    pub entries: Vec<LedgerEntry>,
+}
+
+impl Posting {
+    pub fn new(reason: LedgerReason, reference: impl Into<String>) -> Self {
+        Self { reason, reference: reference.into(), entries: Vec::new() }
+    }
+
+    /// Add one leg, zero deltas are left out.
+    pub fn entry(mut self, account: &str, bucket: LedgerBucket, asset: &str, delta: Decimal) -> Self {
+        if !delta.is_zero() {
+            self.entries.push(LedgerEntry {
+                account: account.to_string(),
+                bucket,
+                asset: asset.to_string(),
+                delta,
+            });
+        }
+        self
+    }
+
+    /// Move `amount` of `asset` between two buckets of the same account.
+    pub fn transfer(self, account: &str, asset: &str, from: LedgerBucket, to: LedgerBucket, amount: Decimal) -> Self {
+        self.entry(account, from, asset, -amount).entry(account, to, asset, amount)
+    }
+
+    /// The first asset whose legs don't sum to zero, with what they sum to.
+    pub fn imbalance(&self) -> Option<(&str, Decimal)> {
+        let mut sums: Vec<(&str, Decimal)> = Vec::new();
+        for entry in &self.entries {
+            match sums.iter_mut().find(|(asset, _)| *asset == entry.asset) {
+                Some((_, sum)) => *sum += entry.delta,
+                None => sums.push((&entry.asset, entry.delta)),
+            }
+        }
+        sums.into_iter().find(|(_, sum)| !sum.is_zero())
+    }
+}
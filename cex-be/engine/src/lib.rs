@@ -0,0 +1,13 @@
+pub mod types;
+pub mod error;
+pub mod engine;
+pub mod redis_manager;
+pub mod orderbook;
+pub mod fees;
+pub mod ledger;
+pub mod invariants;
+pub mod trigger_book;
+pub mod client_orders;
+pub mod timer_wheel;
+pub mod snapshot;
+pub mod journal;
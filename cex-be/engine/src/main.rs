@@ -1,21 +1,51 @@
+use std::{env, path::PathBuf, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
 use redis::RedisResult;
-use crate::{engine::Engine, redis_manager::RedisManager};
-use serde_json;
+use engine::{engine::Engine, journal::{self, Journal}, redis_manager::RedisManager, snapshot, types::{MessageFromApi, ProcessInput}};
 use log::{info, warn, error};
 
-mod types;
-mod engine;
-mod redis_manager;
-mod orderbook;
-
 fn main() -> RedisResult<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     dotenvy::dotenv().ok();
 
     info!("Starting CEX Matching Engine...");
 
-    let mut engine = Engine::new();
+    let snapshot_path = PathBuf::from(env::var("SNAPSHOT_PATH").unwrap_or_else(|_| "engine_snapshot.json".to_string()));
+    let journal_path = PathBuf::from(env::var("JOURNAL_PATH").unwrap_or_else(|_| "engine_journal.log".to_string()));
+    let snapshot_interval = Duration::from_secs(
+        env::var("SNAPSHOT_INTERVAL_SECS").ok().and_then(|secs| secs.parse().ok()).unwrap_or(30),
+    );
+
+    let mut engine = match snapshot::load(&snapshot_path) {
+        Ok(Some(snapshot)) => Engine::from_snapshot(snapshot),
+        Ok(None) => Engine::new(),
+        Err(e) => {
+            // starting empty here would silently drop every order and balance in the file
+            error!("Failed to load snapshot {}: {}", snapshot_path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut journal = match Journal::open(&journal_path) {
+        Ok((journal, entries)) => {
+            match journal::recover(&mut engine, entries) {
+                Ok(applied) => info!("Replayed {} journal entries, now at sequence {}", applied, engine.sequence),
+                Err(e) => {
+                    error!("Failed to replay journal {}: {}", journal_path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+            journal
+        }
+        Err(e) => {
+            error!("Failed to open journal {}: {}", journal_path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    sync_markets(&mut engine, &mut journal);
     info!("Engine initialized successfully");
+    let mut last_snapshot = Instant::now();
+    let mut snapshot_sequence = engine.sequence;
 
     let redis_manager = RedisManager::new();
     info!("Redis manager initialized successfully");
@@ -27,10 +57,11 @@ fn main() -> RedisResult<()> {
             Ok(Some(msg)) => {
                 info!("Received message from API");
                 
-                match serde_json::from_str::<crate::types::ProcessInput>(&msg) {
-                    Ok(order) => {
-                        info!("Processing order for client: {}", order.client_id);
-                        engine.process(order);
+                match protocol::decode::<ProcessInput>(&msg) {
+                    Ok(mut order) => {
+                        // the engine's clock, journaled with the input
+                        order.timestamp = now_millis();
+                        apply(&mut engine, &mut journal, order);
                         info!("Order processed successfully");
                     }
                     Err(e) => {
@@ -39,13 +70,82 @@ fn main() -> RedisResult<()> {
                     }
                 }
             }
-            Ok(None) => {
-                continue;
-            }
+            Ok(None) => {}
             Err(e) => {
                 error!("Error receiving message from Redis: {}", e);
-                continue;
             }
         }
+
+        // BRPOP wakes up every second, so timers fire within a second of falling due
+        let now = now_millis();
+        if engine.timers_due(now) {
+            apply(&mut engine, &mut journal, ProcessInput {
+                message: MessageFromApi::TICK,
+                client_id: "engine".to_string(),
+                timestamp: now,
+            });
+        }
+
+        // this also runs while the queue is idle
+        if engine.sequence != snapshot_sequence && last_snapshot.elapsed() >= snapshot_interval {
+            match snapshot::save(&snapshot_path, &engine.snapshot()) {
+                Ok(()) => {
+                    info!("Saved snapshot at sequence {}", engine.sequence);
+                    snapshot_sequence = engine.sequence;
+                    // the snapshot covers everything journaled so far
+                    match journal.compact(snapshot_sequence) {
+                        Ok(dropped) => info!("Compacted journal, dropped {} entries", dropped),
+                        Err(e) => error!("Failed to compact journal {}: {}", journal_path.display(), e),
+                    }
+                }
+                Err(e) => error!("Failed to save snapshot {}: {}", snapshot_path.display(), e),
+            }
+            last_snapshot = Instant::now();
+        }
+    }
+}
+
+// the registry is the source of truth for listings, anything it changed while
+// the engine was down goes through the journal like any other ADD_MARKET
+fn sync_markets(engine: &mut Engine, journal: &mut Journal) {
+    let registry = env::var("DATABASE_URL")
+        .map_err(|e| Box::<dyn std::error::Error>::from(e))
+        .and_then(|database_url| db::markets::fetch_markets(&database_url));
+    let markets = match registry {
+        Ok(markets) => markets,
+        Err(e) if engine.orderbooks.is_empty() => {
+            error!("Failed to load the market registry and no markets are listed: {}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            warn!("Failed to load the market registry, keeping the listed markets: {}", e);
+            return;
+        }
+    };
+
+    for config in markets {
+        if engine.market_config(&config.ticker()).as_ref() == Some(&config) {
+            continue;
+        }
+        apply(engine, journal, ProcessInput {
+            message: MessageFromApi::ADD_MARKET(config),
+            client_id: "market_registry".to_string(),
+            timestamp: now_millis(),
+        });
     }
+    info!("Market registry synced, {} markets listed", engine.orderbooks.len());
+}
+
+// an input that can't be journaled is never applied, or a restart would lose it
+fn apply(engine: &mut Engine, journal: &mut Journal, input: ProcessInput) {
+    if let Err(e) = journal.append(engine.sequence + 1, &input) {
+        error!("Failed to journal input, stopping: {}", e);
+        std::process::exit(1);
+    }
+    info!("Processing order for client: {}", input.client_id);
+    engine.process(input);
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis() as u64).unwrap_or(0)
 }
@@ -1,22 +1,50 @@
-use crate::{engine::Order, types::Side};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::{engine::Order, error::EngineError, fees::FeeSchedule, types::{MarketConfig, MarketStatus, OrderType, SelfTradePrevention, Side, TimeInForce}};
 
 pub struct OrderBook {
     pub base_asset: String,
     pub quote_asset: String,
-    pub bids: Vec<Order>,
-    pub asks: Vec<Order>,
+    pub bids: BTreeMap<Decimal, Level>,
+    pub asks: BTreeMap<Decimal, Level>,
+    // order id -> resting order, the levels only hold ids
+    orders: HashMap<String, Order>,
+    // (user id, client order id) -> order id, for resting orders placed with one
+    client_ids: HashMap<(String, String), String>,
     pub last_trade_id: u64,
-    pub current_price: f64,
+    pub current_price: Decimal,
+    pub price_scale: u32,
+    pub quantity_scale: u32,
+    // prices and quantities are whole multiples of these
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+    pub min_notional: Decimal,
+    pub fees: FeeSchedule,
+    pub status: MarketStatus,
 }
 
 pub struct OrderBookSnapshot<'a> {
     pub base_asset: &'a String,
     pub quote_asset: &'a String,
-    pub bids: &'a Vec<Order>,
-    pub asks: &'a Vec<Order>,
+    pub bids: &'a BTreeMap<Decimal, Level>,
+    pub asks: &'a BTreeMap<Decimal, Level>,
     pub last_trade_id: u64,
-    pub current_price: f64,
+    pub current_price: Decimal,
+}
+
+/// All resting orders at one price, oldest first.
+///
+/// Cancels only drop the order from the book's id index and adjust `total`,
+/// the stale id is skipped the next time the queue is walked.
+#[derive(Clone, Debug, Default)]
+pub struct Level {
+    pub total: Decimal,
+    pub count: usize,
+    queue: VecDeque<String>,
 }
+
 #[derive(Clone, Debug, serde::Serialize)]
 pub struct PriceLevel {
     pub price: String,
@@ -31,11 +59,33 @@ pub struct Depth {
 
 #[derive(Clone, Debug)]
 pub struct Fill {
-    pub price: String,
-    pub qty: u64,
+    pub price: Decimal,
+    pub qty: Decimal,
     pub trade_id: u64,
     pub other_user_id: String,
-    pub market_order_id: String
+    pub market_order_id: String,
+    // the resting order's size and how much of it is filled after this match
+    pub maker_quantity: Decimal,
+    pub maker_filled: Decimal,
+    // set by `fees::charge` once the match is done
+    pub taker_fee: Decimal,
+    pub maker_fee: Decimal,
+}
+
+/// What self-trade prevention took out of one match instead of trading.
+#[derive(Clone, Debug, Default)]
+pub struct SelfTrade {
+    // quantity dropped from the incoming order
+    pub taker_cancelled: Decimal,
+    // the user's own resting orders that were shrunk or removed
+    pub makers: Vec<MakerCancel>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MakerCancel {
+    // the resting order after the cut, gone from the book once `filled` reaches `quantity`
+    pub order: Order,
+    pub cancelled_qty: Decimal,
 }
 
 
@@ -46,22 +96,61 @@ impl OrderBook {
         bids: Vec<Order>,
         asks: Vec<Order>,
         last_trade_id: Option<u64>,
-        current_price: Option<f64>,
+        current_price: Option<Decimal>,
+        price_scale: u32,
+        quantity_scale: u32,
     ) -> Self {
-        Self {
+        let mut orderbook = Self {
             base_asset,
             quote_asset,
-            bids,
-            asks,
-            last_trade_id: last_trade_id.unwrap_or(0), 
-            current_price: current_price.unwrap_or(0.0),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            orders: HashMap::new(),
+            client_ids: HashMap::new(),
+            last_trade_id: last_trade_id.unwrap_or(0),
+            current_price: current_price.unwrap_or(Decimal::ZERO),
+            price_scale,
+            quantity_scale,
+            tick_size: Decimal::new(1, price_scale),
+            lot_size: Decimal::new(1, quantity_scale),
+            min_notional: Decimal::ZERO,
+            fees: FeeSchedule::default(),
+            status: MarketStatus::Open,
+        };
+        for order in bids.into_iter().chain(asks) {
+            orderbook.rest(order);
         }
+        orderbook
     }
 
     pub fn ticker(&self) -> String {
         format!("{}-{}", self.base_asset, self.quote_asset)
     }
 
+    /// Take the registry's tick, lot, minimum and fees. Resting orders are left
+    /// alone, the new sizes only apply to what comes in next.
+    pub fn configure(&mut self, config: &MarketConfig) {
+        self.tick_size = config.tick_size.normalize();
+        self.lot_size = config.lot_size.normalize();
+        self.price_scale = config.price_scale();
+        self.quantity_scale = config.quantity_scale();
+        self.min_notional = config.min_notional;
+        self.fees = FeeSchedule::new(config.maker_fee_rate, config.taker_fee_rate);
+    }
+
+    pub fn config(&self) -> MarketConfig {
+        MarketConfig {
+            base_asset: self.base_asset.clone(),
+            quote_asset: self.quote_asset.clone(),
+            tick_size: self.tick_size,
+            lot_size: self.lot_size,
+            min_notional: self.min_notional,
+            maker_fee_rate: self.fees.maker_rate,
+            taker_fee_rate: self.fees.taker_rate,
+            status: self.status,
+        }
+    }
+
     pub fn get_snapshot(&self) -> OrderBookSnapshot<'_> {
         OrderBookSnapshot {
             base_asset: &self.base_asset,
@@ -73,168 +162,390 @@ impl OrderBook {
         }
     }
 
-    pub fn addOrder(&mut self, mut order: Order) -> (f64, Vec<Fill>) {
-        if order.side == crate::types::Side::Buy {
-            let (executed_qty, fills) = self.matchBid(order.clone());
-            order.filled = executed_qty;
-            if executed_qty < order.quantity {
-                self.bids.push(order);
-            }
-            return (executed_qty, fills);
+    /// Orders must come in whole ticks and lots of the market, otherwise the
+    /// locked and filled amounts stop lining up with the book.
+    pub fn check_scale(&self, price: Decimal, quantity: Decimal) -> Result<(), EngineError> {
+        if !(price % self.tick_size).is_zero() {
+            return Err(EngineError::InvalidOrder(format!("Price must be a multiple of the {} tick size", self.tick_size)));
+        }
+        if !(quantity % self.lot_size).is_zero() {
+            return Err(EngineError::InvalidOrder(format!("Quantity must be a multiple of the {} lot size", self.lot_size)));
+        }
+        Ok(())
+    }
+
+    pub fn get_order(&self, order_id: &str) -> Option<&Order> {
+        self.orders.get(order_id)
+    }
+
+    /// Resting orders on one side, oldest first within each level, so
+    /// resting them again in this order keeps their priority.
+    pub fn resting_orders(&self, side: Side) -> Vec<Order> {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels
+            .values()
+            .flat_map(|level| level.queue.iter())
+            .filter_map(|id| self.orders.get(id))
+            .filter(|order| order.side == side)
+            .cloned()
+            .collect()
+    }
+
+    pub fn addOrder(&mut self, mut order: Order) -> (Decimal, Vec<Fill>, SelfTrade) {
+        let (executed_qty, fills, self_trade) = if order.side == Side::Buy {
+            self.matchBid(order.clone())
         } else {
-            let (executed_qty, fills) = self.matchAsk(order.clone());
-            order.filled = executed_qty;
-            if executed_qty < order.quantity {
-                self.asks.push(order);
+            self.matchAsk(order.clone())
+        };
+        order.filled += executed_qty;
+        order.quantity -= self_trade.taker_cancelled;
+        if order.filled < order.quantity && Self::can_rest(&order) {
+            self.rest(order);
+        }
+        (executed_qty, fills, self_trade)
+    }
+
+    // IOC, FOK and market orders never sit on the book
+    pub fn can_rest(order: &Order) -> bool {
+        order.order_type == OrderType::Limit
+            && matches!(order.time_in_force, TimeInForce::Gtc | TimeInForce::PostOnly)
+    }
+
+    pub fn best_bid(&self) -> Option<Decimal> {
+        self.bids.last_key_value().map(|(price, _)| *price)
+    }
+
+    pub fn best_ask(&self) -> Option<Decimal> {
+        self.asks.first_key_value().map(|(price, _)| *price)
+    }
+
+    /// Whether an order at `price` on `side` would trade against the book.
+    pub fn would_cross(&self, side: Side, price: Decimal) -> bool {
+        match side {
+            Side::Buy => self.best_ask().is_some_and(|ask| ask <= price),
+            Side::Sell => self.best_bid().is_some_and(|bid| bid >= price),
+        }
+    }
+
+    /// How much of `quantity` a `side` order could fill right now without going past `price`.
+    /// The user's own orders never fill it, and unless self-trade prevention cancels
+    /// them out of the way, matching stops at the first one.
+    pub fn fillable_qty(&self, side: Side, price: Decimal, quantity: Decimal, user_id: &str, stp: SelfTradePrevention) -> Decimal {
+        let levels: Box<dyn Iterator<Item = (&Decimal, &Level)>> = match side {
+            Side::Buy => Box::new(self.asks.iter().take_while(|(ask, _)| **ask <= price)),
+            Side::Sell => Box::new(self.bids.iter().rev().take_while(|(bid, _)| **bid >= price)),
+        };
+        let mut fillable = Decimal::ZERO;
+        'levels: for (_, level) in levels {
+            for resting in level.queue.iter().filter_map(|id| self.orders.get(id)) {
+                if fillable >= quantity {
+                    break 'levels;
+                }
+                if resting.user_id != user_id {
+                    fillable += resting.quantity - resting.filled;
+                } else if stp != SelfTradePrevention::CancelOldest {
+                    break 'levels;
+                }
             }
-            return (executed_qty, fills);
         }
+        fillable.min(quantity)
     }
 
-    pub fn matchBid(&mut self, order: Order) -> (f64, Vec<Fill>) {
-        let mut fills: Vec<Fill> = Vec::new();
-        let mut executed_qty: f64 = 0.0;
-        
-        // Match against asks (sell orders)
-        let mut i = 0;
-        while i < self.asks.len() && executed_qty < order.quantity {
-            let ask = &mut self.asks[i];
-            if ask.price <= order.price {
-                let fill_qty = (ask.quantity - ask.filled).min(order.quantity - executed_qty);
-                executed_qty += fill_qty;
-                ask.filled += fill_qty;
-                
-                fills.push(Fill {
-                    price: ask.price.to_string(),
-                    qty: fill_qty as u64,
-                    trade_id: self.last_trade_id + 1,
-                    other_user_id: ask.user_id.clone(),
-                    market_order_id: ask.order_id.clone(),
-                });
-                
-                // Remove fully filled asks
-                if ask.filled >= ask.quantity {
-                    self.asks.remove(i);
-                } else {
-                    i += 1;
+    /// Fill a market order against the book, nothing is left resting.
+    /// `order.price` is the worst price the taker accepts and `quote_budget`
+    /// caps how much quote a market buy may spend.
+    pub fn sweep(&mut self, order: Order, quote_budget: Option<Decimal>) -> (Decimal, Vec<Fill>, SelfTrade) {
+        match order.side {
+            Side::Buy => self.match_levels(Side::Sell, order, quote_budget),
+            Side::Sell => self.match_levels(Side::Buy, order, None),
+        }
+    }
+
+    /// Quote needed to buy `quantity` from the asks without going above `worst_price`.
+    /// The user's own asks are walked the way self-trade prevention will treat
+    /// them: skipped under CancelOldest, so the sweep reaches deeper levels.
+    pub fn sweep_cost(&self, quantity: Decimal, worst_price: Decimal, user_id: &str, stp: SelfTradePrevention) -> Decimal {
+        let mut remaining = quantity;
+        let mut cost = Decimal::ZERO;
+        'levels: for (price, level) in self.asks.iter() {
+            if *price > worst_price {
+                break;
+            }
+            for resting in level.queue.iter().filter_map(|id| self.orders.get(id)) {
+                if remaining <= Decimal::ZERO {
+                    break 'levels;
                 }
-            } else {
-                break; // No more matching asks
+                let resting_left = resting.quantity - resting.filled;
+                if resting.user_id == user_id {
+                    match stp {
+                        SelfTradePrevention::CancelOldest => continue,
+                        // the overlap comes off the taker without trading
+                        SelfTradePrevention::DecrementAndCancel => {
+                            remaining -= resting_left.min(remaining);
+                            continue;
+                        }
+                        SelfTradePrevention::CancelNewest | SelfTradePrevention::CancelBoth => break 'levels,
+                    }
+                }
+                let qty = resting_left.min(remaining);
+                cost += qty * *price;
+                remaining -= qty;
             }
         }
-        
-        (executed_qty, fills)
+        cost
+    }
+
+    // append to the back of its price level
+    fn rest(&mut self, order: Order) {
+        let levels = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let level = levels.entry(order.price).or_default();
+        level.total += order.quantity - order.filled;
+        level.count += 1;
+        level.queue.push_back(order.order_id.clone());
+        if let Some(client_order_id) = &order.client_order_id {
+            self.client_ids.insert((order.user_id.clone(), client_order_id.clone()), order.order_id.clone());
+        }
+        self.orders.insert(order.order_id.clone(), order);
+    }
+
+    /// Shrink a resting order to `quantity` in place, it keeps its queue position.
+    pub fn reduce_order(&mut self, order_id: &str, quantity: Decimal) -> Option<&Order> {
+        let order = self.orders.get_mut(order_id)?;
+        let levels = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let level = levels.get_mut(&order.price)?;
+        level.total -= order.quantity - quantity;
+        order.quantity = quantity;
+        Some(order)
+    }
+
+    /// Take a resting order off the book, queue entry included, so it can be
+    /// placed again under the same id without being walked twice.
+    pub fn take_order(&mut self, order_id: &str) -> Option<Order> {
+        let order = self.orders.get(order_id)?.clone();
+        self.cancel(order.side, order_id)?;
+        let levels = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if let Some(level) = levels.get_mut(&order.price) {
+            level.queue.retain(|id| id != order_id);
+        }
+        Some(order)
+    }
+
+    /// Id of the user's resting order placed with `client_order_id`.
+    pub fn client_order(&self, user_id: &str, client_order_id: &str) -> Option<&String> {
+        self.client_ids.get(&(user_id.to_string(), client_order_id.to_string()))
+    }
+
+    pub fn matchBid(&mut self, order: Order) -> (Decimal, Vec<Fill>, SelfTrade) {
+        // Match against asks (sell orders), lowest price first
+        self.match_levels(Side::Sell, order, None)
+    }
+
+    pub fn matchAsk(&mut self, order: Order) -> (Decimal, Vec<Fill>, SelfTrade) {
+        // Match against bids (buy orders), highest price first
+        self.match_levels(Side::Buy, order, None)
     }
 
-    pub fn matchAsk(&mut self, order: Order) -> (f64, Vec<Fill>) {
+    fn match_levels(&mut self, book_side: Side, order: Order, quote_budget: Option<Decimal>) -> (Decimal, Vec<Fill>, SelfTrade) {
         let mut fills: Vec<Fill> = Vec::new();
-        let mut executed_qty: f64 = 0.0;
-        
-        // Match against bids (buy orders)
-        let mut i = 0;
-        while i < self.bids.len() && executed_qty < order.quantity {
-            let bid = &mut self.bids[i];
-            if bid.price >= order.price {
-                let fill_qty = (bid.quantity - bid.filled).min(order.quantity - executed_qty);
+        let mut executed_qty = Decimal::ZERO;
+        let mut self_trade = SelfTrade::default();
+        let mut quote_left = quote_budget;
+        // an amended order comes back with what it already filled
+        let wanted = order.quantity - order.filled;
+        let levels = match book_side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        while executed_qty + self_trade.taker_cancelled < wanted {
+            let best = match book_side {
+                Side::Buy => levels.last_entry(),
+                Side::Sell => levels.first_entry(),
+            };
+            let Some(mut entry) = best else { break };
+            let level_price = *entry.key();
+            let crosses = match book_side {
+                Side::Buy => level_price >= order.price,
+                Side::Sell => level_price <= order.price,
+            };
+            if !crosses {
+                break; // No more matching levels
+            }
+
+            let level = entry.get_mut();
+            while executed_qty + self_trade.taker_cancelled < wanted {
+                let Some(resting_id) = level.queue.front() else { break };
+                let Some(resting) = self.orders.get_mut(resting_id) else {
+                    // cancelled while queued
+                    level.queue.pop_front();
+                    continue;
+                };
+                let taker_left = wanted - executed_qty - self_trade.taker_cancelled;
+
+                if resting.user_id == order.user_id {
+                    let maker_left = resting.quantity - resting.filled;
+                    let (taker_cut, maker_cut) = match order.self_trade_prevention {
+                        SelfTradePrevention::CancelNewest => (taker_left, Decimal::ZERO),
+                        SelfTradePrevention::CancelOldest => (Decimal::ZERO, maker_left),
+                        SelfTradePrevention::CancelBoth => (taker_left, maker_left),
+                        SelfTradePrevention::DecrementAndCancel => {
+                            let overlap = taker_left.min(maker_left);
+                            (overlap, overlap)
+                        }
+                    };
+                    self_trade.taker_cancelled += taker_cut;
+                    if maker_cut > Decimal::ZERO {
+                        resting.quantity -= maker_cut;
+                        level.total -= maker_cut;
+                        self_trade.makers.push(MakerCancel { order: resting.clone(), cancelled_qty: maker_cut });
+                        if resting.filled >= resting.quantity {
+                            let cancelled_id = resting.order_id.clone();
+                            if let Some(cancelled) = self.orders.remove(&cancelled_id) {
+                                unindex(&mut self.client_ids, &cancelled);
+                            }
+                            level.queue.pop_front();
+                            level.count -= 1;
+                        }
+                    }
+                    continue;
+                }
+
+                let mut fill_qty = (resting.quantity - resting.filled).min(taker_left);
+                if let Some(budget) = quote_left {
+                    let affordable = (budget / level_price / self.lot_size)
+                        .round_dp_with_strategy(0, RoundingStrategy::ToZero) * self.lot_size;
+                    fill_qty = fill_qty.min(affordable);
+                    if fill_qty <= Decimal::ZERO {
+                        break;
+                    }
+                    quote_left = Some(budget - fill_qty * level_price);
+                }
                 executed_qty += fill_qty;
-                bid.filled += fill_qty;
-                
+                resting.filled += fill_qty;
+                level.total -= fill_qty;
+                // per-market sequence, restored from the snapshot so ids carry on after a restart
+                self.last_trade_id += 1;
+
                 fills.push(Fill {
-                    price: bid.price.to_string(),
-                    qty: fill_qty as u64,
-                    trade_id: self.last_trade_id + 1,
-                    other_user_id: bid.user_id.clone(),
-                    market_order_id: bid.order_id.clone(),
+                    price: level_price,
+                    qty: fill_qty,
+                    trade_id: self.last_trade_id,
+                    other_user_id: resting.user_id.clone(),
+                    market_order_id: resting.order_id.clone(),
+                    maker_quantity: resting.quantity,
+                    maker_filled: resting.filled,
+                    taker_fee: Decimal::ZERO,
+                    maker_fee: Decimal::ZERO,
                 });
-                
-                // Remove fully filled bids
-                if bid.filled >= bid.quantity {
-                    self.bids.remove(i);
-                } else {
-                    i += 1;
+
+                // Remove fully filled orders
+                if resting.filled >= resting.quantity {
+                    let filled_id = resting.order_id.clone();
+                    if let Some(filled) = self.orders.remove(&filled_id) {
+                        unindex(&mut self.client_ids, &filled);
+                    }
+                    level.queue.pop_front();
+                    level.count -= 1;
                 }
-            } else {
-                break; // No more matching bids
             }
-        }
-        
-        (executed_qty, fills)
-    }
-
 
-    pub fn getDepth(&self) -> Depth {
-        let mut bids: Vec<PriceLevel> = Vec::new();
-        let mut asks: Vec<PriceLevel> = Vec::new();
-
-        let mut bids_obj: std::collections::HashMap<String, f64> = std::collections::HashMap::new();
-        let mut asks_obj: std::collections::HashMap<String, f64> = std::collections::HashMap::new();
-
-        // Aggregate bids by price
-        for bid in self.bids.iter() {
-            let price_key = bid.price.to_string();
-            let remaining_qty = bid.quantity - bid.filled;
-            if remaining_qty > 0.0 {
-                *bids_obj.entry(price_key).or_insert(0.0) += remaining_qty;
+            if level.count == 0 {
+                entry.remove();
+            } else if quote_left.is_some_and(|budget| budget < level_price * self.lot_size) {
+                break; // budget can't buy another lot at this level
             }
         }
 
-        // Aggregate asks by price
-        for ask in self.asks.iter() {
-            let price_key = ask.price.to_string();
-            let remaining_qty = ask.quantity - ask.filled;
-            if remaining_qty > 0.0 {
-                *asks_obj.entry(price_key).or_insert(0.0) += remaining_qty;
-            }
+        if let Some(last_fill) = fills.last() {
+            self.current_price = last_fill.price;
         }
 
-        // Convert to PriceLevel vectors
-        for (price, quantity) in bids_obj {
-            bids.push(PriceLevel {
-                price: price.clone(),
-                quantity: quantity.to_string(),
-            });
-        }
+        (executed_qty, fills, self_trade)
+    }
 
-        for (price, quantity) in asks_obj {
-            asks.push(PriceLevel {
-                price: price.clone(),
-                quantity: quantity.to_string(),
-            });
-        }
 
-        // Sort bids (highest price first) and asks (lowest price first)
-        bids.sort_by(|a, b| b.price.parse::<f64>().unwrap_or(0.0).partial_cmp(&a.price.parse::<f64>().unwrap_or(0.0)).unwrap());
-        asks.sort_by(|a, b| a.price.parse::<f64>().unwrap_or(0.0).partial_cmp(&b.price.parse::<f64>().unwrap_or(0.0)).unwrap());
+    pub fn getDepth(&self) -> Depth {
+        let to_level = |(price, level): (&Decimal, &Level)| PriceLevel {
+            price: price.to_string(),
+            quantity: level.total.to_string(),
+        };
+
+        // bids highest price first, asks lowest price first
+        Depth {
+            bids: self.bids.iter().rev().map(to_level).collect(),
+            asks: self.asks.iter().map(to_level).collect(),
+        }
+    }
 
-        Depth { bids, asks }
+    /// Ids of the user's resting orders, on one side if `side` is given, oldest first.
+    pub fn user_order_ids(&self, user_id: &str, side: Option<Side>) -> Vec<String> {
+        let mut ids: Vec<String> = self.orders
+            .values()
+            .filter(|order| order.user_id == user_id && side.is_none_or(|side| order.side == side))
+            .map(|order| order.order_id.clone())
+            .collect();
+        // ids come from the order id epoch and input sequence, so this is placement order
+        ids.sort();
+        ids
     }
 
     pub fn getOpenOrders(&self, user_id: String) -> String {
-        let mut open_orders: Vec<&Order> = Vec::new();
-        open_orders.extend(self.asks.iter().filter(|ask| ask.user_id == user_id));
-        open_orders.extend(self.bids.iter().filter(|bid| bid.user_id == user_id));
+        let open_orders: Vec<&Order> = self.orders.values().filter(|o| o.user_id == user_id).collect();
 
         // TODO: Implement proper JSON serialization
         format!("Found {} open orders for user {}", open_orders.len(), user_id)
     }
 
-    pub fn cancelBid(&mut self, order_id: &str) -> Option<f64> {
-        if let Some(index) = self.bids.iter().position(|o| o.order_id == order_id) {
-            let order = self.bids.remove(index);
-            Some(order.price)
-        } else {
-            None
-        }
+    pub fn cancelBid(&mut self, order_id: &str) -> Option<Decimal> {
+        self.cancel(Side::Buy, order_id)
     }
 
-    pub fn cancelAsk(&mut self, order_id: &str) -> Option<f64> {
-        if let Some(index) = self.asks.iter().position(|o| o.order_id == order_id) {
-            let order = self.asks.remove(index);
-            Some(order.price)
-        } else {
-            None
+    pub fn cancelAsk(&mut self, order_id: &str) -> Option<Decimal> {
+        self.cancel(Side::Sell, order_id)
+    }
+
+    fn cancel(&mut self, side: Side, order_id: &str) -> Option<Decimal> {
+        if self.orders.get(order_id)?.side != side {
+            return None;
         }
+        let order = self.orders.remove(order_id)?;
+        unindex(&mut self.client_ids, &order);
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if let Some(level) = levels.get_mut(&order.price) {
+            level.total -= order.quantity - order.filled;
+            level.count -= 1;
+            if level.count == 0 {
+                levels.remove(&order.price);
+            } else if level.queue.len() > 2 * level.count {
+                // drop stale ids so a busy level doesn't grow without bound
+                let orders = &self.orders;
+                level.queue.retain(|id| orders.contains_key(id));
+            }
+        }
+        Some(order.price)
     }
 
 
-}
\ No newline at end of file
+}
+
+// drop the client id entry of an order leaving the book
+fn unindex(client_ids: &mut HashMap<(String, String), String>, order: &Order) {
+    if let Some(client_order_id) = &order.client_order_id {
+        client_ids.remove(&(order.user_id.clone(), client_order_id.clone()));
+    }
+}
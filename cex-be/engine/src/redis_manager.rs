@@ -1,41 +1,10 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use futures_util::lock::Mutex;
 use log::info;
 use once_cell::sync::Lazy;
 use redis::{Client, RedisResult};
-use serde::{Deserialize, Serialize};
-
-#[derive(Serialize, Deserialize)]
-pub enum DbMessage {
-    TradeAdded(TradeAdded),
-    OrderUpdate(OrderUpdate)
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct TradeAdded {
-    pub id: String,
-    pub is_buyer_maker: bool,
-    pub price: String,
-    pub quote_quantity: String,
-    pub timestamp: i64,
-    pub market: String
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct OrderUpdate {
-    pub order_id: String,
-    pub exec_qty: f64,
-    market: Option<String>,
-    price: Option<String>,
-    quantity: Option<String>,
-    side: Option<Side>
-}
-
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum Side {
-    Buy,
-    Sell
-}
+use protocol::{channels, queues};
 
 pub struct RedisManager {
     redis_client: Client,
@@ -48,11 +17,14 @@ static INSTANCE: Lazy<Mutex<RedisManager>> = Lazy::new(|| {
     Mutex::new(RedisManager::new())
 });
 
+// set while replaying the journal, the engine's output already went out once
+static MUTED: AtomicBool = AtomicBool::new(false);
+
 impl RedisManager {
     pub fn new() -> Self {
         //redis 1 pubsub
         println!("Initializing redis manager");
-        let redis_client = Client::open("redis://localhost:6379".to_string()).expect("Fail to connect redis client");
+        let redis_client = Client::open(queues::ENGINE_INPUT_URL).expect("Fail to connect redis client");
         //redis 2 pubsub
         let ws_client = Client::open("redis://localhost:6380".to_string()).expect("Fail to connect ws client");
         //redis 3 queue/db
@@ -70,10 +42,19 @@ impl RedisManager {
         &INSTANCE
     }
 
+    /// Drop everything published, pushed or sent back until unmuted.
+    pub fn set_muted(muted: bool) {
+        MUTED.store(muted, Ordering::SeqCst);
+    }
+
+    pub fn is_muted() -> bool {
+        MUTED.load(Ordering::SeqCst)
+    }
+
     pub fn pop_message(&self) -> RedisResult<Option<String>> {
         let mut conn = self.redis_client.get_connection()?;
         let response: Option<(String, String)> = redis::cmd("BRPOP")
-            .arg("messages")
+            .arg(queues::ENGINE_INPUT)
             .arg(1) // block for 1 second
             .query(&mut conn)?;
 
@@ -82,6 +63,9 @@ impl RedisManager {
 
     /// Publish updates for WebSocket consumers
     pub fn publish_ws(&self, channel: &str, payload: &str) -> RedisResult<()> {
+        if Self::is_muted() {
+            return Ok(());
+        }
         let mut conn = self.ws_client.get_connection()?;
         redis::cmd("PUBLISH").arg(channel).arg(payload).execute(&mut conn);
         Ok(())
@@ -89,15 +73,21 @@ impl RedisManager {
 
     /// Push events into DB queue for persistence
     pub fn push_db(&self, payload: &str) -> RedisResult<()> {
+        if Self::is_muted() {
+            return Ok(());
+        }
         let mut conn = self.db_client.get_connection()?;
-        redis::cmd("RPUSH").arg("db_events").arg(payload).execute(&mut conn);
+        redis::cmd("LPUSH").arg(queues::DB_EVENTS).arg(payload).execute(&mut conn);
         Ok(())
     }
 
     /// Send message back to API via Redis
     pub fn send_to_api(&self, client_id: &str, message: &str) -> RedisResult<()> {
+        if Self::is_muted() {
+            return Ok(());
+        }
         let mut conn = self.redis_client.get_connection()?;
-        let channel = format!("api_response:{}", client_id);
+        let channel = channels::api_response(client_id);
         redis::cmd("PUBLISH").arg(channel).arg(message).execute(&mut conn);
         Ok(())
     }
@@ -0,0 +1,194 @@
+use std::{collections::{BTreeSet, HashMap}, fs::{self, File}, io::{self, Write}, path::Path};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{client_orders::ClientOrders, engine::{Order, UserBalance}, trigger_book::StopOrder, types::{DepositPayload, MarketStatus, WithdrawalPayload}};
+
+/// Bump whenever the layout below changes, older files are refused instead of misread.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything the engine needs to pick up where it left off.
+#[derive(Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub version: u32,
+    // sequence number of the last input applied before the snapshot was taken
+    pub sequence: u64,
+    pub markets: Vec<MarketSnapshot>,
+    pub balances: HashMap<String, HashMap<String, UserBalance>>,
+    // user id -> fee tier level, missing from snapshots taken before tiers existed
+    #[serde(default)]
+    pub fee_tiers: HashMap<String, u32>,
+    // engine clock in unix ms, the latest input timestamp applied
+    #[serde(default)]
+    pub time: u64,
+    // user id -> dead man's switch deadline in unix ms
+    #[serde(default)]
+    pub dead_man_switches: HashMap<String, u64>,
+    // txn id -> credited deposit, so a deposit retried after a restart is still credited once
+    #[serde(default)]
+    pub deposits: HashMap<String, DepositPayload>,
+    // withdrawal id -> withdrawal, pending ones hold their amount
+    #[serde(default)]
+    pub withdrawals: HashMap<String, WithdrawalPayload>,
+    // id of the last ledger transaction posted, ids carry on from it after a restore
+    #[serde(default)]
+    pub ledger_sequence: u64,
+    // recently accepted client_order_ids per user, a retry after a restart still finds its order
+    #[serde(default)]
+    pub client_orders: ClientOrders,
+    // high half of order ids, zero in snapshots from before it and then set by the next input
+    #[serde(default)]
+    pub order_id_epoch: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub price_scale: u32,
+    pub quantity_scale: u32,
+    pub last_trade_id: u64,
+    pub current_price: Decimal,
+    // resting orders in time priority within each price level
+    pub bids: Vec<Order>,
+    pub asks: Vec<Order>,
+    pub stops: Vec<StopOrder>,
+    #[serde(default)]
+    pub status: MarketStatus,
+    // registry config as last applied, zero in snapshots from before the registry
+    #[serde(default)]
+    pub tick_size: Decimal,
+    #[serde(default)]
+    pub lot_size: Decimal,
+    #[serde(default)]
+    pub min_notional: Decimal,
+    #[serde(default)]
+    pub maker_fee_rate: Decimal,
+    #[serde(default)]
+    pub taker_fee_rate: Decimal,
+}
+
+/// Read the snapshot at `path`, `None` if there isn't one yet.
+pub fn load(path: &Path) -> io::Result<Option<EngineSnapshot>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let snapshot: EngineSnapshot = serde_json::from_slice(&bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("snapshot version {} is not supported, expected {}", snapshot.version, SNAPSHOT_VERSION),
+        ));
+    }
+    Ok(Some(snapshot))
+}
+
+/// Write through a temporary file and rename it over `path`, so a crash
+/// mid-write leaves the previous snapshot intact.
+pub fn save(path: &Path, snapshot: &EngineSnapshot) -> io::Result<()> {
+    let bytes = serde_json::to_vec(snapshot).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// What differs between two snapshots, one line per difference. Empty when the
+/// books and balances match, which is what a replay regression run checks for.
+pub fn diff(expected: &EngineSnapshot, actual: &EngineSnapshot) -> Vec<String> {
+    let mut differences = Vec::new();
+    if expected.sequence != actual.sequence {
+        differences.push(format!("sequence: expected {}, got {}", expected.sequence, actual.sequence));
+    }
+
+    let ticker = |market: &MarketSnapshot| format!("{}-{}", market.base_asset, market.quote_asset);
+    let expected_markets: HashMap<String, &MarketSnapshot> = expected.markets.iter().map(|m| (ticker(m), m)).collect();
+    let actual_markets: HashMap<String, &MarketSnapshot> = actual.markets.iter().map(|m| (ticker(m), m)).collect();
+    let tickers: BTreeSet<&String> = expected_markets.keys().chain(actual_markets.keys()).collect();
+    for ticker in tickers {
+        let (Some(expected), Some(actual)) = (expected_markets.get(ticker), actual_markets.get(ticker)) else {
+            differences.push(format!("{}: only in one snapshot", ticker));
+            continue;
+        };
+        if expected.last_trade_id != actual.last_trade_id {
+            differences.push(format!("{}: last_trade_id expected {}, got {}", ticker, expected.last_trade_id, actual.last_trade_id));
+        }
+        let config = |market: &MarketSnapshot| (market.tick_size, market.lot_size, market.min_notional, market.maker_fee_rate, market.taker_fee_rate);
+        if config(expected) != config(actual) {
+            differences.push(format!("{}: tick, lot, min notional or fees differ", ticker));
+        }
+        if expected.status != actual.status {
+            differences.push(format!("{}: status expected {}, got {}", ticker, expected.status.as_str(), actual.status.as_str()));
+        }
+        if expected.current_price != actual.current_price {
+            differences.push(format!("{}: current_price expected {}, got {}", ticker, expected.current_price, actual.current_price));
+        }
+        for (side, expected_orders, actual_orders) in [("bids", &expected.bids, &actual.bids), ("asks", &expected.asks, &actual.asks)] {
+            if let Some(index) = (0..expected_orders.len().max(actual_orders.len()))
+                .find(|&i| expected_orders.get(i) != actual_orders.get(i))
+            {
+                differences.push(format!(
+                    "{} {}: first difference at position {}: expected {:?}, got {:?}",
+                    ticker, side, index, expected_orders.get(index), actual_orders.get(index)
+                ));
+            }
+        }
+        let stop_ids = |stops: &[StopOrder]| stops.iter().map(|stop| stop.order.order_id.clone()).collect::<Vec<_>>();
+        if stop_ids(&expected.stops) != stop_ids(&actual.stops) {
+            differences.push(format!("{}: pending stop orders differ", ticker));
+        }
+    }
+
+    if expected.fee_tiers != actual.fee_tiers {
+        differences.push("fee tiers differ".to_string());
+    }
+    if expected.time != actual.time {
+        differences.push(format!("time: expected {}, got {}", expected.time, actual.time));
+    }
+    if expected.dead_man_switches != actual.dead_man_switches {
+        differences.push("dead man's switches differ".to_string());
+    }
+    if expected.deposits != actual.deposits {
+        differences.push("credited deposits differ".to_string());
+    }
+    if expected.withdrawals != actual.withdrawals {
+        differences.push("withdrawals differ".to_string());
+    }
+    if expected.order_id_epoch != actual.order_id_epoch {
+        differences.push(format!("order id epoch: expected {}, got {}", expected.order_id_epoch, actual.order_id_epoch));
+    }
+    if expected.client_orders != actual.client_orders {
+        differences.push("client order ids differ".to_string());
+    }
+    if expected.ledger_sequence != actual.ledger_sequence {
+        differences.push(format!("ledger transactions: expected {}, got {}", expected.ledger_sequence, actual.ledger_sequence));
+    }
+
+    // an asset a user never touched and one sitting at zero are the same thing
+    let users: BTreeSet<&String> = expected.balances.keys().chain(actual.balances.keys()).collect();
+    for user_id in users {
+        let expected_assets = expected.balances.get(user_id);
+        let actual_assets = actual.balances.get(user_id);
+        let assets: BTreeSet<&String> = expected_assets.into_iter().chain(actual_assets).flat_map(|assets| assets.keys()).collect();
+        for asset in assets {
+            let amounts = |assets: Option<&HashMap<String, UserBalance>>| {
+                assets
+                    .and_then(|assets| assets.get(asset))
+                    .map_or((Decimal::ZERO, Decimal::ZERO), |balance| (balance.available, balance.locked))
+            };
+            let (expected_amounts, actual_amounts) = (amounts(expected_assets), amounts(actual_assets));
+            if expected_amounts != actual_amounts {
+                differences.push(format!(
+                    "balance {} {}: expected available {} locked {}, got available {} locked {}",
+                    user_id, asset, expected_amounts.0, expected_amounts.1, actual_amounts.0, actual_amounts.1
+                ));
+            }
+        }
+    }
+    differences
+}
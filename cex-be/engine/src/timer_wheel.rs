@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+// one slot per second, deadlines further out than this share slots across laps
+const SLOTS: u64 = 512;
+
+/// Hashed timer wheel of deadlines in unix ms, keyed by whatever the caller
+/// arms it for. Re-arming a key only moves its deadline, the stale slot entry
+/// is dropped when its slot is next swept, so arm and refresh stay O(1).
+pub struct TimerWheel {
+    slots: Vec<Vec<(String, u64)>>,
+    // key -> current deadline, a slot entry only counts while it matches
+    deadlines: HashMap<String, u64>,
+    // second the last sweep reached, it is swept again as it may not be over
+    cursor: u64,
+}
+
+impl TimerWheel {
+    pub fn new() -> Self {
+        Self {
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+            deadlines: HashMap::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn arm(&mut self, key: &str, deadline: u64) {
+        // a deadline already behind the cursor goes in the cursor's slot, the next sweep covers it
+        let second = (deadline / 1000).max(self.cursor);
+        self.slots[(second % SLOTS) as usize].push((key.to_string(), deadline));
+        self.deadlines.insert(key.to_string(), deadline);
+    }
+
+    /// Forget `key`'s deadline, returning whether one was armed.
+    pub fn disarm(&mut self, key: &str) -> bool {
+        self.deadlines.remove(key).is_some()
+    }
+
+    pub fn deadline(&self, key: &str) -> Option<u64> {
+        self.deadlines.get(key).copied()
+    }
+
+    pub fn deadlines(&self) -> &HashMap<String, u64> {
+        &self.deadlines
+    }
+
+    /// Whether any deadline is at or before `now`, without expiring it.
+    pub fn is_due(&self, now: u64) -> bool {
+        self.swept_slots(now).any(|slot| {
+            self.slots[slot].iter().any(|(key, deadline)| *deadline <= now && self.deadline(key) == Some(*deadline))
+        })
+    }
+
+    /// Remove and return every key whose deadline is at or before `now`, sorted
+    /// so callers act on them in the same order on every run.
+    pub fn expire(&mut self, now: u64) -> Vec<String> {
+        let mut expired = Vec::new();
+        for slot in self.swept_slots(now).collect::<Vec<_>>() {
+            let deadlines = &mut self.deadlines;
+            self.slots[slot].retain(|(key, deadline)| {
+                if deadlines.get(key) != Some(deadline) {
+                    return false; // re-armed or disarmed since
+                }
+                if *deadline > now {
+                    return true;
+                }
+                deadlines.remove(key);
+                expired.push(key.clone());
+                false
+            });
+        }
+        self.cursor = self.cursor.max(now / 1000);
+        expired.sort();
+        expired
+    }
+
+    fn swept_slots(&self, now: u64) -> impl Iterator<Item = usize> {
+        let second = now / 1000;
+        let (start, count) = if second < self.cursor {
+            (self.cursor, 1)
+        } else {
+            (self.cursor, (second - self.cursor + 1).min(SLOTS))
+        };
+        (start..start + count).map(|second| (second % SLOTS) as usize)
+    }
+}
+
+impl Default for TimerWheel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{engine::Order, types::Side};
+
+/// A stop order waiting for the last trade price to reach `trigger_price`.
+/// Its funds are locked when it is placed, so triggering can't fail on balance.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StopOrder {
+    pub order: Order,
+    pub trigger_price: Decimal,
+    pub lock_amount: Decimal,
+    // quote-sized stop-market buys
+    pub quote_quantity: Option<Decimal>,
+}
+
+/// Pending stop orders of one market, keyed by trigger price.
+#[derive(Default)]
+pub struct TriggerBook {
+    // buy stops fire when the price rises to the trigger
+    buys: BTreeMap<Decimal, Vec<StopOrder>>,
+    // sell stops fire when the price falls to the trigger
+    sells: BTreeMap<Decimal, Vec<StopOrder>>,
+}
+
+impl TriggerBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, stop: StopOrder) {
+        let stops = match stop.order.side {
+            Side::Buy => &mut self.buys,
+            Side::Sell => &mut self.sells,
+        };
+        stops.entry(stop.trigger_price).or_default().push(stop);
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&StopOrder> {
+        self.orders().find(|stop| stop.order.order_id == order_id)
+    }
+
+    /// The user's pending stop placed with `client_order_id`.
+    pub fn client_order(&self, user_id: &str, client_order_id: &str) -> Option<&StopOrder> {
+        self.orders().find(|stop| {
+            stop.order.user_id == user_id && stop.order.client_order_id.as_deref() == Some(client_order_id)
+        })
+    }
+
+    /// Every pending stop, buys then sells, in trigger order.
+    pub fn orders(&self) -> impl Iterator<Item = &StopOrder> {
+        self.buys.values().chain(self.sells.values().rev()).flatten()
+    }
+
+    pub fn remove(&mut self, order_id: &str) -> Option<StopOrder> {
+        for stops in [&mut self.buys, &mut self.sells] {
+            let found = stops.iter().find_map(|(trigger, queue)| {
+                queue.iter().position(|stop| stop.order.order_id == order_id).map(|index| (*trigger, index))
+            });
+            if let Some((trigger, index)) = found {
+                let queue = stops.get_mut(&trigger)?;
+                let stop = queue.remove(index);
+                if queue.is_empty() {
+                    stops.remove(&trigger);
+                }
+                return Some(stop);
+            }
+        }
+        None
+    }
+
+    /// Take every stop `last_price` has reached, nearest trigger first and in
+    /// placement order within a trigger.
+    pub fn take_triggered(&mut self, last_price: Decimal) -> Vec<StopOrder> {
+        let mut triggered = Vec::new();
+        while let Some(entry) = self.buys.first_entry() {
+            if *entry.key() > last_price {
+                break;
+            }
+            triggered.extend(entry.remove());
+        }
+        while let Some(entry) = self.sells.last_entry() {
+            if *entry.key() < last_price {
+                break;
+            }
+            triggered.extend(entry.remove());
+        }
+        triggered
+    }
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::{balance, dec, deposit, engine, limit, send};
+use engine::error::EngineError;
+use protocol::messages::{AmendOrderData, MarketConfig, MessageFromApi, Side};
+
+#[test]
+fn shrinking_below_the_minimum_notional_is_refused() {
+    let mut engine = engine();
+    engine.add_market(&MarketConfig { min_notional: dec("10"), ..common::market("BTC", "USD", "0.01", "0.0001") }).unwrap();
+    deposit(&mut engine, "u1", "USD", "1000");
+    send(&mut engine, MessageFromApi::CREATE_ORDER(limit("u1", Side::Buy, "100", "1")));
+    let order_id = engine.orderbooks[0].user_order_ids("u1", None).pop();
+
+    let amend = AmendOrderData {
+        order_id,
+        client_order_id: None,
+        market: "BTC-USD".to_string(),
+        user_id: "u1".to_string(),
+        quantity: Some(dec("0.05")),
+        price: None,
+    };
+    assert!(matches!(engine.amend_order(&amend), Err(EngineError::InvalidOrder(_))));
+    assert_eq!(balance(&engine, "u1", "USD"), (dec("900"), dec("100")));
+
+    engine.amend_order(&AmendOrderData { quantity: Some(dec("0.1")), ..amend }).unwrap();
+    assert_eq!(balance(&engine, "u1", "USD"), (dec("990"), dec("10")));
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{balance, dec, deposit, engine, limit, market_order, send};
+use engine::{engine::{Engine, OrderOutcome}, error::EngineError};
+use protocol::messages::{CreateOrderData, MessageFromApi, Side};
+
+fn with_client_id(order: CreateOrderData, client_order_id: &str) -> CreateOrderData {
+    CreateOrderData { client_order_id: Some(client_order_id.to_string()), ..order }
+}
+
+#[test]
+fn retrying_an_open_order_returns_it() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "1000");
+    let bid = with_client_id(limit("u1", Side::Buy, "100", "1"), "b1");
+    let placed = engine.create_order(&bid).unwrap();
+
+    let retried = engine.create_order(&bid).unwrap();
+    assert_eq!(retried.order_id, placed.order_id);
+    assert!(matches!(retried.outcome, OrderOutcome::Duplicate(_)));
+    assert_eq!(balance(&engine, "u1", "USD"), (dec("900"), dec("100")));
+}
+
+#[test]
+fn retrying_a_filled_order_places_nothing() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "1000");
+    deposit(&mut engine, "u2", "BTC", "2");
+    send(&mut engine, MessageFromApi::CREATE_ORDER(limit("u2", Side::Sell, "100", "2")));
+    let buy = with_client_id(market_order("u1", Side::Buy, Some("1"), None), "m1");
+    let placed = engine.create_order(&buy).unwrap();
+    assert_eq!(placed.executed_qty, dec("1"));
+
+    match engine.create_order(&buy) {
+        Err(EngineError::DuplicateClientOrderId { order_id, .. }) => assert_eq!(order_id, placed.order_id),
+        other => panic!("expected the retry to be refused, got {:?}", other.map(|result| result.order_id)),
+    }
+    assert_eq!(balance(&engine, "u1", "BTC"), (dec("1"), dec("0")));
+    assert_eq!(balance(&engine, "u1", "USD"), (dec("900"), dec("0")));
+
+    // still refused once the engine is restored from a snapshot
+    let mut restored = Engine::from_snapshot(engine.snapshot());
+    assert!(matches!(restored.create_order(&buy), Err(EngineError::DuplicateClientOrderId { .. })));
+}
+
+#[test]
+fn an_order_that_was_refused_can_be_retried() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "50");
+    let bid = with_client_id(limit("u1", Side::Buy, "100", "1"), "b1");
+    assert!(matches!(engine.create_order(&bid), Err(EngineError::InsufficientBalance { .. })));
+
+    deposit(&mut engine, "u1", "USD", "50");
+    assert!(matches!(engine.create_order(&bid).unwrap().outcome, OrderOutcome::Placed));
+}
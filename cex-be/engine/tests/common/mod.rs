@@ -0,0 +1,105 @@
+#![allow(dead_code)]
+
+use std::str::FromStr;
+
+use engine::{engine::{Engine, Order}, redis_manager::RedisManager};
+use protocol::messages::{
+    CreateOrderData, MarketConfig, MarketStatus, MessageFromApi, OrderType, ProcessInput, SelfTradePrevention, Side, TimeInForce, ONRAMPDATA,
+};
+use rust_decimal::Decimal;
+
+pub fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+}
+
+/// An engine with nothing sent anywhere, BTC-USD listed at 0.01 ticks and
+/// 0.0001 lots, without fees.
+pub fn engine() -> Engine {
+    RedisManager::set_muted(true);
+    let mut engine = Engine::new();
+    engine.add_market(&market("BTC", "USD", "0.01", "0.0001")).unwrap();
+    engine
+}
+
+pub fn market(base: &str, quote: &str, tick_size: &str, lot_size: &str) -> MarketConfig {
+    MarketConfig {
+        base_asset: base.to_string(),
+        quote_asset: quote.to_string(),
+        tick_size: dec(tick_size),
+        lot_size: dec(lot_size),
+        min_notional: Decimal::ZERO,
+        maker_fee_rate: Decimal::ZERO,
+        taker_fee_rate: Decimal::ZERO,
+        status: MarketStatus::Open,
+    }
+}
+
+/// Apply `message` the way the main loop does, one second after the last input.
+pub fn send(engine: &mut Engine, message: MessageFromApi) {
+    let timestamp = engine.time + 1000;
+    engine.process(ProcessInput { message, client_id: "test".to_string(), timestamp });
+}
+
+pub fn deposit(engine: &mut Engine, user_id: &str, asset: &str, amount: &str) {
+    let txn_id = format!("deposit-{}", engine.deposits.len() + 1);
+    send(engine, MessageFromApi::ON_RAMP(ONRAMPDATA {
+        amount: dec(amount),
+        user_id: user_id.to_string(),
+        txn_id,
+        asset: asset.to_string(),
+    }));
+}
+
+pub fn limit(user_id: &str, side: Side, price: &str, quantity: &str) -> CreateOrderData {
+    CreateOrderData {
+        market: "BTC-USD".to_string(),
+        order_type: OrderType::Limit,
+        price: Some(dec(price)),
+        quantity: Some(dec(quantity)),
+        quote_quantity: None,
+        worst_price: None,
+        trigger_price: None,
+        time_in_force: TimeInForce::Gtc,
+        reprice_post_only: false,
+        self_trade_prevention: SelfTradePrevention::CancelNewest,
+        client_order_id: None,
+        expire_at: None,
+        side,
+        user_id: user_id.to_string(),
+    }
+}
+
+pub fn market_order(user_id: &str, side: Side, quantity: Option<&str>, quote_quantity: Option<&str>) -> CreateOrderData {
+    CreateOrderData {
+        order_type: OrderType::Market,
+        price: None,
+        quantity: quantity.map(dec),
+        quote_quantity: quote_quantity.map(dec),
+        ..limit(user_id, side, "0", "0")
+    }
+}
+
+pub fn balance(engine: &Engine, user_id: &str, asset: &str) -> (Decimal, Decimal) {
+    engine.balances
+        .get(user_id)
+        .and_then(|assets| assets.get(asset))
+        .map(|balance| (balance.available, balance.locked))
+        .unwrap_or_default()
+}
+
+/// A good-til-cancelled limit order as the book holds it, nothing filled.
+pub fn order(order_id: &str, user_id: &str, side: Side, price: &str, quantity: &str) -> Order {
+    Order {
+        price: dec(price),
+        quantity: dec(quantity),
+        order_id: order_id.to_string(),
+        filled: Decimal::ZERO,
+        side,
+        user_id: user_id.to_string(),
+        order_type: OrderType::Limit,
+        time_in_force: TimeInForce::Gtc,
+        self_trade_prevention: SelfTradePrevention::CancelNewest,
+        client_order_id: None,
+        expire_at: None,
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{dec, deposit, engine, limit, market_order, order, send};
+use engine::{engine::{Engine, Order}, invariants, orderbook::OrderBook};
+use protocol::messages::{AmendOrderData, CancelOrderData, CreateOrderData, MessageFromApi, Side, WithdrawData};
+
+// swap BTC-USD for a book holding exactly these orders, nothing is matched
+fn seed_book(engine: &mut Engine, bids: Vec<Order>, asks: Vec<Order>) {
+    let config = engine.orderbooks[0].config();
+    let mut orderbook = OrderBook::new("BTC".to_string(), "USD".to_string(), bids, asks, None, None, 2, 4);
+    orderbook.configure(&config);
+    engine.orderbooks[0] = orderbook;
+}
+
+fn with_client_id(order: CreateOrderData, client_order_id: &str) -> CreateOrderData {
+    CreateOrderData { client_order_id: Some(client_order_id.to_string()), ..order }
+}
+
+#[test]
+fn trading_keeps_the_engine_sound() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "10000");
+    deposit(&mut engine, "u2", "BTC", "5");
+
+    let inputs = vec![
+        MessageFromApi::CREATE_ORDER(limit("u1", Side::Buy, "100", "1")),
+        MessageFromApi::CREATE_ORDER(with_client_id(limit("u1", Side::Buy, "99", "2"), "b2")),
+        // takes the bid at 100 and half of the one at 99
+        MessageFromApi::CREATE_ORDER(limit("u2", Side::Sell, "99", "1.5")),
+        MessageFromApi::CANCEL_ORDER(CancelOrderData {
+            order_id: None,
+            client_order_id: Some("b2".to_string()),
+            market: "BTC-USD".to_string(),
+            user_id: "u1".to_string(),
+        }),
+        MessageFromApi::CREATE_ORDER(with_client_id(limit("u2", Side::Sell, "105", "1"), "a1")),
+        MessageFromApi::AMEND_ORDER(AmendOrderData {
+            order_id: None,
+            client_order_id: Some("a1".to_string()),
+            market: "BTC-USD".to_string(),
+            user_id: "u2".to_string(),
+            quantity: None,
+            price: Some(dec("104")),
+        }),
+        MessageFromApi::CREATE_ORDER(market_order("u1", Side::Buy, None, Some("52"))),
+        MessageFromApi::WITHDRAW(WithdrawData {
+            withdrawal_id: "w1".to_string(),
+            user_id: "u2".to_string(),
+            asset: "USD".to_string(),
+            amount: dec("10"),
+            address: "addr".to_string(),
+        }),
+    ];
+    for input in inputs {
+        send(&mut engine, input);
+        let violations = invariants::check(&engine);
+        assert!(violations.is_empty(), "after input {}: {:?}", engine.sequence, violations);
+    }
+    assert_eq!(engine.orderbooks[0].best_ask(), Some(dec("104")));
+}
+
+#[test]
+fn a_leaked_lock_is_reported() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "1000");
+    send(&mut engine, MessageFromApi::CREATE_ORDER(limit("u1", Side::Buy, "100", "1")));
+
+    // locked without an order behind it, the total is unchanged
+    let usd = engine.balances.get_mut("u1").unwrap().get_mut("USD").unwrap();
+    usd.available -= dec("5");
+    usd.locked += dec("5");
+
+    assert_eq!(invariants::check(&engine), vec!["u1 USD: locked 105 but open orders back 100".to_string()]);
+}
+
+#[test]
+fn an_overfilled_order_is_reported() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "BTC", "1");
+    seed_book(&mut engine, Vec::new(), vec![Order { filled: dec("1.5"), ..order("o1", "u1", Side::Sell, "100", "1") }]);
+
+    let violations = invariants::check(&engine);
+    assert!(violations.contains(&"BTC-USD order o1 filled 1.5 of 1".to_string()), "{:?}", violations);
+}
+
+#[test]
+fn a_crossed_book_is_reported() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "101");
+    deposit(&mut engine, "u2", "BTC", "1");
+    seed_book(
+        &mut engine,
+        vec![order("o1", "u1", Side::Buy, "101", "1")],
+        vec![order("o2", "u2", Side::Sell, "100", "1")],
+    );
+    for (user_id, asset, amount) in [("u1", "USD", "101"), ("u2", "BTC", "1")] {
+        let balance = engine.balances.get_mut(user_id).unwrap().get_mut(asset).unwrap();
+        balance.available -= dec(amount);
+        balance.locked += dec(amount);
+    }
+
+    assert_eq!(invariants::check(&engine), vec!["BTC-USD book is crossed: best bid 101 >= best ask 100".to_string()]);
+}
+
+#[test]
+fn crediting_the_seller_the_wrong_asset_is_reported() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "100");
+    deposit(&mut engine, "u2", "BTC", "1");
+    send(&mut engine, MessageFromApi::CREATE_ORDER(limit("u1", Side::Buy, "100", "1")));
+    send(&mut engine, MessageFromApi::CREATE_ORDER(limit("u2", Side::Sell, "100", "1")));
+    assert!(invariants::check(&engine).is_empty());
+
+    // the seller is paid 100 BTC instead of 100 USD
+    let seller = engine.balances.get_mut("u2").unwrap();
+    seller.get_mut("USD").unwrap().available -= dec("100");
+    seller.get_mut("BTC").unwrap().available += dec("100");
+
+    assert_eq!(invariants::check(&engine), vec![
+        "BTC: accounts hold 101, deposits 1 less withdrawals 0 and fees 0 leave 1".to_string(),
+        "USD: accounts hold 0, deposits 100 less withdrawals 0 and fees 0 leave 100".to_string(),
+    ]);
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use std::{fs::{self, OpenOptions}, io::Write, path::PathBuf, process};
+
+use common::{dec, limit, market};
+use engine::{engine::Engine, journal::{self, Journal}, redis_manager::RedisManager, snapshot};
+use protocol::messages::{CancelOrderData, MessageFromApi, ProcessInput, Side, ONRAMPDATA};
+
+// a journal path of its own for each test, empty to start with
+fn journal_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("engine-journal-{}-{}.log", name, process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+// journal the input and then apply it, the way the engine's main loop does
+fn record(engine: &mut Engine, journal: &mut Journal, message: MessageFromApi) {
+    let input = ProcessInput { message, client_id: "test".to_string(), timestamp: engine.time + 1000 };
+    journal.append(engine.sequence + 1, &input).unwrap();
+    engine.process(input);
+}
+
+fn on_ramp(user_id: &str, asset: &str, amount: &str, txn_id: &str) -> MessageFromApi {
+    MessageFromApi::ON_RAMP(ONRAMPDATA {
+        amount: dec(amount),
+        user_id: user_id.to_string(),
+        txn_id: txn_id.to_string(),
+        asset: asset.to_string(),
+    })
+}
+
+// two deposits journaled into a fresh journal at `path`
+fn two_entries(path: &PathBuf) -> Engine {
+    RedisManager::set_muted(true);
+    let mut engine = Engine::new();
+    let (mut journal, _) = Journal::open(path).unwrap();
+    record(&mut engine, &mut journal, on_ramp("u1", "USD", "100", "t1"));
+    record(&mut engine, &mut journal, on_ramp("u2", "USD", "100", "t2"));
+    engine
+}
+
+#[test]
+fn snapshot_and_journal_tail_rebuild_the_same_state() {
+    RedisManager::set_muted(true);
+    let path = journal_path("rebuild");
+    let mut engine = Engine::new();
+    let (mut journal, _) = Journal::open(&path).unwrap();
+    record(&mut engine, &mut journal, MessageFromApi::ADD_MARKET(market("BTC", "USD", "0.01", "0.0001")));
+    record(&mut engine, &mut journal, on_ramp("u1", "USD", "1000", "t1"));
+    record(&mut engine, &mut journal, on_ramp("u2", "BTC", "2", "t2"));
+    record(&mut engine, &mut journal, MessageFromApi::CREATE_ORDER(limit("u1", Side::Buy, "100", "1")));
+    let snapshot_path = path.with_extension("snapshot");
+    snapshot::save(&snapshot_path, &engine.snapshot()).unwrap();
+    let taken = || snapshot::load(&snapshot_path).unwrap().unwrap();
+
+    record(&mut engine, &mut journal, MessageFromApi::CREATE_ORDER(limit("u2", Side::Sell, "100", "0.25")));
+    record(&mut engine, &mut journal, MessageFromApi::CREATE_ORDER(limit("u2", Side::Sell, "101", "1")));
+    let bid = engine.orderbooks[0].user_order_ids("u1", None).pop();
+    record(&mut engine, &mut journal, MessageFromApi::CANCEL_ORDER(CancelOrderData {
+        order_id: bid,
+        client_order_id: None,
+        market: "BTC-USD".to_string(),
+        user_id: "u1".to_string(),
+    }));
+    let expected = engine.snapshot();
+
+    let mut from_snapshot = Engine::from_snapshot(taken());
+    assert_eq!(journal::replay(&mut from_snapshot, journal::read(&path).unwrap()).unwrap(), 3);
+    assert_eq!(snapshot::diff(&expected, &from_snapshot.snapshot()), Vec::<String>::new());
+
+    let mut from_scratch = Engine::new();
+    assert_eq!(journal::replay(&mut from_scratch, journal::read(&path).unwrap()).unwrap(), 7);
+    assert_eq!(snapshot::diff(&expected, &from_scratch.snapshot()), Vec::<String>::new());
+
+    // once compacted only the tail past the snapshot is left, and it still rebuilds
+    assert_eq!(journal.compact(taken().sequence).unwrap(), 4);
+    let tail = journal::read(&path).unwrap();
+    assert_eq!(tail.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), vec![5, 6, 7]);
+    let mut from_snapshot = Engine::from_snapshot(taken());
+    journal::replay(&mut from_snapshot, tail).unwrap();
+    assert_eq!(snapshot::diff(&expected, &from_snapshot.snapshot()), Vec::<String>::new());
+
+    record(&mut engine, &mut journal, on_ramp("u3", "USD", "5", "t3"));
+    assert_eq!(journal::read(&path).unwrap().last().map(|entry| entry.sequence), Some(8));
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&snapshot_path).unwrap();
+}
+
+#[test]
+fn a_torn_last_line_is_cut_off() {
+    let path = journal_path("torn");
+    let mut engine = two_entries(&path);
+    let len = fs::metadata(&path).unwrap().len();
+    OpenOptions::new().append(true).open(&path).unwrap().write_all(b"3 1a2b").unwrap();
+
+    let (mut journal, entries) = Journal::open(&path).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+    record(&mut engine, &mut journal, on_ramp("u3", "USD", "100", "t3"));
+    assert_eq!(journal::read(&path).unwrap().len(), 3);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn a_last_line_failing_its_checksum_is_cut_off() {
+    let path = journal_path("bad-crc");
+    let mut engine = two_entries(&path);
+    let len = fs::metadata(&path).unwrap().len();
+    let line = r#"3 deadbeef {"message":{"type":"TICK"},"client_id":"engine","timestamp":3000}"#;
+    OpenOptions::new().append(true).open(&path).unwrap().write_all(format!("{}\n", line).as_bytes()).unwrap();
+
+    let (mut journal, entries) = Journal::open(&path).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+    record(&mut engine, &mut journal, on_ramp("u3", "USD", "100", "t3"));
+    assert_eq!(journal::read(&path).unwrap().len(), 3);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn a_bad_line_before_the_last_is_an_error() {
+    let path = journal_path("corrupt");
+    two_entries(&path);
+    let contents = fs::read_to_string(&path).unwrap();
+    let (first, rest) = contents.split_once(' ').unwrap();
+    let (_, rest) = rest.split_once(' ').unwrap();
+    fs::write(&path, format!("{} deadbeef {}", first, rest)).unwrap();
+
+    let error = Journal::open(&path).err().unwrap();
+    assert!(error.to_string().contains("journal line 1: checksum mismatch"), "{}", error);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn starting_over_without_a_snapshot_or_journal_hands_out_new_order_ids() {
+    // the same inputs, applied by an engine started later with nothing to restore
+    let run = |started_at: u64| {
+        let mut engine = common::engine();
+        for (message, offset) in [
+            (on_ramp("u1", "USD", "1000", "t1"), 0),
+            (MessageFromApi::CREATE_ORDER(limit("u1", Side::Buy, "100", "1")), 1000),
+        ] {
+            engine.process(ProcessInput { message, client_id: "test".to_string(), timestamp: started_at + offset });
+        }
+        engine.orderbooks[0].user_order_ids("u1", None)
+    };
+
+    let first = run(1_000_000);
+    assert_eq!(first, run(1_000_000));
+    assert_ne!(first, run(2_000_000));
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{balance, dec, deposit, engine, limit, market_order, send};
+use engine::invariants;
+use protocol::messages::{CreateOrderData, MessageFromApi, SelfTradePrevention, Side};
+
+#[test]
+fn market_buy_locks_for_the_levels_past_own_asks_it_cancels() {
+    let mut engine = engine();
+    deposit(&mut engine, "u1", "USD", "1000");
+    deposit(&mut engine, "u1", "BTC", "1");
+    deposit(&mut engine, "u2", "BTC", "1");
+    send(&mut engine, MessageFromApi::CREATE_ORDER(limit("u1", Side::Sell, "100", "1")));
+    send(&mut engine, MessageFromApi::CREATE_ORDER(limit("u2", Side::Sell, "110", "1")));
+
+    // the own ask at 100 is cancelled rather than filled, so the buy pays 110
+    let buy = market_order("u1", Side::Buy, Some("1"), None);
+    send(&mut engine, MessageFromApi::CREATE_ORDER(CreateOrderData {
+        self_trade_prevention: SelfTradePrevention::CancelOldest,
+        ..buy
+    }));
+
+    assert_eq!(balance(&engine, "u1", "USD"), (dec("890"), dec("0")));
+    assert_eq!(balance(&engine, "u1", "BTC"), (dec("2"), dec("0")));
+    assert_eq!(balance(&engine, "u2", "USD"), (dec("110"), dec("0")));
+    assert!(invariants::check(&engine).is_empty());
+}
+
+#[test]
+fn quote_budget_short_of_a_whole_lot_stops_the_sweep() {
+    let mut engine = engine();
+    engine.add_market(&common::market("ETH", "USD", "1", "10")).unwrap();
+    deposit(&mut engine, "u1", "USD", "1000");
+    deposit(&mut engine, "u2", "ETH", "100");
+    send(&mut engine, MessageFromApi::CREATE_ORDER(CreateOrderData {
+        market: "ETH-USD".to_string(),
+        ..limit("u2", Side::Sell, "5", "100")
+    }));
+
+    // 30 USD buys 6 ETH at 5, less than one 10 ETH lot
+    let result = engine.create_order(&CreateOrderData {
+        market: "ETH-USD".to_string(),
+        ..market_order("u1", Side::Buy, None, Some("30"))
+    }).unwrap();
+
+    assert_eq!(result.executed_qty, dec("0"));
+    assert_eq!(balance(&engine, "u1", "USD"), (dec("1000"), dec("0")));
+    assert!(invariants::check(&engine).is_empty());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::{dec, order};
+use engine::{engine::Order, orderbook::{OrderBook, PriceLevel}};
+use protocol::messages::Side;
+
+// BTC-USD at 0.01 ticks and 0.0001 lots holding `asks`
+fn book(asks: Vec<Order>) -> OrderBook {
+    OrderBook::new("BTC".to_string(), "USD".to_string(), Vec::new(), asks, None, None, 2, 4)
+}
+
+// buy `quantity` at up to `price`, returning which orders filled and by how much
+fn buy(orderbook: &mut OrderBook, price: &str, quantity: &str) -> Vec<(String, String)> {
+    let (_, fills, _) = orderbook.addOrder(order("taker", "t", Side::Buy, price, quantity));
+    fills.into_iter().map(|fill| (fill.market_order_id, fill.qty.to_string())).collect()
+}
+
+fn fill(order_id: &str, quantity: &str) -> (String, String) {
+    (order_id.to_string(), quantity.to_string())
+}
+
+fn levels(levels: Vec<PriceLevel>) -> Vec<(String, String)> {
+    levels.into_iter().map(|level| (level.price, level.quantity)).collect()
+}
+
+#[test]
+fn better_prices_fill_first_then_older_orders() {
+    let mut orderbook = book(vec![
+        order("o1", "u1", Side::Sell, "100", "1"),
+        order("o2", "u2", Side::Sell, "100", "1"),
+        order("o3", "u3", Side::Sell, "99", "1"),
+    ]);
+
+    assert_eq!(buy(&mut orderbook, "100", "2.5"), vec![fill("o3", "1"), fill("o1", "1"), fill("o2", "0.5")]);
+    assert_eq!(orderbook.get_order("o2").unwrap().filled, dec("0.5"));
+}
+
+#[test]
+fn cancelling_from_the_middle_of_a_level_keeps_the_others_in_line() {
+    let mut orderbook = book(vec![
+        order("o1", "u1", Side::Sell, "100", "1"),
+        order("o2", "u2", Side::Sell, "100", "1"),
+        order("o3", "u3", Side::Sell, "100", "1"),
+    ]);
+
+    assert_eq!(orderbook.cancelAsk("o2"), Some(dec("100")));
+    assert_eq!(orderbook.cancelBid("o1"), None);
+    assert_eq!(levels(orderbook.getDepth().asks), vec![("100".to_string(), "2".to_string())]);
+    assert_eq!(buy(&mut orderbook, "100", "2"), vec![fill("o1", "1"), fill("o3", "1")]);
+}
+
+#[test]
+fn growing_an_order_loses_its_place_and_shrinking_keeps_it() {
+    let mut orderbook = book(vec![
+        order("o1", "u1", Side::Sell, "100", "1"),
+        order("o2", "u2", Side::Sell, "100", "1"),
+        order("o3", "u3", Side::Sell, "100", "1"),
+    ]);
+
+    // an amend to a larger size goes back on the book as a new arrival
+    let grown = Order { quantity: dec("2"), ..orderbook.take_order("o1").unwrap() };
+    orderbook.addOrder(grown);
+    orderbook.reduce_order("o2", dec("0.5"));
+
+    assert_eq!(levels(orderbook.getDepth().asks), vec![("100".to_string(), "3.5".to_string())]);
+    assert_eq!(buy(&mut orderbook, "100", "3.5"), vec![fill("o2", "0.5"), fill("o3", "1"), fill("o1", "2")]);
+}
+
+#[test]
+fn emptied_levels_leave_the_book() {
+    let mut orderbook = book(vec![
+        order("o1", "u1", Side::Sell, "99", "1"),
+        order("o2", "u2", Side::Sell, "100", "1"),
+        order("o3", "u3", Side::Sell, "101", "1"),
+    ]);
+
+    orderbook.cancelAsk("o1");
+    assert_eq!(orderbook.best_ask(), Some(dec("100")));
+    buy(&mut orderbook, "100", "1");
+    assert_eq!(orderbook.best_ask(), Some(dec("101")));
+    assert_eq!(levels(orderbook.getDepth().asks), vec![("101".to_string(), "1".to_string())]);
+}
+
+#[test]
+fn depth_sums_what_is_left_of_each_level() {
+    let mut orderbook = OrderBook::new(
+        "BTC".to_string(),
+        "USD".to_string(),
+        vec![order("b1", "u1", Side::Buy, "98", "2"), order("b2", "u2", Side::Buy, "97", "1")],
+        vec![
+            order("o1", "u1", Side::Sell, "100", "1"),
+            order("o2", "u2", Side::Sell, "100", "1.5"),
+            order("o3", "u3", Side::Sell, "101", "0.25"),
+        ],
+        None,
+        None,
+        2,
+        4,
+    );
+
+    buy(&mut orderbook, "100", "0.4");
+    let depth = orderbook.getDepth();
+    assert_eq!(levels(depth.bids), vec![("98".to_string(), "2".to_string()), ("97".to_string(), "1".to_string())]);
+    assert_eq!(levels(depth.asks), vec![("100".to_string(), "2.1".to_string()), ("101".to_string(), "0.25".to_string())]);
+}
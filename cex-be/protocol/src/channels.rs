@@ -0,0 +1,48 @@
+//! Redis pub/sub channel names.
+//!
+//! The engine answers each API request on its own `api_response:<client id>`
+//! channel. Market data and order updates go out on `<stream>@<market or user>`
+//! channels, which the ws server relays to the clients subscribed to them.
+
+/// Where the engine sends the `MessageToApi` answering one request.
+pub fn api_response(client_id: &str) -> String {
+    format!("api_response:{}", client_id)
+}
+
+pub fn trade(market: &str) -> String {
+    format!("trade@{}", market)
+}
+
+pub fn depth(market: &str) -> String {
+    format!("depth@{}", market)
+}
+
+/// Private order lifecycle updates for one user.
+pub fn order(user_id: &str) -> String {
+    format!("order@{}", user_id)
+}
+
+/// Trading status changes of one market.
+pub fn status(market: &str) -> String {
+    format!("status@{}", market)
+}
+
+/// Streams clients may subscribe to through the ws server.
+pub const STREAMS: [&str; 4] = ["trade", "depth", "order", "status"];
+
+/// The user a private stream belongs to, none for market streams. Only that
+/// user may subscribe to it.
+pub fn private_owner(channel: &str) -> Option<&str> {
+    match channel.split_once('@') {
+        Some(("order", user_id)) => Some(user_id),
+        _ => None,
+    }
+}
+
+/// Whether `channel` names one of `STREAMS` for some market or user.
+pub fn is_stream(channel: &str) -> bool {
+    match channel.split_once('@') {
+        Some((stream, target)) => STREAMS.contains(&stream) && !target.is_empty(),
+        None => false,
+    }
+}
@@ -0,0 +1,44 @@
+//! Account fee tiers by trailing quote volume. The db job assigns them, the
+//! engine applies them and the api reports them, all from this one table.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Days of trading the volume behind a tier is summed over.
+pub const VOLUME_WINDOW_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FeeTier {
+    pub level: u32,
+    // trailing quote volume, maker and taker side alike, needed to reach the tier
+    pub min_volume: Decimal,
+    // fraction taken off the market's positive fee rates, rebates are left as they are
+    pub discount: Decimal,
+}
+
+/// Every tier, lowest first. Quote volume is summed across quote assets as is.
+pub fn tiers() -> [FeeTier; 4] {
+    [
+        FeeTier { level: 0, min_volume: Decimal::ZERO, discount: Decimal::ZERO },
+        FeeTier { level: 1, min_volume: Decimal::new(100_000, 0), discount: Decimal::new(10, 2) },
+        FeeTier { level: 2, min_volume: Decimal::new(1_000_000, 0), discount: Decimal::new(20, 2) },
+        FeeTier { level: 3, min_volume: Decimal::new(10_000_000, 0), discount: Decimal::new(30, 2) },
+    ]
+}
+
+/// The tier at `level`, or the base tier if no such level exists.
+pub fn tier(level: u32) -> FeeTier {
+    let tiers = tiers();
+    tiers.iter().copied().find(|tier| tier.level == level).unwrap_or(tiers[0])
+}
+
+/// The highest tier `volume` qualifies for.
+pub fn tier_for_volume(volume: Decimal) -> FeeTier {
+    let tiers = tiers();
+    tiers.iter().rev().copied().find(|tier| volume >= tier.min_volume).unwrap_or(tiers[0])
+}
+
+/// The tier above `level`, `None` at the top.
+pub fn next_tier(level: u32) -> Option<FeeTier> {
+    tiers().into_iter().find(|tier| tier.level > level)
+}
@@ -0,0 +1,68 @@
+//! Wire protocol shared by the api, engine, ws and db services: queue and
+//! channel names, the messages sent over them and the versioned envelope
+//! every queued message travels in.
+
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub mod channels;
+pub mod fee_tiers;
+pub mod messages;
+pub mod queues;
+
+/// Bump when a message changes shape in a way older services can't read.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// `{"version": 1, "body": ...}`, so a service can refuse a message from a
+/// newer or older peer instead of misreading it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Envelope<T> {
+    pub version: u32,
+    pub body: T,
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    UnsupportedVersion { found: u32, expected: u32 },
+    Malformed(serde_json::Error),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::UnsupportedVersion { found, expected } => {
+                write!(f, "protocol version {} is not supported, expected {}", found, expected)
+            }
+            ProtocolError::Malformed(e) => write!(f, "malformed message: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<serde_json::Error> for ProtocolError {
+    fn from(error: serde_json::Error) -> Self {
+        ProtocolError::Malformed(error)
+    }
+}
+
+/// Wrap `body` in an envelope at the current version and serialize it.
+pub fn encode<T: Serialize>(body: &T) -> Result<String, ProtocolError> {
+    Ok(serde_json::to_string(&Envelope { version: PROTOCOL_VERSION, body })?)
+}
+
+/// Read an envelope, checking the version before looking at the body.
+pub fn decode<T: DeserializeOwned>(raw: &str) -> Result<T, ProtocolError> {
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+
+    let Version { version } = serde_json::from_str(raw)?;
+    if version != PROTOCOL_VERSION {
+        return Err(ProtocolError::UnsupportedVersion { found: version, expected: PROTOCOL_VERSION });
+    }
+    let envelope: Envelope<T> = serde_json::from_str(raw)?;
+    Ok(envelope.body)
+}
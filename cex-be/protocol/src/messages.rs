@@ -0,0 +1,778 @@
+//! Every message that crosses a queue or channel between the services.
+
+// variant and struct names double as the wire names
+#![allow(non_camel_case_types)]
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "data")] 
+pub enum MessageFromApi {
+    CREATE_ORDER(CreateOrderData),
+    CANCEL_ORDER(CancelOrderData),
+    ON_RAMP(ONRAMPDATA),
+    GET_DEPTH(GETDEPTHDATA),      
+    GET_OPEN_ORDERS(GETOPENORDERS),
+    GET_BALANCES(GetBalancesData),
+    SET_FEE_TIERS(SetFeeTiersData),
+    GET_ORDER(GetOrderData),
+    AMEND_ORDER(AmendOrderData),
+    CANCEL_ALL_ORDERS(CancelAllOrdersData),
+    SET_DEAD_MAN_SWITCH(DeadManSwitchData),
+    SET_MARKET_STATUS(SetMarketStatusData),
+    // lists a market, or updates the config of one already listed
+    ADD_MARKET(MarketConfig),
+    // holds funds for a withdrawal until it is settled
+    WITHDRAW(WithdrawData),
+    SETTLE_WITHDRAWAL(SettleWithdrawalData),
+    // checks the engine's books and balances are consistent, answered with INVARIANTS
+    CHECK_INVARIANTS,
+    // pushed by the engine itself when a timer is due, so expiries land in the journal
+    TICK,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "data")]
+pub enum PushToDb {
+    TRADE_ADDED(TRADEADDEDDATA),
+    ORDER_UPDATE(ORDERUPDATEDATA),
+    DEPOSIT(DepositPayload),
+    // a withdrawal was requested or settled in the engine
+    WITHDRAWAL(WithdrawalPayload),
+    // every balance change, as one balanced double-entry transaction
+    LEDGER(LedgerTransaction),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProcessInput {
+    pub message: MessageFromApi,
+    pub client_id: String,
+    // unix ms the engine received the input at, stamped by the engine before
+    // journaling so a replay runs on the same clock. Senders leave it at 0
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreateOrderData {
+    pub market: String,
+    #[serde(default)]
+    pub order_type: OrderType,
+    // limit price, not used by market orders
+    pub price: Option<Decimal>,
+    // base amount
+    pub quantity: Option<Decimal>,
+    // market buys only: quote amount to spend instead of a base quantity
+    #[serde(default)]
+    pub quote_quantity: Option<Decimal>,
+    // market orders only: stop sweeping past this price
+    #[serde(default)]
+    pub worst_price: Option<Decimal>,
+    // stop orders only: last trade price that activates the order
+    #[serde(default)]
+    pub trigger_price: Option<Decimal>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    // post-only orders that would cross are moved one tick behind the touch instead of rejected
+    #[serde(default)]
+    pub reprice_post_only: bool,
+    // applied when this order meets a resting order of the same user
+    #[serde(default)]
+    pub self_trade_prevention: SelfTradePrevention,
+    // unique per user among open orders, a resubmission returns the open order instead
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    // good-til-time: unix ms in engine time after which whatever is still open expires
+    #[serde(default)]
+    pub expire_at: Option<u64>,
+    pub side: Side,
+    pub user_id: String,
+}
+
+// names the order by its engine id or by the client_order_id it was placed with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CancelOrderData {
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    pub market: String,
+    // only the owner may cancel
+    pub user_id: String,
+}
+
+// change a resting order in one step: a smaller quantity at the same price keeps
+// its queue position, a new price or a larger quantity sends it to the back
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AmendOrderData {
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    pub market: String,
+    pub user_id: String,
+    // new total quantity, the filled part included
+    #[serde(default)]
+    pub quantity: Option<Decimal>,
+    #[serde(default)]
+    pub price: Option<Decimal>,
+}
+
+// every open order of the user, resting and pending, narrowed by market and side when given
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CancelAllOrdersData {
+    pub user_id: String,
+    #[serde(default)]
+    pub market: Option<String>,
+    #[serde(default)]
+    pub side: Option<Side>,
+}
+
+// longest countdown a dead man's switch can be armed with
+pub const MAX_DEAD_MAN_SWITCH_SECS: u64 = 86_400;
+
+// cancel every open order of the user unless re-sent within `timeout_secs`, 0 disarms
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeadManSwitchData {
+    pub user_id: String,
+    pub timeout_secs: u64,
+}
+
+// one market of the registry, the db `markets` table
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MarketConfig {
+    pub base_asset: String,
+    pub quote_asset: String,
+    // prices and quantities must be whole multiples of these
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+    // smallest price * quantity an order may have
+    pub min_notional: Decimal,
+    // fractions of what each side receives, a negative maker rate is a rebate
+    #[serde(default)]
+    pub maker_fee_rate: Decimal,
+    #[serde(default)]
+    pub taker_fee_rate: Decimal,
+    #[serde(default)]
+    pub status: MarketStatus,
+}
+
+impl MarketConfig {
+    pub fn ticker(&self) -> String {
+        format!("{}-{}", self.base_asset, self.quote_asset)
+    }
+
+    /// Decimal places a price can have.
+    pub fn price_scale(&self) -> u32 {
+        self.tick_size.normalize().scale()
+    }
+
+    /// Decimal places a quantity can have.
+    pub fn quantity_scale(&self) -> u32 {
+        self.lot_size.normalize().scale()
+    }
+}
+
+// admin only, closing a market cancels everything still open on it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetMarketStatusData {
+    pub market: String,
+    pub status: MarketStatus,
+}
+
+// one of the user's open orders, by engine id or client_order_id
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetOrderData {
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    pub market: String,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ONRAMPDATA {
+    pub amount: Decimal,
+    pub user_id: String,
+    // external transaction id, a deposit repeated under the same id is credited once
+    pub txn_id: String,
+    // deposits from before assets were named are USD
+    #[serde(default = "default_deposit_asset")]
+    pub asset: String,
+}
+
+fn default_deposit_asset() -> String {
+    "USD".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetBalancesData {
+    pub user_id: String,
+    // values the balances in this asset as well when set
+    #[serde(default)]
+    pub quote_asset: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WithdrawData {
+    // picked by the client, a retried request with the same id holds funds once
+    pub withdrawal_id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    pub address: String,
+}
+
+// `status` is sent (burns the hold), failed or cancelled (both release it)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SettleWithdrawalData {
+    pub withdrawal_id: String,
+    pub status: WithdrawalStatus,
+    #[serde(default)]
+    pub tx_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GETDEPTHDATA {
+    pub market: String,
+}
+
+/// Every account above the base fee tier. It replaces the previous set, so an
+/// account left out drops back to the base tier.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetFeeTiersData {
+    pub tiers: Vec<UserFeeTier>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserFeeTier {
+    pub user_id: String,
+    pub level: u32,
+    // trailing quote volume the level was computed from
+    pub volume: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GETOPENORDERS {
+    pub user_id: String,
+    pub market: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    #[default]
+    Limit,
+    Market,
+    // rest in the trigger book until the last trade price reaches trigger_price
+    StopMarket,
+    StopLimit,
+}
+
+// trading state of one market, enforced by the engine on every order and cancel
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketStatus {
+    #[default]
+    Open,
+    // no orders or cancels from users, the book is frozen as is
+    Halted,
+    // cancels only, e.g. while winding a market down
+    CancelOnly,
+    // only post-only orders, so the book can refill without trading
+    PostOnly,
+    // delisted, nothing rests on it
+    Closed,
+}
+
+impl MarketStatus {
+    /// Whether new orders are taken at all, post-only markets take post-only orders only.
+    pub fn accepts_orders(&self) -> bool {
+        matches!(self, MarketStatus::Open | MarketStatus::PostOnly)
+    }
+
+    pub fn accepts_cancels(&self) -> bool {
+        matches!(self, MarketStatus::Open | MarketStatus::PostOnly | MarketStatus::CancelOnly)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarketStatus::Open => "open",
+            MarketStatus::Halted => "halted",
+            MarketStatus::CancelOnly => "cancel_only",
+            MarketStatus::PostOnly => "post_only",
+            MarketStatus::Closed => "closed",
+        }
+    }
+
+    /// The status `as_str` spells as `status`.
+    pub fn parse(status: &str) -> Option<Self> {
+        [MarketStatus::Open, MarketStatus::Halted, MarketStatus::CancelOnly, MarketStatus::PostOnly, MarketStatus::Closed]
+            .into_iter()
+            .find(|candidate| candidate.as_str() == status)
+    }
+}
+
+// pending -> approved -> sent | failed, or pending -> cancelled. The engine holds
+// the funds while pending or approved and never sees the approval itself
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalStatus {
+    #[default]
+    Pending,
+    Approved,
+    Sent,
+    Failed,
+    Cancelled,
+}
+
+impl WithdrawalStatus {
+    /// Whether a withdrawal in this status can move to `next`.
+    pub fn can_become(&self, next: WithdrawalStatus) -> bool {
+        matches!(
+            (self, next),
+            (WithdrawalStatus::Pending, WithdrawalStatus::Approved | WithdrawalStatus::Cancelled)
+                | (WithdrawalStatus::Approved, WithdrawalStatus::Sent | WithdrawalStatus::Failed)
+        )
+    }
+
+    /// Whether the withdrawal is over and its hold burnt or released.
+    pub fn is_settled(&self) -> bool {
+        matches!(self, WithdrawalStatus::Sent | WithdrawalStatus::Failed | WithdrawalStatus::Cancelled)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WithdrawalStatus::Pending => "pending",
+            WithdrawalStatus::Approved => "approved",
+            WithdrawalStatus::Sent => "sent",
+            WithdrawalStatus::Failed => "failed",
+            WithdrawalStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// The status `as_str` spells as `status`.
+    pub fn parse(status: &str) -> Option<Self> {
+        [WithdrawalStatus::Pending, WithdrawalStatus::Approved, WithdrawalStatus::Sent, WithdrawalStatus::Failed, WithdrawalStatus::Cancelled]
+            .into_iter()
+            .find(|candidate| candidate.as_str() == status)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TimeInForce {
+    // good till cancelled
+    #[default]
+    Gtc,
+    // immediate or cancel
+    Ioc,
+    // fill or kill
+    Fok,
+    PostOnly,
+}
+
+// decided by the incoming order, the resting order's own setting is not consulted
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SelfTradePrevention {
+    // drop the rest of the incoming order
+    #[default]
+    CancelNewest,
+    // cancel the resting order and keep matching
+    CancelOldest,
+    CancelBoth,
+    // shrink both by the overlap, whichever runs out is gone
+    DecrementAndCancel,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "payload")]
+pub enum MessageToApi {
+    ORDER_PLACED(OrderPlacedPayload),
+    ORDER_PARTIALLY_CANCELLED(OrderPartiallyCancelledPayload),
+    ORDER_KILLED(OrderKilledPayload),
+    ORDER_POST_ONLY_REJECTED(OrderPostOnlyRejectedPayload),
+    ORDER_REPRICED(OrderRepricedPayload),
+    ORDER_PENDING(OrderPendingPayload),
+    ORDER_SELF_TRADE_PREVENTED(OrderSelfTradePreventedPayload),
+    ORDER_CANCELLED(OrderCancelledPayload),
+    ORDER(OrderPayload),
+    // CREATE_ORDER reused an open order's client_order_id, nothing new was placed
+    ORDER_DUPLICATE(OrderPayload),
+    ORDER_AMENDED(OrderAmendedPayload),
+    ORDERS_CANCELLED(OrdersCancelledPayload),
+    DEAD_MAN_SWITCH(DeadManSwitchPayload),
+    MARKET_STATUS(MarketStatusPayload),
+    MARKET_ADDED(MarketConfig),
+    DEPOSITED(DepositPayload),
+    // ON_RAMP repeated a txn_id that was already credited, nothing new was credited
+    DEPOSIT_DUPLICATE(DepositPayload),
+    WITHDRAWAL(WithdrawalPayload),
+    OPEN_ORDERS(OpenOrdersPayload),
+    BALANCES(BalancesPayload),
+    DEPTH(DepthPayload),
+    INVARIANTS(InvariantsPayload),
+    ERROR(ErrorPayload),
+}
+
+// a request the engine refused, `code` is one of the EngineError codes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorPayload {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderPlacedPayload {
+    pub order_id: String,
+    pub executed_qty: Decimal,
+    pub fills: Vec<FillResponse>,
+}
+
+// IOC and market orders: whatever did not fill straight away is dropped
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderPartiallyCancelledPayload {
+    pub order_id: String,
+    pub executed_qty: Decimal,
+    pub cancelled_qty: Decimal,
+    pub fills: Vec<FillResponse>,
+}
+
+// FOK orders the book could not fill in full, nothing was executed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderKilledPayload {
+    pub order_id: String,
+    pub quantity: Decimal,
+    pub fillable_qty: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderPostOnlyRejectedPayload {
+    pub order_id: String,
+    pub price: Decimal,
+}
+
+// post-only order moved so it rests without crossing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderRepricedPayload {
+    pub order_id: String,
+    pub original_price: Decimal,
+    pub price: Decimal,
+}
+
+// stop order waiting in the trigger book
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderPendingPayload {
+    pub order_id: String,
+    pub trigger_price: Decimal,
+}
+
+// some of the order met the user's own resting orders instead of trading
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderSelfTradePreventedPayload {
+    pub order_id: String,
+    pub executed_qty: Decimal,
+    pub cancelled_qty: Decimal,
+    pub fills: Vec<FillResponse>,
+    // resting orders of the same user that were cancelled or shrunk
+    pub cancelled_orders: Vec<SelfTradeCancelResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelfTradeCancelResponse {
+    pub order_id: String,
+    pub cancelled_qty: Decimal,
+    pub remaining_qty: Decimal,
+}
+
+// an open order, resting on the book or waiting in the trigger book
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderPayload {
+    pub order_id: String,
+    pub client_order_id: Option<String>,
+    pub market: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub price: Option<Decimal>,
+    pub quantity: Decimal,
+    pub executed_qty: Decimal,
+    pub trigger_price: Option<Decimal>,
+    #[serde(default)]
+    pub expire_at: Option<u64>,
+    pub status: OrderStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderAmendedPayload {
+    pub order_id: String,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub executed_qty: Decimal,
+    // still on the book, zero if the new price filled it
+    pub remaining_qty: Decimal,
+    // false when the order went to the back of its price level
+    pub kept_priority: bool,
+    // trades the new price crossed into
+    pub fills: Vec<FillResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderCancelledPayload {
+    pub order_id: String,
+    pub executed_qty: Decimal,
+    pub remaining_qty: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrdersCancelledPayload {
+    pub cancelled: Vec<OrderCancelledPayload>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MarketStatusPayload {
+    pub market: String,
+    pub status: MarketStatus,
+    // open orders cancelled by the change, only closing cancels any
+    pub cancelled: usize,
+}
+
+// a credited deposit, `timestamp` is unix ms in engine time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DepositPayload {
+    pub txn_id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    pub timestamp: u64,
+}
+
+// every asset the user holds any of, sorted by asset
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BalancesPayload {
+    pub balances: Vec<AssetBalance>,
+    #[serde(default)]
+    pub portfolio: Option<PortfolioPayload>,
+}
+
+// `withdrawing` is held by pending withdrawals, it is in neither available nor locked
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AssetBalance {
+    pub asset: String,
+    pub available: Decimal,
+    pub locked: Decimal,
+    pub withdrawing: Decimal,
+}
+
+// `total_value` only adds up the assets that could be priced
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PortfolioPayload {
+    pub quote_asset: String,
+    pub total_value: Decimal,
+    pub assets: Vec<AssetValuation>,
+}
+
+// `price` is the last trade price in the quote asset, none while no market
+// between the two has traded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AssetValuation {
+    pub asset: String,
+    pub total: Decimal,
+    pub price: Option<Decimal>,
+    pub value: Option<Decimal>,
+}
+
+// what the invariant checker found after input `sequence`, empty when consistent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InvariantsPayload {
+    pub sequence: u64,
+    pub violations: Vec<String>,
+}
+
+// `timestamp` is unix ms in engine time of the request or, once settled, of the settlement
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WithdrawalPayload {
+    pub withdrawal_id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    pub address: String,
+    pub status: WithdrawalStatus,
+    #[serde(default)]
+    pub tx_hash: Option<String>,
+    pub timestamp: u64,
+}
+
+// `deadline` is unix ms in engine time, none once disarmed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeadManSwitchPayload {
+    pub timeout_secs: u64,
+    pub deadline: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FillResponse {
+    pub price: Decimal,
+    pub qty: Decimal,
+    pub trade_id: u64,
+    pub other_user_id: String,
+    pub market_order_id: String,
+    // taker fee on this fill, negative for a rebate
+    pub fee: Decimal,
+    pub fee_asset: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenOrdersPayload {
+    pub payload: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DepthPayload {
+    pub payload: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TRADEADDEDDATA {
+    pub market: String,
+    // sequential per market, so consumers can spot gaps and drop duplicates
+    pub trade_id: u64,
+    pub is_buyer_maker: bool,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub quote_quantity: Decimal,
+    pub timestamp: i64,
+    pub maker_fee: Decimal,
+    pub maker_fee_asset: String,
+    pub taker_fee: Decimal,
+    pub taker_fee_asset: String,
+    pub maker_user_id: String,
+    pub taker_user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ORDERUPDATEDATA {
+    pub order_id: String,
+    pub exec_qty: Decimal,
+    pub market: Option<String>,
+    pub price: Option<Decimal>,
+    pub quantity: Option<Decimal>,
+    pub side: Option<Side>,
+    pub status: Option<OrderStatus>,
+}
+
+// `transaction_id` counts every transaction the engine posted, `sequence` is
+// the input that caused it. The legs of one asset always sum to zero
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LedgerTransaction {
+    pub transaction_id: u64,
+    pub sequence: u64,
+    pub reason: LedgerReason,
+    // order id, `<market>:<trade_id>`, deposit txn_id or withdrawal id
+    pub reference: String,
+    pub timestamp: u64,
+    pub entries: Vec<LedgerEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub account: String,
+    pub bucket: LedgerBucket,
+    pub asset: String,
+    pub delta: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerBucket {
+    Available,
+    Locked,
+    // held by pending withdrawals
+    Withdrawing,
+    // the account's counterpart outside the exchange, funds enter and leave through it
+    External,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerReason {
+    Deposit,
+    OrderLock,
+    OrderRelease,
+    // both sides of a fill and the fees they paid
+    Trade,
+    WithdrawalHold,
+    WithdrawalSent,
+    // a failed or cancelled withdrawal handed back
+    WithdrawalReleased,
+}
+
+impl LedgerBucket {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerBucket::Available => "available",
+            LedgerBucket::Locked => "locked",
+            LedgerBucket::Withdrawing => "withdrawing",
+            LedgerBucket::External => "external",
+        }
+    }
+}
+
+impl LedgerReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerReason::Deposit => "deposit",
+            LedgerReason::OrderLock => "order_lock",
+            LedgerReason::OrderRelease => "order_release",
+            LedgerReason::Trade => "trade",
+            LedgerReason::WithdrawalHold => "withdrawal_hold",
+            LedgerReason::WithdrawalSent => "withdrawal_sent",
+            LedgerReason::WithdrawalReleased => "withdrawal_released",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    // resting on the book
+    Open,
+    // stop order accepted into the trigger book
+    Pending,
+    // stop order released to the matching engine
+    Triggered,
+    // resting order taken off the book by the engine
+    Cancelled,
+    // resting order shrunk in place, it keeps its queue position
+    Reduced,
+    // resting order given a new price or a larger size, it lost its queue position
+    Amended,
+    // good-til-time order, resting or pending, taken off once its expire_at passed
+    Expired,
+    // matched in full, nothing left on the book
+    Filled,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Open => "open",
+            OrderStatus::Pending => "pending",
+            OrderStatus::Triggered => "triggered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Reduced => "reduced",
+            OrderStatus::Amended => "amended",
+            OrderStatus::Expired => "expired",
+            OrderStatus::Filled => "filled",
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+//! Redis lists the services hand work over on. Producers LPUSH and consumers
+//! BRPOP, so every queue is first in, first out.
+
+/// api -> engine, `ProcessInput` envelopes.
+pub const ENGINE_INPUT: &str = "messages";
+
+/// Redis server the engine pops `ENGINE_INPUT` from, where producers push
+/// unless `REDIS_URL` points them elsewhere.
+pub const ENGINE_INPUT_URL: &str = "redis://localhost:6379";
+
+/// engine -> db processor, `PushToDb` envelopes.
+pub const DB_EVENTS: &str = "db_processor";
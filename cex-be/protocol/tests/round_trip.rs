@@ -0,0 +1,326 @@
+use protocol::{
+    channels, decode, encode, fee_tiers,
+    messages::{
+        AssetBalance, AssetValuation, BalancesPayload, CancelOrderData, CreateOrderData, GetBalancesData, LedgerBucket, LedgerEntry, LedgerReason, LedgerTransaction, PortfolioPayload, DeadManSwitchData, DepositPayload, DeadManSwitchPayload, MarketConfig, MarketStatus, MarketStatusPayload, SetMarketStatusData, ErrorPayload, FillResponse, InvariantsPayload, MessageFromApi, MessageToApi,
+        ONRAMPDATA, OrderPlacedPayload, OrderStatus, OrderType, ProcessInput, PushToDb, SelfTradePrevention, SetFeeTiersData,
+        SettleWithdrawalData, Side, TimeInForce, UserFeeTier, WithdrawData, WithdrawalPayload, WithdrawalStatus, ORDERUPDATEDATA, TRADEADDEDDATA,
+    },
+    ProtocolError, PROTOCOL_VERSION,
+};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(message: T) {
+    let raw = encode(&message).unwrap();
+    let decoded: T = decode(&raw).unwrap();
+    assert_eq!(decoded, message);
+}
+
+fn create_order() -> CreateOrderData {
+    CreateOrderData {
+        market: "BTC-USD".to_string(),
+        order_type: OrderType::StopLimit,
+        price: Some(Decimal::new(10050, 2)),
+        quantity: Some(Decimal::new(5, 1)),
+        quote_quantity: None,
+        worst_price: None,
+        trigger_price: Some(Decimal::new(10000, 2)),
+        time_in_force: TimeInForce::Ioc,
+        reprice_post_only: false,
+        self_trade_prevention: SelfTradePrevention::DecrementAndCancel,
+        client_order_id: Some("my-order-1".to_string()),
+        expire_at: Some(1_760_000_060_000),
+        side: Side::Buy,
+        user_id: "u1".to_string(),
+    }
+}
+
+#[test]
+fn api_to_engine_messages_round_trip() {
+    round_trip(ProcessInput {
+        message: MessageFromApi::CREATE_ORDER(create_order()),
+        client_id: "client-1".to_string(),
+        timestamp: 1_760_000_000_000,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::CANCEL_ORDER(CancelOrderData {
+            order_id: None,
+            client_order_id: Some("my-order-1".to_string()),
+            market: "BTC-USD".to_string(),
+            user_id: "u1".to_string(),
+        }),
+        client_id: "client-2".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::SET_FEE_TIERS(SetFeeTiersData {
+            tiers: vec![UserFeeTier { user_id: "u1".to_string(), level: 2, volume: Decimal::new(2_500_000, 0) }],
+        }),
+        client_id: "fee_tier_job".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::SET_DEAD_MAN_SWITCH(DeadManSwitchData { user_id: "u1".to_string(), timeout_secs: 30 }),
+        client_id: "client-3".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::SET_MARKET_STATUS(SetMarketStatusData { market: "BTC-USD".to_string(), status: MarketStatus::CancelOnly }),
+        client_id: "admin".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::ADD_MARKET(MarketConfig {
+            base_asset: "SOL".to_string(),
+            quote_asset: "USDC".to_string(),
+            tick_size: Decimal::new(5, 3),
+            lot_size: Decimal::new(1, 2),
+            min_notional: Decimal::new(5, 0),
+            maker_fee_rate: Decimal::new(1, 3),
+            taker_fee_rate: Decimal::new(2, 3),
+            status: MarketStatus::PostOnly,
+        }),
+        client_id: "admin".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::ON_RAMP(ONRAMPDATA {
+            amount: Decimal::new(15, 1),
+            user_id: "u1".to_string(),
+            txn_id: "0xabc".to_string(),
+            asset: "BTC".to_string(),
+        }),
+        client_id: "admin".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::WITHDRAW(WithdrawData {
+            withdrawal_id: "w1".to_string(),
+            user_id: "u1".to_string(),
+            asset: "BTC".to_string(),
+            amount: Decimal::new(5, 1),
+            address: "bc1qexample".to_string(),
+        }),
+        client_id: "client-4".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::SETTLE_WITHDRAWAL(SettleWithdrawalData {
+            withdrawal_id: "w1".to_string(),
+            status: WithdrawalStatus::Sent,
+            tx_hash: Some("0xdef".to_string()),
+        }),
+        client_id: "admin".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::GET_BALANCES(GetBalancesData { user_id: "u1".to_string(), quote_asset: Some("USD".to_string()) }),
+        client_id: "client-5".to_string(),
+        timestamp: 0,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::TICK,
+        client_id: "engine".to_string(),
+        timestamp: 1_760_000_030_000,
+    });
+    round_trip(ProcessInput {
+        message: MessageFromApi::CHECK_INVARIANTS,
+        client_id: "client-3".to_string(),
+        timestamp: 0,
+    });
+}
+
+#[test]
+fn engine_to_api_messages_round_trip() {
+    round_trip(MessageToApi::ORDER_PLACED(OrderPlacedPayload {
+        order_id: "o1".to_string(),
+        executed_qty: Decimal::new(25, 2),
+        fills: vec![FillResponse {
+            price: Decimal::new(100, 0),
+            qty: Decimal::new(25, 2),
+            trade_id: 7,
+            other_user_id: "u2".to_string(),
+            market_order_id: "o0".to_string(),
+            fee: Decimal::new(25, 5),
+            fee_asset: "BTC".to_string(),
+        }],
+    }));
+    round_trip(MessageToApi::DEAD_MAN_SWITCH(DeadManSwitchPayload { timeout_secs: 30, deadline: Some(1_760_000_030_000) }));
+    round_trip(MessageToApi::MARKET_STATUS(MarketStatusPayload { market: "BTC-USD".to_string(), status: MarketStatus::Closed, cancelled: 3 }));
+    round_trip(MessageToApi::BALANCES(BalancesPayload {
+        balances: vec![AssetBalance {
+            asset: "BTC".to_string(),
+            available: Decimal::new(15, 1),
+            locked: Decimal::new(5, 1),
+            withdrawing: Decimal::ZERO,
+        }],
+        portfolio: Some(PortfolioPayload {
+            quote_asset: "USD".to_string(),
+            total_value: Decimal::new(200, 0),
+            assets: vec![AssetValuation {
+                asset: "BTC".to_string(),
+                total: Decimal::new(2, 0),
+                price: Some(Decimal::new(100, 0)),
+                value: Some(Decimal::new(200, 0)),
+            }],
+        }),
+    }));
+    round_trip(MessageToApi::INVARIANTS(InvariantsPayload {
+        sequence: 42,
+        violations: vec!["BTC-USD book is crossed: best bid 101 >= best ask 100".to_string()],
+    }));
+    round_trip(MessageToApi::ERROR(ErrorPayload {
+        code: "INSUFFICIENT_BALANCE".to_string(),
+        message: "Insufficient USD balance".to_string(),
+    }));
+}
+
+#[test]
+fn db_events_round_trip() {
+    round_trip(PushToDb::TRADE_ADDED(TRADEADDEDDATA {
+        market: "BTC-USD".to_string(),
+        trade_id: 7,
+        is_buyer_maker: true,
+        price: Decimal::new(100, 0),
+        quantity: Decimal::new(25, 2),
+        quote_quantity: Decimal::new(25, 0),
+        timestamp: 1_700_000_000,
+        maker_fee: Decimal::new(-25, 4),
+        maker_fee_asset: "USD".to_string(),
+        taker_fee: Decimal::new(125, 6),
+        taker_fee_asset: "BTC".to_string(),
+        maker_user_id: "u2".to_string(),
+        taker_user_id: "u1".to_string(),
+    }));
+    round_trip(PushToDb::ORDER_UPDATE(ORDERUPDATEDATA {
+        order_id: "o1".to_string(),
+        exec_qty: Decimal::new(25, 2),
+        market: Some("BTC-USD".to_string()),
+        price: None,
+        quantity: Some(Decimal::ONE),
+        side: Some(Side::Sell),
+        status: Some(OrderStatus::Cancelled),
+    }));
+    round_trip(PushToDb::DEPOSIT(DepositPayload {
+        txn_id: "0xabc".to_string(),
+        user_id: "u1".to_string(),
+        asset: "BTC".to_string(),
+        amount: Decimal::new(15, 1),
+        timestamp: 1_760_000_000_000,
+    }));
+    round_trip(PushToDb::WITHDRAWAL(WithdrawalPayload {
+        withdrawal_id: "w1".to_string(),
+        user_id: "u1".to_string(),
+        asset: "BTC".to_string(),
+        amount: Decimal::new(5, 1),
+        address: "bc1qexample".to_string(),
+        status: WithdrawalStatus::Pending,
+        tx_hash: None,
+        timestamp: 1_760_000_000_000,
+    }));
+    round_trip(PushToDb::LEDGER(LedgerTransaction {
+        transaction_id: 42,
+        sequence: 17,
+        reason: LedgerReason::Trade,
+        reference: "BTC-USD:7".to_string(),
+        timestamp: 1_760_000_000_000,
+        entries: vec![
+            LedgerEntry { account: "u1".to_string(), bucket: LedgerBucket::Locked, asset: "USD".to_string(), delta: Decimal::new(-25, 0) },
+            LedgerEntry { account: "u2".to_string(), bucket: LedgerBucket::Available, asset: "USD".to_string(), delta: Decimal::new(25, 0) },
+        ],
+    }));
+}
+
+#[test]
+fn withdrawals_follow_their_lifecycle() {
+    assert!(WithdrawalStatus::Pending.can_become(WithdrawalStatus::Approved));
+    assert!(WithdrawalStatus::Pending.can_become(WithdrawalStatus::Cancelled));
+    assert!(WithdrawalStatus::Approved.can_become(WithdrawalStatus::Failed));
+    assert!(!WithdrawalStatus::Pending.can_become(WithdrawalStatus::Sent));
+    assert!(!WithdrawalStatus::Approved.can_become(WithdrawalStatus::Cancelled));
+    assert!(!WithdrawalStatus::Sent.can_become(WithdrawalStatus::Failed));
+    assert_eq!(WithdrawalStatus::parse("sent"), Some(WithdrawalStatus::Sent));
+}
+
+#[test]
+fn order_statuses_are_stored_as_they_are_sent() {
+    for status in [OrderStatus::Open, OrderStatus::Cancelled, OrderStatus::Expired, OrderStatus::Filled] {
+        assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+    }
+}
+
+#[test]
+fn wire_format_is_tagged_and_versioned() {
+    let raw = encode(&PushToDb::ORDER_UPDATE(ORDERUPDATEDATA {
+        order_id: "o1".to_string(),
+        exec_qty: Decimal::ONE,
+        market: None,
+        price: None,
+        quantity: None,
+        side: None,
+        status: None,
+    }))
+    .unwrap();
+    let value: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    assert_eq!(value["version"], PROTOCOL_VERSION);
+    assert_eq!(value["body"]["type"], "ORDER_UPDATE");
+    assert_eq!(value["body"]["data"]["exec_qty"], "1");
+
+    let raw = encode(&MessageFromApi::CREATE_ORDER(create_order())).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    assert_eq!(value["body"]["data"]["order_type"], "stop_limit");
+    assert_eq!(value["body"]["data"]["self_trade_prevention"], "DECREMENT_AND_CANCEL");
+}
+
+#[test]
+fn deposits_without_an_asset_are_usd() {
+    let raw = format!(
+        r#"{{"version":{},"body":{{"type":"ON_RAMP","data":{{"amount":"10000","user_id":"u1","txn_id":"t1"}}}}}}"#,
+        PROTOCOL_VERSION
+    );
+    match decode::<MessageFromApi>(&raw).unwrap() {
+        MessageFromApi::ON_RAMP(deposit) => {
+            assert_eq!(deposit.asset, "USD");
+            assert_eq!(deposit.amount, Decimal::new(10_000, 0));
+        }
+        other => panic!("expected an ON_RAMP, got {:?}", other),
+    }
+}
+
+#[test]
+fn other_versions_are_refused() {
+    let raw = format!(r#"{{"version":{},"body":{{"type":"ORDER_UPDATE"}}}}"#, PROTOCOL_VERSION + 1);
+    match decode::<PushToDb>(&raw) {
+        Err(ProtocolError::UnsupportedVersion { found, expected }) => {
+            assert_eq!(found, PROTOCOL_VERSION + 1);
+            assert_eq!(expected, PROTOCOL_VERSION);
+        }
+        other => panic!("expected a version error, got {:?}", other),
+    }
+    assert!(matches!(decode::<PushToDb>(r#"{"type":"ORDER_UPDATE"}"#), Err(ProtocolError::Malformed(_))));
+}
+
+#[test]
+fn stream_channels_are_recognised() {
+    assert_eq!(channels::trade("BTC-USD"), "trade@BTC-USD");
+    assert_eq!(channels::api_response("c1"), "api_response:c1");
+    assert!(channels::is_stream(&channels::depth("ETH-USD")));
+    assert!(channels::is_stream(&channels::order("u1")));
+    assert!(channels::is_stream(&channels::status("BTC-USD")));
+    assert!(!channels::is_stream("trade@"));
+    assert!(!channels::is_stream("api_response:c1"));
+    assert_eq!(channels::private_owner(&channels::order("u1")), Some("u1"));
+    assert_eq!(channels::private_owner(&channels::trade("BTC-USD")), None);
+}
+
+#[test]
+fn fee_tiers_follow_volume() {
+    assert_eq!(fee_tiers::tier_for_volume(Decimal::ZERO).level, 0);
+    assert_eq!(fee_tiers::tier_for_volume(Decimal::new(99_999, 0)).level, 0);
+    assert_eq!(fee_tiers::tier_for_volume(Decimal::new(100_000, 0)).level, 1);
+    assert_eq!(fee_tiers::tier_for_volume(Decimal::new(50_000_000, 0)).level, 3);
+    assert_eq!(fee_tiers::next_tier(1).map(|tier| tier.level), Some(2));
+    assert_eq!(fee_tiers::next_tier(3), None);
+    assert_eq!(fee_tiers::tier(42).level, 0);
+}
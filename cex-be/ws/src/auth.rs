@@ -0,0 +1,18 @@
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+// the part of the API's token claims the socket needs
+#[derive(Deserialize)]
+struct Claims {
+    user_id: String,
+}
+
+/// User id of a token issued by the API's auth service, `None` if it is
+/// invalid or expired.
+pub fn verify_token(token: &str) -> Option<String> {
+    let jwt_secret = std::env::var("JWT_SECRET")
+        .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string());
+    decode::<Claims>(token, &DecodingKey::from_secret(jwt_secret.as_ref()), &Validation::new(Algorithm::HS256))
+        .ok()
+        .map(|data| data.claims.user_id)
+}
@@ -1,9 +1,11 @@
-use std::{ env, net::SocketAddr};
-use tokio::net::{TcpListener, TcpStream};
-use log::{info, warn, error};
-use futures::{StreamExt, SinkExt};
+use std::{ env, net::SocketAddr, sync::Arc};
+use tokio::{net::{TcpListener, TcpStream}, sync::Mutex};
+use log::{info, error};
 use tokio_tungstenite::accept_async;
 
+use crate::{subscription_manager::SubscriptionManager, user_manager::UserManager};
+
+mod auth;
 mod user;
 pub mod types;
 mod subscription_manager;
@@ -19,20 +21,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let listenter = TcpListener::bind(&addr).await.expect("Failed to bind");
 
+    let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+    let user_manager = UserManager::new();
+    let subscription_manager = Arc::new(Mutex::new(SubscriptionManager::new(&redis_url, user_manager.clone())?));
+
     info!("Starting CEX WebSocket server...");
     info!("Listening on address: {}", addr);
     info!("WebSocket server is ready to accept connections");
 
     while let Ok((stream, _)) = listenter.accept().await {
         info!("New WebSocket connection established");
-        tokio::spawn(handle_connection(stream));
+        tokio::spawn(handle_connection(stream, user_manager.clone(), subscription_manager.clone()));
     }
 
     Ok(())
 }
 
-async fn handle_connection(stream: TcpStream) {
-    let mut ws_stream = match accept_async(stream).await {
+async fn handle_connection(
+    stream: TcpStream,
+    user_manager: Arc<UserManager>,
+    subscription_manager: Arc<Mutex<SubscriptionManager>>,
+) {
+    let ws_stream = match accept_async(stream).await {
         Ok(ws) => ws,
         Err(e) => {
             error!("WebSocket handshake failed: {}", e);
@@ -42,25 +52,8 @@ async fn handle_connection(stream: TcpStream) {
     
     info!("WebSocket connection established successfully");
 
-    while let Some(result) = ws_stream.next().await {
-        match result {
-            Ok(msg) => {
-                if msg.is_text() || msg.is_binary() {
-                    if ws_stream.send(msg).await.is_err() { 
-                        warn!("Failed to send message to client");
-                        break; 
-                    }
-                } else if msg.is_close() {
-                    info!("Client disconnected gracefully");
-                    break;
-                }
-            }
-            Err(e) => {
-                error!("WebSocket error: {}", e);
-                break;
-            }
-        }
-    }
+    let user = user_manager.add_user(ws_stream, subscription_manager).await;
+    user.listen().await;
     
     info!("WebSocket connection closed");
-}
\ No newline at end of file
+}
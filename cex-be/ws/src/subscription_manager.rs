@@ -3,14 +3,19 @@ use std::{collections::HashMap};
 use anyhow::Ok;
 use futures::{ StreamExt };
 use redis::Client;
-use tokio::task;
-use std::sync::Arc;
+use tokio::task::{self, JoinHandle};
+use std::sync::{Arc, Mutex};
 
 use crate::user_manager::{UserManager};
 
+// channel -> users subscribed to it, shared with the task forwarding each channel
+type Subscribers = Arc<Mutex<HashMap<String, Vec<String>>>>;
+
 pub struct SubscriptionManager{
     subscription: HashMap<String, Vec<String>>,
-    reverse_subscriptions: HashMap<String, Vec<String>>,
+    reverse_subscriptions: Subscribers,
+    // task forwarding each channel with subscribers from its own redis pubsub connection
+    listeners: HashMap<String, JoinHandle<()>>,
     redis_client: Client,
     user_manager: Arc<UserManager>
 }
@@ -20,7 +25,8 @@ impl SubscriptionManager {
         let client = Client::open(redis_url)?;
         Ok(Self {
             subscription: HashMap::new(),
-            reverse_subscriptions: HashMap::new(),
+            reverse_subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            listeners: HashMap::new(),
             redis_client: client,
             user_manager
         })
@@ -48,38 +54,33 @@ impl SubscriptionManager {
             .push(subscription.clone());
 
         // 3. Add to topic -> users map
-        let user = self.reverse_subscriptions
-            .entry(subscription.clone())
-            .or_default(); // it will return this &mut Vec<String>
-        // now it will still return this ()
-        user.push(user_id.clone());
+        let first = {
+            let mut subscribers = self.reverse_subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+            let user = subscribers
+                .entry(subscription.clone())
+                .or_default(); // it will return this &mut Vec<String>
+            user.push(user_id.clone());
+            user.len() == 1
+        };
 
         // 4. If first user for this topic, subscribe in Redis
-        if user.len() == 1 {
-            let mut conn = self.redis_client.get_async_connection().await?;
+        if first {
+            let conn = self.redis_client.get_async_connection().await?;
             let mut pubsub = conn.into_pubsub();
             pubsub.subscribe(subscription.clone()).await?;
 
             // background task to handle messages from this channel
-            let user_manager = Arc::new(self.user_manager.clone()); // everything inside task spawn must be static not borrowed that's why cloned it. because task may outlive the function
-            task::spawn(async move {
+            let user_manager = self.user_manager.clone(); // everything inside task spawn must be static not borrowed that's why cloned it. because task may outlive the function
+            let subscribers = self.reverse_subscriptions.clone();
+            let listener = task::spawn(async move {
                 let mut pubsub = pubsub;
                 while let Some(msg) = pubsub.on_message().next().await {
                     let payload: String = msg.get_payload().unwrap_or_default();
                     let channel = msg.get_channel_name().to_string();
-                    println!("Received from Redis: {payload} on {channel}");
-
-                    let outgoing_msg = crate::types::OutgoingMessage {
-                        event: channel.clone(),
-                        data: payload.clone(),
-                    };
-                    
-                    // Send to all users subscribed to this channel
-                    if let Err(e) = user_manager.emit_to_user(&channel, outgoing_msg).await {
-                        eprintln!("Failed to emit message: {}", e);
-                    }
+                    Self::handle_redis_message(&subscribers, &user_manager, &channel, &payload).await;
                 }
             });
+            self.listeners.insert(subscription, listener);
         }
 
         Ok(())
@@ -92,16 +93,24 @@ impl SubscriptionManager {
         }
 
         // 2. Remove from topic -> users map
-        if let Some(users) = self.reverse_subscriptions.get_mut(&subscription) {
-            users.retain(|u| u != &user_id);
-
-            // 3. If no users left for this topic, unsubscribe from Redis
-            if users.is_empty() {
-                let mut conn = self.redis_client.get_async_connection().await?;
-                let mut pubsub = conn.into_pubsub();
-                pubsub.unsubscribe(subscription.clone()).await?;
-                println!("Unsubscribed from Redis channel: {}", subscription);
+        let last = {
+            let mut subscribers = self.reverse_subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+            let last = subscribers.get_mut(&subscription).is_some_and(|users| {
+                users.retain(|u| u != &user_id);
+                users.is_empty()
+            });
+            if last {
+                subscribers.remove(&subscription);
             }
+            last
+        };
+
+        // 3. If no users left for this topic, drop its pubsub connection and with it the Redis subscription
+        if last {
+            if let Some(listener) = self.listeners.remove(&subscription) {
+                listener.abort();
+            }
+            println!("Unsubscribed from Redis channel: {}", subscription);
         }
 
         Ok(())
@@ -115,22 +124,28 @@ impl SubscriptionManager {
                 self.unsubscribe(user_id.to_string(), s).await?;
             }
         }
+        self.subscription.remove(user_id);
 
         println!("user left {}", user_id);
         Ok(())
     }
 
     // redisCallbackHandler
-    pub async fn handle_redis_message(&self, channel: &str, payload: &str) {
-        if let Some(user_ids) = self.reverse_subscriptions.get(channel){
-            for user_id in user_ids {
-                let outgoing_msg = crate::types::OutgoingMessage {
-                    event: channel.to_string(),
-                    data: payload.to_string(),
-                };
-                if let Err(e) = self.user_manager.emit_to_user(user_id, outgoing_msg).await {
-                    eprintln!("Failed to emit message to user {}: {}", user_id, e);
-                }
+    async fn handle_redis_message(subscribers: &Subscribers, user_manager: &UserManager, channel: &str, payload: &str) {
+        // copied out so the lock isn't held across the sends
+        let user_ids = subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(channel)
+            .cloned()
+            .unwrap_or_default();
+        for user_id in user_ids {
+            let outgoing_msg = crate::types::OutgoingMessage {
+                event: channel.to_string(),
+                data: payload.to_string(),
+            };
+            if let Err(e) = user_manager.emit_to_user(&user_id, outgoing_msg).await {
+                eprintln!("Failed to emit message to user {}: {}", user_id, e);
             }
         }
     }
@@ -1,32 +1,54 @@
 use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
 use tokio::net::TcpStream;
-use futures::{SinkExt, StreamExt};
+use futures::{stream::SplitStream, StreamExt};
 use crate::types::{IncomingMessage, OutgoingMessage};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 use crate::subscription_manager::SubscriptionManager;
+use crate::user_manager::UserManager;
+use protocol::{channels, messages::{DeadManSwitchData, MessageFromApi, ProcessInput, MAX_DEAD_MAN_SWITCH_SECS}, queues};
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use crate::auth;
 
 pub struct User {
     id: String,
-    ws: WebSocketStream<TcpStream>,
+    ws: SplitStream<WebSocketStream<TcpStream>>,
+    // replies go through the same outbox as stream updates, the user manager's writer sends them
+    outbox: mpsc::UnboundedSender<Message>,
     subscription: Vec<String>,
-    subscription_manager: Arc<Mutex<SubscriptionManager>>
+    subscription_manager: Arc<Mutex<SubscriptionManager>>,
+    user_manager: Arc<UserManager>,
+    // account the connection authenticated as with AUTH, needed for anything acting on orders
+    account_id: Option<String>,
+    engine_client: redis::Client,
+    // opened on the first message to the engine and kept for the connection's lifetime
+    engine_conn: Option<MultiplexedConnection>
 }
 
 pub const SUBSCRIBE: &str = "SUBSCRIBE";
 pub const UNSUBSCRIBE: &str = "UNSUBSCRIBE";
+pub const AUTH: &str = "AUTH";
+pub const DEAD_MAN_SWITCH: &str = "DEAD_MAN_SWITCH";
 
 impl User {
     pub fn new(
         id: impl Into<String>,
-        ws: WebSocketStream<TcpStream>,
+        ws: SplitStream<WebSocketStream<TcpStream>>,
+        outbox: mpsc::UnboundedSender<Message>,
         subscription_manager: Arc<Mutex<SubscriptionManager>>,
+        user_manager: Arc<UserManager>,
     ) -> Self {
+        let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
         Self { 
             id: id.into(), 
             ws, 
+            outbox,
             subscription: Vec::new(),
-            subscription_manager
+            subscription_manager,
+            user_manager,
+            account_id: None,
+            engine_client: redis::Client::open(redis_url).expect("Invalid REDIS_URL"),
+            engine_conn: None
         }
     }
 
@@ -48,9 +70,66 @@ impl User {
         });
     }
 
+    pub async fn authenticate(&mut self, token: &str) -> anyhow::Result<()> {
+        let Some(account_id) = auth::verify_token(token) else {
+            return self.emit(OutgoingMessage { event: "error".to_string(), data: "Invalid token".to_string() }).await;
+        };
+        // private streams of an account this connection no longer is go away
+        let foreign: Vec<String> = self.subscription
+            .iter()
+            .filter(|s| channels::private_owner(s).is_some_and(|owner| owner != account_id))
+            .cloned()
+            .collect();
+        for subscription in foreign {
+            self.unsubscribe(subscription).await;
+        }
+        self.account_id = Some(account_id.clone());
+        self.emit(OutgoingMessage { event: "authenticated".to_string(), data: account_id }).await
+    }
+
+    // arms, refreshes or with 0 disarms the account's switch, the engine enforces it
+    // even after this connection is gone
+    pub async fn set_dead_man_switch(&mut self, timeout_secs: &str) -> anyhow::Result<()> {
+        let Some(user_id) = self.account_id.clone() else {
+            return self.emit(OutgoingMessage { event: "error".to_string(), data: "Authentication required".to_string() }).await;
+        };
+        let timeout_secs = match timeout_secs.parse::<u64>() {
+            Ok(secs) if secs <= MAX_DEAD_MAN_SWITCH_SECS => secs,
+            _ => {
+                let error = format!("Timeout must be 0 to {} seconds", MAX_DEAD_MAN_SWITCH_SECS);
+                return self.emit(OutgoingMessage { event: "error".to_string(), data: error }).await;
+            }
+        };
+
+        let message = protocol::encode(&ProcessInput {
+            message: MessageFromApi::SET_DEAD_MAN_SWITCH(DeadManSwitchData { user_id, timeout_secs }),
+            client_id: format!("ws:{}", self.id),
+            timestamp: 0,
+        })?;
+        self.push_to_engine(message).await?;
+        self.emit(OutgoingMessage { event: "dead_man_switch".to_string(), data: timeout_secs.to_string() }).await
+    }
+
+    async fn push_to_engine(&mut self, message: String) -> anyhow::Result<()> {
+        let mut conn = match &self.engine_conn {
+            Some(conn) => conn.clone(),
+            None => {
+                let conn = self.engine_client.get_multiplexed_tokio_connection().await?;
+                self.engine_conn = Some(conn.clone());
+                conn
+            }
+        };
+        if let Err(e) = conn.lpush::<_, _, ()>(queues::ENGINE_INPUT, message).await {
+            // a broken connection stays broken, the next message opens a new one
+            self.engine_conn = None;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
     pub async fn emit(&mut self, message: OutgoingMessage) -> anyhow::Result<()> {
         let json = serde_json::to_string(&message)?;
-        self.ws.send(Message::Text(json.into())).await.map_err(|e| anyhow::anyhow!("WebSocket send error: {}", e))?;
+        self.outbox.send(Message::Text(json.into())).map_err(|e| anyhow::anyhow!("WebSocket send error: {}", e))?;
         Ok(())
     }
 
@@ -65,6 +144,18 @@ impl User {
                             SUBSCRIBE => {
                                 for s in parsed.params {
                                     println!("what is s here {}", s);
+                                    if !channels::is_stream(&s) {
+                                        eprintln!("Ignoring subscription to unknown stream {}", s);
+                                        continue;
+                                    }
+                                    // order updates are private, only their own account gets them
+                                    if channels::private_owner(&s).is_some_and(|owner| self.account_id.as_deref() != Some(owner)) {
+                                        let error = format!("Not allowed to subscribe to {}", s);
+                                        if let Err(e) = self.emit(OutgoingMessage { event: "error".to_string(), data: error }).await {
+                                            eprintln!("Failed to reject subscription of user {}: {}", self.id, e);
+                                        }
+                                        continue;
+                                    }
                                     self.subscribe(s).await;
                                 }
                             }
@@ -74,6 +165,18 @@ impl User {
                                     self.unsubscribe(s).await;
                                 }
                             }
+                            AUTH => {
+                                let token = parsed.params.first().cloned().unwrap_or_default();
+                                if let Err(e) = self.authenticate(&token).await {
+                                    eprintln!("Failed to authenticate user {}: {}", self.id, e);
+                                }
+                            }
+                            DEAD_MAN_SWITCH => {
+                                let timeout_secs = parsed.params.first().cloned().unwrap_or_default();
+                                if let Err(e) = self.set_dead_man_switch(&timeout_secs).await {
+                                    eprintln!("Failed to set dead man's switch for user {}: {}", self.id, e);
+                                }
+                            }
                             _ => {}
                         }
                     }
@@ -85,6 +188,11 @@ impl User {
                 }
             }
         }
+
+        self.user_manager.remove_user(&self.id).await;
+        if let Err(e) = self.subscription_manager.lock().await.user_left(&self.id).await {
+            eprintln!("Failed to drop subscriptions of user {}: {}", self.id, e);
+        }
     }
 
 }
\ No newline at end of file
@@ -1,51 +1,56 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use futures::{SinkExt, StreamExt};
+use tokio::sync::{mpsc, Mutex};
 use uuid::Uuid;
-use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
 use tokio::net::TcpStream;
 
 use crate::user::User;
 use crate::subscription_manager::SubscriptionManager;
 
 pub struct UserManager {
-    users: Mutex<HashMap<String, User>>,
-    subscription_manager: Arc<Mutex<SubscriptionManager>>,
+    // connection id -> outbox drained onto that connection's socket
+    users: Mutex<HashMap<String, mpsc::UnboundedSender<Message>>>,
 }
 
 impl UserManager {
-    pub fn new(subscription_manager: Arc<Mutex<SubscriptionManager>>) -> Arc<Self> {
+    pub fn new() -> Arc<Self> {
         Arc::new(Self {
             users: Mutex::new(HashMap::new()),
-            subscription_manager,
         })
     }
 
+    /// Register a new connection. Its socket is written from a task of its own,
+    /// so stream updates reach it while the returned `User` reads its requests.
     pub async fn add_user(
-        self: &Arc<Self>, 
-        ws: WebSocketStream<TcpStream>
-    ) -> String {
+        self: &Arc<Self>,
+        ws: WebSocketStream<TcpStream>,
+        subscription_manager: Arc<Mutex<SubscriptionManager>>,
+    ) -> User {
         let id = Self::random_id();
-        let user = User::new(id.clone(), ws, Arc::clone(&self.subscription_manager));
-        self.users.lock().await.insert(id.clone(), user);
-        id
+        let (mut sink, stream) = ws.split();
+        let (outbox, mut messages) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(message) = messages.recv().await {
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        self.users.lock().await.insert(id.clone(), outbox.clone());
+        User::new(id, stream, outbox, subscription_manager, Arc::clone(self))
     }
 
     pub async fn remove_user(&self, id: &str) {
         self.users.lock().await.remove(id);
-        // Notify subscription manager that user left
-        if let Ok(mut manager) = self.subscription_manager.try_lock() {
-            if let Err(e) = manager.user_left(id).await {
-                eprintln!("Failed to notify subscription manager: {}", e);
-            }
-        }
     }
 
     pub async fn emit_to_user(&self, user_id: &str, message: crate::types::OutgoingMessage) -> anyhow::Result<()> {
-        if let Some(user) = self.users.lock().await.get_mut(user_id) {
-            user.emit(message).await
-        } else {
-            Err(anyhow::anyhow!("User not found: {}", user_id))
+        let json = serde_json::to_string(&message)?;
+        match self.users.lock().await.get(user_id) {
+            Some(outbox) => outbox.send(Message::Text(json.into())).map_err(|_| anyhow::anyhow!("User disconnected: {}", user_id)),
+            None => Err(anyhow::anyhow!("User not found: {}", user_id)),
         }
     }
 